@@ -45,15 +45,15 @@ impl GameExt for Game {
         tracing::trace!("Trying to get latest game version");
 
         // I assume game's API can't return incorrect version format right? Right?
-        Ok(Version::from_str(api::request(edition)?.data.game.latest.version).unwrap())
+        Ok(api::request(edition)?.data.game.latest.version.parse()?)
     }
 
     #[tracing::instrument(level = "debug", ret)]
     fn get_version(&self) -> anyhow::Result<Version> {
         tracing::debug!("Trying to get installed game version");
 
-        fn bytes_to_num(bytes: &Vec<u8>) -> u8 {
-            bytes.iter().fold(0u8, |acc, &x| acc * 10 + (x - b'0'))
+        fn bytes_to_num(bytes: &Vec<u8>) -> u32 {
+            bytes.iter().fold(0u32, |acc, &x| acc.saturating_mul(10).saturating_add((x - b'0') as u32))
         }
 
         let file = File::open(self.path.join(self.edition.data_folder()).join("globalgamemanagers"))?;
@@ -143,7 +143,7 @@ impl Game {
                         let latest = response.data.game.latest;
 
                         return Ok(VersionDiff::NotInstalled {
-                            latest: Version::from_str(&latest.version).unwrap(),
+                            latest: latest.version.parse::<Version>().unwrap(),
 
                             segments_uris: latest.segments.into_iter()
                                 .map(|segment| segment.path)
@@ -156,7 +156,8 @@ impl Game {
 
                             installation_path: Some(self.path.clone()),
                             version_file_path: None,
-                            temp_folder: None
+                            temp_folder: None,
+                            transactional: false
                         });
                     }
 
@@ -177,7 +178,7 @@ impl Game {
                         if diff.version == current {
                             return Ok(VersionDiff::Predownload {
                                 current,
-                                latest: Version::from_str(predownload.latest.version).unwrap(),
+                                latest: predownload.latest.version.parse::<Version>().unwrap(),
 
                                 uri: diff.path,
                                 edition: self.edition,
@@ -187,7 +188,8 @@ impl Game {
 
                                 installation_path: Some(self.path.clone()),
                                 version_file_path: None,
-                                temp_folder: None
+                                temp_folder: None,
+                                transactional: false
                             });
                         }
                     }
@@ -206,7 +208,7 @@ impl Game {
                     if diff.version == current {
                         return Ok(VersionDiff::Diff {
                             current,
-                            latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                            latest: response.data.game.latest.version.parse::<Version>().unwrap(),
 
                             uri: diff.path,
                             edition: self.edition,
@@ -216,14 +218,15 @@ impl Game {
 
                             installation_path: Some(self.path.clone()),
                             version_file_path: None,
-                            temp_folder: None
+                            temp_folder: None,
+                            transactional: false
                         });
                     }
                 }
 
                 Ok(VersionDiff::Outdated {
                     current,
-                    latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                    latest: response.data.game.latest.version.parse::<Version>().unwrap(),
                     edition: self.edition
                 })
             }
@@ -235,7 +238,7 @@ impl Game {
             let latest = response.data.game.latest;
 
             Ok(VersionDiff::NotInstalled {
-                latest: Version::from_str(&latest.version).unwrap(),
+                latest: latest.version.parse::<Version>().unwrap(),
 
                 segments_uris: latest.segments.into_iter()
                     .map(|segment| segment.path)
@@ -248,7 +251,8 @@ impl Game {
 
                 installation_path: Some(self.path.clone()),
                 version_file_path: None,
-                temp_folder: None
+                temp_folder: None,
+                transactional: false
             })
         }
     }
@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
-use std::os::unix::prelude::PermissionsExt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde::{Serialize, Deserialize};
 use thiserror::Error;
@@ -14,8 +15,12 @@ use crate::{
     installer::{
         downloader::{Downloader, DownloadingError},
         installer::Update as InstallerUpdate,
-        free_space,
-        archives::Archive
+        space::{self, SpaceRequirements},
+        event_log::{LoggedEvent, EventKind},
+        hooks::{self, InstallHooks},
+        archives::Archive,
+        transaction::Transaction,
+        permissions
     },
     external::hpatchz
 };
@@ -30,11 +35,41 @@ pub enum DiffUpdate {
     ApplyingHdiffProgress(u64, u64),
     ApplyingHdiffFinished,
 
+    /// Failed to apply hdiff patch, so the file is repaired instead
+    /// 
+    /// `(relative file path)`
+    RepairingFileStarted(PathBuf),
+
+    RepairingFileFinished,
+    RepairingFileError(String),
+
     RemovingOutdatedStarted,
     RemovingOutdatedProgress(u64, u64),
     RemovingOutdatedFinished
 }
 
+#[cfg(feature = "install")]
+impl LoggedEvent for DiffUpdate {
+    fn kind(&self) -> EventKind {
+        match self {
+            Self::InstallerUpdate(update) => update.kind(),
+
+            Self::ApplyingHdiffProgress(_, _) |
+            Self::RemovingOutdatedProgress(_, _) => EventKind::Progress,
+
+            Self::RepairingFileStarted(_) => EventKind::Retry,
+            Self::RepairingFileError(_) => EventKind::Error,
+
+            Self::CheckingFreeSpace(_) |
+            Self::ApplyingHdiffStarted |
+            Self::ApplyingHdiffFinished |
+            Self::RepairingFileFinished |
+            Self::RemovingOutdatedStarted |
+            Self::RemovingOutdatedFinished => EventKind::Phase
+        }
+    }
+}
+
 impl From<InstallerUpdate> for DiffUpdate {
     #[inline]
     fn from(update: InstallerUpdate) -> Self {
@@ -65,6 +100,19 @@ pub enum DiffDownloadingError {
     #[error("Failed to apply hdiff patch: {0}")]
     HdiffPatch(String),
 
+    /// Failed to unpack downloaded archive
+    #[error("Failed to unpack downloaded archive: {0}")]
+    Unpacking(String),
+
+    /// Failed to apply staged changes to the game folder.
+    /// In transactional mode all the changes are rolled back so the game folder is left untouched
+    #[error("Failed to apply changes: {0}")]
+    Transaction(String),
+
+    /// Installation was vetoed by one of its hooks
+    #[error("{0}")]
+    HookVetoed(String),
+
     /// Installation path wasn't specified. This could happen when you
     /// try to call `install` method on `VersionDiff` that was generated
     /// in `VoicePackage::list_latest`. This method couldn't know
@@ -108,7 +156,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     },
 
     /// Component should be updated before using it
@@ -131,7 +182,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     },
 
     /// Difference can't be calculated because installed version is too old
@@ -159,7 +213,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     }
 }
 
@@ -223,6 +280,42 @@ impl VersionDiff {
             }
         }
     }
+
+    /// Check if changes are staged and applied to the game folder
+    /// only when the whole update succeeded
+    /// 
+    /// Default is `false`, so changes are applied to the game folder directly
+    pub fn is_transactional(&self) -> bool {
+        match self {
+            // Can't be installed
+            Self::Latest { .. } |
+            Self::Outdated { .. } => false,
+
+            // Can be installed
+            Self::Predownload { transactional, .. } |
+            Self::Diff { transactional, .. } |
+            Self::NotInstalled { transactional, .. } => *transactional
+        }
+    }
+
+    /// Specify whether changes should be staged and applied
+    /// to the game folder only when the whole update succeeded
+    pub fn with_transactional(mut self, value: bool) -> Self {
+        match &mut self {
+            // Can't be installed
+            Self::Latest { .. } |
+            Self::Outdated { .. } => self,
+
+            // Can be installed
+            Self::Predownload { transactional, .. } |
+            Self::Diff { transactional, .. } |
+            Self::NotInstalled { transactional, .. } => {
+                *transactional = value;
+
+                self
+            }
+        }
+    }
 }
 
 impl VersionDiffExt for VersionDiff {
@@ -343,13 +436,25 @@ impl VersionDiffExt for VersionDiff {
         Ok(())
     }
 
-    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+    fn install_to_with_hooks(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        let result = self.install_hooked(path.as_ref(), hooks, updater);
+
+        if let Err(err) = &result {
+            hooks.on_failure(&err.to_string());
+        }
+
+        result
+    }
+}
+
+impl VersionDiff {
+    fn install_hooked(&self, path: &Path, hooks: &impl InstallHooks, updater: impl Fn(DiffUpdate) + Clone + Send + 'static) -> Result<(), DiffDownloadingError> {
         tracing::debug!("Installing version difference");
 
         let uris = match self {
             // Can't be installed
-            Self::Latest { .. } => return Err(Self::Error::AlreadyLatest),
-            Self::Outdated { .. } => return Err(Self::Error::Outdated),
+            Self::Latest { .. } => return Err(DiffDownloadingError::AlreadyLatest),
+            Self::Outdated { .. } => return Err(DiffDownloadingError::Outdated),
 
             // Can be installed
             Self::Predownload { uri, .. } |
@@ -358,55 +463,22 @@ impl VersionDiffExt for VersionDiff {
             Self::NotInstalled { segments_uris, .. } => segments_uris.to_owned()
         };
 
-        let path = path.as_ref().to_path_buf();
+        let path = path.to_path_buf();
         let temp_folder = self.temp_folder();
 
+        hooks::run("pre_download", || hooks.pre_download(&path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         let downloaded_size = self.downloaded_size().expect("Failed to retrieve downloaded size");
         let unpacked_size = self.unpacked_size().expect("Failed to retrieve unpacked size");
 
-        (updater)(DiffUpdate::CheckingFreeSpace(temp_folder.clone()));
-
-        // Check available free space for archive itself
-        let Some(space) = free_space::available(&temp_folder) else {
-            tracing::error!("Path is not mounted: {:?}", temp_folder);
-
-            return Err(DownloadingError::PathNotMounted(temp_folder).into());
-        };
-
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&temp_folder, &path) {
-            downloaded_size + unpacked_size
-        } else {
-            downloaded_size
-        };
-
-        if space < required {
-            tracing::error!("No free space available in the temp folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(temp_folder, required, space).into());
-        }
-
-        (updater)(DiffUpdate::CheckingFreeSpace(path.clone()));
-
-        // Check available free space for unpacked archive data
-        let Some(space) = free_space::available(&path) else {
-            tracing::error!("Path is not mounted: {:?}", &path);
-
-            return Err(DownloadingError::PathNotMounted(path.to_path_buf()).into());
-        };
-
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&path, &temp_folder) {
-            unpacked_size + downloaded_size
-        } else {
-            unpacked_size
-        };
+        (updater)(DiffUpdate::CheckingFreeSpace(temp_folder.to_path_buf()));
+        (updater)(DiffUpdate::CheckingFreeSpace(path.to_path_buf()));
 
-        if space < required {
-            tracing::error!("No free space available in the installation folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(path.to_path_buf(), required, space).into());
-        }
+        // Check available free space for the archive and its unpacked data
+        // using sizes reported by the API. More accurate check is performed
+        // and the space is reserved after downloading when we can read the archive's entries
+        SpaceRequirements::from_sizes(&temp_folder, &path, downloaded_size, unpacked_size).check()?;
 
         let mut current_downloaded = 0;
         let mut segments_names = Vec::new();
@@ -446,127 +518,197 @@ impl VersionDiffExt for VersionDiff {
         // Imitate Installer update message
         (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::DownloadingFinished));
 
+        hooks::run("post_download", || hooks.post_download(&temp_folder.join(&first_segment_name)))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
+        // In transactional mode changes are staged and applied
+        // to the game folder only if everything was successfully installed
+        let transaction = if self.is_transactional() {
+            Transaction::new(&path)
+        } else {
+            Transaction::direct(&path)
+        };
+
+        let mut transaction = transaction
+            .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+
         // Extract downloaded segments
         // Ctrl+C / Ctrl+V from the Installer. Not a good approach,
         // but current core library is somehow legacy as I already started work
         // on a full rewrite so this code won't stay here for always
-        match Archive::open(temp_folder.join(&first_segment_name)) {
-            Ok(mut archive) => {
-                // Temporary workaround as we can't get archive extraction process
-                // directly - we'll spawn it in another thread and check this archive entries appearance in the filesystem
-                let mut total = 0;
-
-                let entries = archive
-                    .get_entries()
-                    .expect("Failed to get archive entries");
-
-                for entry in &entries {
-                    total += entry.size.get_size();
-
-                    let path = path.join(&entry.name);
-
-                    // Failed to change permissions => likely patch-related file and was made by the sudo, so root
-                    #[allow(unused_must_use)]
-                    if std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).is_err() {
-                        // For weird reason we can delete files made by root, but can't modify their permissions
-                        // We're not checking its result because if it's error - then it's either couldn't be removed (which is not the case)
-                        // or the file doesn't exist, which we obviously can just ignore
-                        std::fs::remove_file(&path);
-                    }
+        let mut archive = match Archive::open(temp_folder.join(&first_segment_name)) {
+            Ok(archive) => archive,
+            Err(err) => {
+                (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingError(err.to_string())));
+
+                return Err(DiffDownloadingError::Unpacking(err.to_string()));
+            }
+        };
+
+        // Check space required by the downloaded archive's entries
+        (updater)(DiffUpdate::CheckingFreeSpace(path.clone()));
+
+        // Space reserved for the archive's entries, released as they're being extracted
+        // and kept for applying hdiff patches
+        let reservations = match space::estimate(&archive, &path, !transaction.is_direct()) {
+            Ok(requirements) => requirements.reserve()?,
+
+            Err(err) => {
+                tracing::warn!("Failed to estimate required free space: {err}");
+
+                Vec::new()
+            }
+        };
+
+        let reservations = Arc::new(reservations.into_iter()
+            .map(|reservation| {
+                let amount = reservation.amount();
+
+                (reservation, amount)
+            })
+            .collect::<Vec<_>>());
+
+        // Temporary workaround as we can't get archive extraction process
+        // directly - we'll spawn it in another thread and check this archive entries appearance in the filesystem
+        let entries = archive.get_entries()
+            .map_err(|err| DiffDownloadingError::Unpacking(err.to_string()))?;
+
+        let total = entries.iter()
+            .map(|entry| entry.size.get_size())
+            .sum::<u64>();
+
+        tracing::trace!("Extracting archive");
+
+        let extract_to = transaction.staging().to_path_buf();
+
+        hooks::run("pre_extract", || hooks.pre_extract(&extract_to))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
+        // Direct updates overwrite files of the game folder, so make sure all of them
+        // can be modified by the current user. Files of the previous installation
+        // could be made by root or marked as read-only
+        if transaction.is_direct() {
+            (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UpdatingPermissionsStarted(path.clone())));
+
+            let report = permissions::scan_files(&path, entries.iter().map(|entry| entry.name.as_str()))
+                .fix();
+
+            let entries_number = entries.len() as u64;
+
+            (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UpdatingPermissions(entries_number, entries_number)));
+
+            if !report.is_ok() {
+                for issue in &report.issues {
+                    tracing::error!("Can't modify game file: {issue}");
                 }
 
-                tracing::trace!("Extracting archive");
+                let message = format!("{} files can't be modified by the current user", report.issues.len());
 
-                let unpacking_path = path.clone();
-                let unpacking_updater = updater.clone();
+                (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UpdatingPermissionsError(report.issues)));
 
-                let handle_2 = std::thread::spawn(move || {
-                    let mut entries = entries.into_iter()
-                        .map(|entry| (unpacking_path.join(&entry.name), entry.size.get_size(), true))
-                        .collect::<Vec<_>>();
+                return Err(DiffDownloadingError::Unpacking(message));
+            }
 
-                    let mut unpacked = 0;
+            (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UpdatingPermissionsFinished));
+        }
 
-                    loop {
-                        std::thread::sleep(std::time::Duration::from_millis(250));
+        // Some entries could be skipped by the extraction (e.g. unsafe symlinks)
+        // so we can't wait until all of them appear in the filesystem
+        let unpacking_finished = Arc::new(AtomicBool::new(false));
+        let unpacking_finished_2 = unpacking_finished.clone();
 
-                        let mut empty = true;
+        let unpacking_path = extract_to.clone();
+        let unpacking_updater = updater.clone();
+        let unpacking_reservations = reservations.clone();
 
-                        for (path, size, remained) in &mut entries {
-                            if *remained {
-                                empty = false;
+        let handle_2 = std::thread::spawn(move || {
+            let mut entries = entries.into_iter()
+                .map(|entry| (unpacking_path.join(&entry.name), entry.size.get_size(), true))
+                .collect::<Vec<_>>();
 
-                                if std::path::Path::new(path).exists() {
-                                    *remained = false;
+            let mut unpacked = 0;
 
-                                    unpacked += *size;
-                                }
-                            }
-                        }
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(250));
+
+                let mut empty = true;
+
+                for (path, size, remained) in &mut entries {
+                    if *remained {
+                        empty = false;
 
-                        (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingProgress(unpacked, total)));
+                        if std::path::Path::new(path).exists() {
+                            *remained = false;
 
-                        if empty {
-                            break;
+                            unpacked += *size;
                         }
                     }
-                });
-
-                let unpacking_updater = updater.clone();
-                let extract_to = path.clone();
-
-                // Run archive extraction in another thread to not to freeze the current one
-                let handle_1 = std::thread::spawn(move || {
-                    (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingStarted(extract_to.clone())));
-
-                    // We have to create new instance of Archive here
-                    // because otherwise it may not work after get_entries method call
-                    match Archive::open(temp_folder.join(first_segment_name)) {
-                        Ok(mut archive) => match archive.extract(&extract_to) {
-                            Ok(_) => {
-                                // TODO error handling
-                                #[allow(unused_must_use)] {
-                                    for name in segments_names {
-                                        std::fs::remove_file(temp_folder.join(name));
-                                    }
-                                }
+                }
 
-                                (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingFinished));
-                            }
+                // Unpacked data already takes space on the disk
+                for (reservation, amount) in unpacking_reservations.iter() {
+                    reservation.shrink_to(amount.saturating_sub(unpacked));
+                }
 
-                            Err(err) => (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingError(err.to_string())))
-                        }
+                (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingProgress(unpacked, total)));
 
-                        Err(err) => (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingError(err.to_string())))
+                if empty || unpacking_finished_2.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+        });
+
+        let unpacking_updater = updater.clone();
+
+        // Run archive extraction in another thread to not to freeze the current one
+        let handle_1 = std::thread::spawn(move || {
+            (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingStarted(extract_to.clone())));
+
+            let result = archive.extract(&extract_to);
+
+            unpacking_finished.store(true, Ordering::Relaxed);
+
+            match result {
+                Ok(_) => {
+                    // TODO error handling
+                    #[allow(unused_must_use)] {
+                        for name in segments_names {
+                            std::fs::remove_file(temp_folder.join(name));
+                        }
                     }
-                });
 
-                handle_1.join().unwrap();
-                handle_2.join().unwrap();
-            }
+                    (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingFinished));
 
-            Err(err) => (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingError(err.to_string())))
-        }
+                    Ok(())
+                }
 
-        // Imitate Installer update message
-        (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingFinished));
+                Err(err) => {
+                    (unpacking_updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::UnpackingError(err.to_string())));
 
-        // Create `.version` file here even if hdiff patching is failed because
-        // it's easier to explain user why he should run files repairer than
-        // why he should re-download entire game update because something is failed
-        #[allow(unused_must_use)] {
-            let version_path = self.version_file_path()
-                .unwrap_or(path.join(".version"));
+                    Err(err)
+                }
+            }
+        });
 
-            std::fs::write(version_path, self.latest().version);
+        let result = handle_1.join().unwrap();
+
+        handle_2.join().unwrap();
+
+        if let Err(err) = result {
+            return Err(DiffDownloadingError::Unpacking(err.to_string()));
         }
 
+        let staging = transaction.staging().to_path_buf();
+
+        hooks::run("post_extract", || hooks.post_extract(&staging))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         // Apply hdiff patches
         // We're ignoring Err because in practice it means that hdifffiles.txt is missing
-        if let Ok(files) = std::fs::read_to_string(path.join("hdifffiles.txt")) {
+        if let Ok(files) = std::fs::read_to_string(staging.join("hdifffiles.txt")) {
             tracing::debug!("Applying hdiff patches");
 
-            (updater)(Self::Update::ApplyingHdiffStarted);
+            (updater)(DiffUpdate::ApplyingHdiffStarted);
 
             let files = files.lines().collect::<Vec<&str>>();
             let hdiffs = files.len() as u64;
@@ -575,23 +717,29 @@ impl VersionDiffExt for VersionDiff {
             for (i, file) in files.into_iter().enumerate() {
                 let relative_file = &file[16..file.len() - 2];
 
-                let file = path.join(relative_file);
-                let patch = path.join(format!("{relative_file}.hdiff"));
-                let output = path.join(format!("{relative_file}.hdiff_patched"));
+                // Patched files are written to the staging folder
+                // and will replace original ones on commit
+                let file = transaction.resolve(relative_file);
+                let patch = staging.join(format!("{relative_file}.hdiff"));
+                let output = staging.join(format!("{relative_file}.hdiff_patched"));
 
                 // If failed to apply the patch
                 if let Err(err) = hpatchz::patch(&file, &patch, &output) {
                     tracing::warn!("Failed to apply hdiff patch for {:?}: {err}", file);
                     tracing::debug!("Trying to repair corrupted file");
 
+                    (updater)(DiffUpdate::RepairingFileStarted(PathBuf::from(relative_file)));
+
                     // If we were able to get API response - it shouldn't be impossible
                     // to also get integrity files list from the same API
                     match super::repairer::try_get_integrity_file(self.edition(), relative_file, Some(*crate::REQUESTS_TIMEOUT)) {
                         Ok(Some(integrity)) => {
                             if !integrity.fast_verify(&path) {
-                                if let Err(err) = integrity.repair(&path) {
+                                if let Err(err) = integrity.repair(&staging) {
                                     tracing::error!("Failed to repair corrupted file: {err}");
 
+                                    (updater)(DiffUpdate::RepairingFileError(err.to_string()));
+
                                     return Err(err.into());
                                 }
                             }
@@ -600,69 +748,96 @@ impl VersionDiffExt for VersionDiff {
                         Ok(None) => {
                             tracing::error!("Failed to repair corrupted file: not found");
 
-                            return Err(Self::Error::HdiffPatch(err.to_string()))
+                            (updater)(DiffUpdate::RepairingFileError(String::from("File is not found in the integrity files list")));
+
+                            return Err(DiffDownloadingError::HdiffPatch(err.to_string()))
                         }
 
                         Err(repair_fail) => {
                             tracing::error!("Failed to repair corrupted file: {repair_fail}");
 
-                            return Err(Self::Error::HdiffPatch(err.to_string()))
+                            (updater)(DiffUpdate::RepairingFileError(repair_fail.to_string()));
+
+                            return Err(DiffDownloadingError::HdiffPatch(err.to_string()))
                         }
                     }
 
                     #[allow(unused_must_use)] {
                         std::fs::remove_file(&patch);
+                        std::fs::remove_file(&output);
                     }
+
+                    (updater)(DiffUpdate::RepairingFileFinished);
                 }
 
                 // If patch was successfully applied
                 else {
-                    // FIXME: handle errors properly
-                    std::fs::remove_file(&file)
-                        .expect(&format!("Failed to remove hdiff patch: {:?}", file));
-
                     std::fs::remove_file(&patch)
-                        .expect(&format!("Failed to remove hdiff patch: {:?}", patch));
+                        .map_err(|err| DiffDownloadingError::HdiffPatch(format!("Failed to remove hdiff patch {:?}: {err}", patch)))?;
 
-                    std::fs::rename(&output, &file)
-                        .expect(&format!("Failed to rename hdiff patch: {:?}", file));
+                    std::fs::rename(&output, staging.join(relative_file))
+                        .map_err(|err| DiffDownloadingError::HdiffPatch(format!("Failed to rename hdiff patch {:?}: {err}", output)))?;
                 }
 
-                (updater)(Self::Update::ApplyingHdiffProgress(i as u64 + 1, hdiffs));
+                (updater)(DiffUpdate::ApplyingHdiffProgress(i as u64 + 1, hdiffs));
             }
 
-            std::fs::remove_file(path.join("hdifffiles.txt"))
-                .expect("Failed to remove hdifffiles.txt");
+            std::fs::remove_file(staging.join("hdifffiles.txt"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
 
-            (updater)(Self::Update::ApplyingHdiffFinished);
+            (updater)(DiffUpdate::ApplyingHdiffFinished);
         }
 
         tracing::debug!("Deleting outdated files");
 
         // Remove outdated files
         // We're ignoring Err because in practice it means that deletefiles.txt is missing
-        if let Ok(files) = std::fs::read_to_string(path.join("deletefiles.txt")) {
+        if let Ok(files) = std::fs::read_to_string(staging.join("deletefiles.txt")) {
             let files = files.lines().collect::<Vec<&str>>();
             let files_len = files.len() as u64;
 
-            (updater)(Self::Update::RemovingOutdatedStarted);
+            (updater)(DiffUpdate::RemovingOutdatedStarted);
 
             // AnimeGame_Data/Plugins/metakeeper.dll
             for (i, file) in files.into_iter().enumerate() {
-                let file = path.join(file);
-
-                std::fs::remove_file(&file)
-                    .expect(&format!("Failed to remove outdated file: {:?}", file));
+                transaction.remove(file);
 
-                (updater)(Self::Update::RemovingOutdatedProgress(i as u64 + 1, files_len));
+                (updater)(DiffUpdate::RemovingOutdatedProgress(i as u64 + 1, files_len));
             }
 
-            std::fs::remove_file(path.join("deletefiles.txt"))
-                .expect("Failed to remove deletefiles.txt");
+            std::fs::remove_file(staging.join("deletefiles.txt"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+
+            (updater)(DiffUpdate::RemovingOutdatedFinished);
+        }
+
+        // `.version` file is committed together with the game files
+        // so it's never updated if the game files are not
+        let version_path = self.version_file_path();
+
+        if version_path.is_none() {
+            self.latest().write_file(staging.join(".version"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+        }
+
+        (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingStarted(path.to_path_buf())));
+
+        if let Err(err) = transaction.commit() {
+            (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingError(err.to_string())));
 
-            (updater)(Self::Update::RemovingOutdatedFinished);
+            return Err(DiffDownloadingError::Transaction(err.to_string()));
         }
 
+        (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingFinished));
+
+        if let Some(version_path) = version_path {
+            self.latest().write_file(version_path)
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+        }
+
+        hooks::run("post_patch", || hooks.post_patch(&path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         Ok(())
     }
 }
@@ -42,15 +42,15 @@ impl GameExt for Game {
         tracing::trace!("Trying to get latest game version");
 
         // I assume game's API can't return incorrect version format right? Right?
-        Ok(Version::from_str(api::request(edition)?.data.game.latest.version).unwrap())
+        Ok(api::request(edition)?.data.game.latest.version.parse()?)
     }
 
     #[tracing::instrument(level = "debug", ret)]
     fn get_version(&self) -> anyhow::Result<Version> {
         tracing::debug!("Trying to get installed game version");
 
-        fn bytes_to_num(bytes: &Vec<u8>) -> u8 {
-            bytes.iter().fold(0u8, |acc, &x| acc * 10 + (x - '0' as u8))
+        fn bytes_to_num(bytes: &Vec<u8>) -> u32 {
+            bytes.iter().fold(0u32, |acc, &x| acc.saturating_mul(10).saturating_add((x - b'0') as u32))
         }
 
         let file = File::open(self.path.join(self.edition.data_folder()).join("globalgamemanagers"))?;
@@ -129,7 +129,7 @@ impl Game {
 
                 Ok(VersionDiff::Diff {
                     current,
-                    latest: Version::from_str(latest.version).unwrap(),
+                    latest: latest.version.parse::<Version>().unwrap(),
                     url: latest.path,
 
                     downloaded_size: latest.package_size.parse::<u64>().unwrap(),
@@ -146,7 +146,7 @@ impl Game {
             tracing::debug!("Game is not installed");
 
             Ok(VersionDiff::NotInstalled {
-                latest: Version::from_str(&latest.version).unwrap(),
+                latest: latest.version.parse::<Version>().unwrap(),
                 url: latest.path,
 
                 downloaded_size: latest.package_size.parse::<u64>().unwrap(),
@@ -1,11 +1,20 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use cached::proc_macro::cached;
 
 use super::api;
 use super::consts::GameEdition;
 
-use crate::repairer::IntegrityFile;
+use crate::repairer::{
+    IntegrityFile,
+    PkgVersion,
+    VerificationReport,
+    SkipRules,
+    VerifyOptions,
+    VerifyUpdate,
+    verify_report,
+    pkg_version
+};
 
 fn try_get_some_integrity_files<T: AsRef<str>>(game_edition: GameEdition, file_name: T, timeout: Option<u64>) -> anyhow::Result<Vec<IntegrityFile>> {
     let decompressed_path = api::request(game_edition)?.data.game.latest.decompressed_path;
@@ -14,20 +23,13 @@ fn try_get_some_integrity_files<T: AsRef<str>>(game_edition: GameEdition, file_n
         .with_timeout(timeout.unwrap_or(*crate::REQUESTS_TIMEOUT))
         .send()?;
 
-    let mut files = Vec::new();
+    let (pkg_version, errors) = PkgVersion::parse_lenient(&String::from_utf8_lossy(pkg_version.as_bytes()));
 
-    for line in String::from_utf8_lossy(pkg_version.as_bytes()).lines() {
-        if let Ok(value) = serde_json::from_str::<serde_json::Value>(line) {
-            files.push(IntegrityFile {
-                path: PathBuf::from(value["remoteName"].as_str().unwrap()),
-                md5: value["md5"].as_str().unwrap().to_string(),
-                size: value["fileSize"].as_u64().unwrap(),
-                base_url: decompressed_path.clone()
-            });
-        }
+    for err in errors {
+        tracing::warn!("{err}");
     }
 
-    Ok(files)
+    Ok(pkg_version.to_integrity_files(decompressed_path))
 }
 
 /// Try to list latest game files
@@ -55,22 +57,69 @@ pub fn try_get_integrity_file<T: Into<PathBuf>>(game_edition: GameEdition, relat
     Ok(None)
 }
 
+/// Get rules for files and folders created by the game itself
+pub fn default_skip_rules() -> SkipRules {
+    SkipRules::from_globs(&[
+        "webCaches",
+        "SDKCaches",
+        "ScreenShot"
+    ])
+}
+
 /// Try to get list of files that are not more used by the game and can be deleted
 /// 
 /// ⚠️ Be aware that the game can create its own files after downloading, so "unused files" may not be really unused.
 /// It's strongly recommended to use this function only with manual control from user's side, in example to show him
 /// paths to these files and let him choose what to do with them
-pub fn try_get_unused_files<T: Into<PathBuf>>(game_edition: GameEdition, game_dir: T, timeout: Option<u64>) -> anyhow::Result<Vec<PathBuf>> {
+/// 
+/// `skip_rules` are used in addition to `default_skip_rules`
+pub fn try_get_unused_files<T: Into<PathBuf>>(game_edition: GameEdition, game_dir: T, skip_rules: &SkipRules, timeout: Option<u64>) -> anyhow::Result<Vec<PathBuf>> {
     let used_files = try_get_integrity_files(game_edition, timeout)?
         .into_iter()
         .map(|file| file.path)
         .collect::<Vec<PathBuf>>();
 
-    let skip_names = [
-        String::from("webCaches"),
-        String::from("SDKCaches"),
-        String::from("ScreenShot"),
-    ];
+    let skip_rules = default_skip_rules().merge(skip_rules);
+
+    crate::repairer::try_get_unused_files(game_dir, used_files, &skip_rules)
+}
+
+/// Verify game files and find extraneous ones
+pub fn try_get_verification_report(
+    game_edition: GameEdition,
+    game_dir: impl AsRef<Path>,
+    skip_rules: &SkipRules,
+    options: VerifyOptions,
+    timeout: Option<u64>,
+    updater: impl Fn(VerifyUpdate)
+) -> anyhow::Result<VerificationReport> {
+    let game_dir = game_dir.as_ref();
+
+    let mut report = verify_report(game_dir, try_get_integrity_files(game_edition, timeout)?, options, updater);
+
+    report.find_extraneous(game_dir, &default_skip_rules().merge(skip_rules))?;
+
+    Ok(report)
+}
+
+/// Hash files of the local game installation and write `pkg_version` manifest for them
+/// 
+/// `skip_rules` are used in addition to `default_skip_rules`.
+/// `progress` is called with `(hashed bytes, total bytes)`
+/// 
+/// Written manifest can be read by `PkgVersion` and converted to integrity files
+pub fn generate_pkg_version(
+    game_dir: impl AsRef<Path>,
+    skip_rules: &SkipRules,
+    threads: usize,
+    progress: impl Fn(u64, u64)
+) -> anyhow::Result<()> {
+    let game_dir = game_dir.as_ref();
+
+    let files = pkg_version::list_files(game_dir, &default_skip_rules().merge(skip_rules))?;
+    let entries = pkg_version::generate_entries(game_dir, files, threads, progress)?;
+
+    PkgVersion::new(entries).write(game_dir.join(pkg_version::PKG_VERSION_NAME))?;
 
-    crate::repairer::try_get_unused_files(game_dir, used_files, skip_names)
+    Ok(())
 }
@@ -11,9 +11,11 @@ use crate::installer::{
     downloader::{Downloader, DownloadingError},
     installer::{
         Installer,
+        InstallerError,
         Update as InstallerUpdate
     },
-    free_space
+    space::SpaceRequirements,
+    hooks::{self, InstallHooks}
 };
 
 #[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +28,14 @@ pub enum DiffDownloadingError {
     #[error("{0}")]
     DownloadingError(#[from] DownloadingError),
 
+    /// Failed to unpack downloaded archive
+    #[error("Failed to unpack downloaded archive: {0}")]
+    Unpacking(String),
+
+    /// Installation was vetoed by one of its hooks
+    #[error("{0}")]
+    HookVetoed(String),
+
     /// Installation path wasn't specified. This could happen when you
     /// try to call `install` method on `VersionDiff` that was generated
     /// in `VoicePackage::list_latest`. This method couldn't know
@@ -41,6 +51,19 @@ impl From<minreq::Error> for DiffDownloadingError {
     }
 }
 
+impl From<InstallerError> for DiffDownloadingError {
+    fn from(error: InstallerError) -> Self {
+        match error {
+            InstallerError::Downloading(err) => Self::DownloadingError(err),
+            InstallerError::HookVetoed(err) => Self::HookVetoed(err),
+            InstallerError::Unpacking(err) => Self::Unpacking(err),
+
+            // Installer is not transactional
+            InstallerError::Transaction(err) => Self::Unpacking(err)
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VersionDiff {
     /// Latest version
@@ -234,10 +257,20 @@ impl VersionDiffExt for VersionDiff {
         Ok(())
     }
 
-    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
-        tracing::debug!("Installing version difference");
+    fn install_to_with_hooks(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        let result = self.install_hooked(path.as_ref(), hooks, updater);
 
-        let path = path.as_ref();
+        if let Err(err) = &result {
+            hooks.on_failure(&err.to_string());
+        }
+
+        result
+    }
+}
+
+impl VersionDiff {
+    fn install_hooked(&self, path: &Path, hooks: &impl InstallHooks, updater: impl Fn(InstallerUpdate) + Clone + Send + 'static) -> Result<(), DiffDownloadingError> {
+        tracing::debug!("Installing version difference");
 
         let url = self.downloading_uri().expect("Failed to retreive downloading url");
         let downloaded_size = self.downloaded_size().expect("Failed to retreive downloaded size");
@@ -247,57 +280,23 @@ impl VersionDiffExt for VersionDiff {
             // Set custom temp folder location
             .with_temp_folder(self.temp_folder())
 
-            // Don't perform space checks in the Installer because we're doing it here
-            .with_free_space_check(false);
+            // Check space required by the downloaded archive's entries
+            .with_free_space_check(true);
 
         (updater)(InstallerUpdate::CheckingFreeSpace(installer.temp_folder.to_path_buf()));
-
-        // Check available free space for archive itself
-        let Some(space) = free_space::available(&installer.temp_folder) else {
-            tracing::error!("Path is not mounted: {:?}", installer.temp_folder);
-
-            return Err(DownloadingError::PathNotMounted(installer.temp_folder).into());
-        };
-
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&installer.temp_folder, path) {
-            downloaded_size + unpacked_size
-        } else {
-            downloaded_size
-        };
-
-        if space < required {
-            tracing::error!("No free space available in the temp folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(installer.temp_folder, required, space).into());
-        }
-
         (updater)(InstallerUpdate::CheckingFreeSpace(path.to_path_buf()));
 
-        // Check available free space for unpacked archvie data
-        let Some(space) = free_space::available(&path) else {
-            tracing::error!("Path is not mounted: {:?}", &path);
-
-            return Err(DownloadingError::PathNotMounted(path.to_path_buf()).into());
-        };
-
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&path, &installer.temp_folder) {
-            unpacked_size + downloaded_size
-        } else {
-            unpacked_size
-        };
-
-        if space < required {
-            tracing::error!("No free space available in the installation folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(path.to_path_buf(), required, space).into());
-        }
+        // Check available free space for the archive and its unpacked data
+        // using sizes reported by the API. More accurate check is performed
+        // after downloading when we can read the archive's entries
+        SpaceRequirements::from_sizes(&installer.temp_folder, &path, downloaded_size, unpacked_size).check()?;
 
         // Install data
         let installer_updater = updater.clone();
 
-        installer.install(path, move |update| (installer_updater)(update));
+        // `post_patch` hook is called here when the `.version` file is written,
+        // and `on_failure` hook is called by `install_to_with_hooks`
+        installer.install_unpatched(path, hooks, move |update| (installer_updater)(update))?;
 
         // Create `.version` file here even if hdiff patching is failed because
         // it's easier to explain user why he should run files repairer than
@@ -306,9 +305,12 @@ impl VersionDiffExt for VersionDiff {
             let version_path = self.version_file_path()
                 .unwrap_or_else(|| path.join(".version"));
 
-            std::fs::write(version_path, self.latest().version);
+            self.latest().write_file(version_path);
         }
 
+        hooks::run("post_patch", || hooks.post_patch(path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         Ok(())
     }
 }
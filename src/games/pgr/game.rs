@@ -2,10 +2,9 @@ use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use md5::{Md5, Digest};
-
 use crate::version::Version;
 use crate::traits::game::GameExt;
+use crate::repairer::hash;
 
 use super::api;
 use super::consts::*;
@@ -46,15 +45,15 @@ impl GameExt for Game {
         tracing::trace!("Trying to get latest game version");
 
         // I assume game's API can't return incorrect version format right? Right?
-        Ok(Version::from_str(api::game::request()?.default.version).unwrap())
+        Ok(api::game::request()?.default.version.parse()?)
     }
 
     #[tracing::instrument(level = "debug", ret)]
     fn get_version(&self) -> anyhow::Result<Version> {
         tracing::debug!("Trying to get installed game version");
 
-        fn bytes_to_num(bytes: &Vec<u8>) -> u8 {
-            bytes.iter().fold(0u8, |acc, &x| acc * 10 + (x - '0' as u8))
+        fn bytes_to_num(bytes: &Vec<u8>) -> u32 {
+            bytes.iter().fold(0u32, |acc, &x| acc.saturating_mul(10).saturating_add((x - b'0') as u32))
         }
 
         let file = File::open(self.path.join(DATA_FOLDER_NAME).join("globalgamemanagers"))?;
@@ -143,7 +142,7 @@ impl Game {
                 else if let Ok(metadata) = file_path.metadata() {
                     // And compare updated file size with downloaded one. If they're equal,
                     // then as well compare their md5 hashes if fast_verify = false
-                    if metadata.len() != file.size || (!fast_verify && format!("{:x}", Md5::digest(std::fs::read(file_path)?)).to_ascii_lowercase() != file.md5.to_ascii_lowercase()) {
+                    if metadata.len() != file.size || (!fast_verify && !hash::md5_file(file_path)?.eq_ignore_ascii_case(&file.md5)) {
                         files.push(file.dest.clone());
 
                         // Add only files difference in size to the total download size
@@ -167,7 +166,7 @@ impl Game {
         let latest = api::game::request()?.default;
 
         if let Ok(current) = self.get_version() {
-            if current >= Version::from_str(&latest.version).unwrap() {
+            if current >= latest.version.parse::<Version>().unwrap() {
                 tracing::debug!("Game version is latest");
 
                 Ok(VersionDiff::Latest(current))
@@ -180,7 +179,7 @@ impl Game {
 
                 Ok(VersionDiff::Outdated {
                     current,
-                    latest: Version::from_str(latest.version).unwrap(),
+                    latest: latest.version.parse::<Version>().unwrap(),
 
                     unpacked_url: format!("{API_BASE_URI}/{}", latest.resourcesBasePath),
                     files,
@@ -200,7 +199,7 @@ impl Game {
             let (files, total_size) = get_files(&self.path, self.fast_verify)?;
 
             Ok(VersionDiff::NotInstalled {
-                latest: Version::from_str(&latest.version).unwrap(),
+                latest: latest.version.parse::<Version>().unwrap(),
 
                 unpacked_url: format!("{API_BASE_URI}/{}", latest.resourcesBasePath),
                 files,
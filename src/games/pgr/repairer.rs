@@ -1,11 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use cached::proc_macro::cached;
 
 use super::api;
 use super::consts::API_BASE_URI;
 
-use crate::repairer::IntegrityFile;
+use crate::repairer::{
+    IntegrityFile,
+    VerificationReport,
+    SkipRules,
+    VerifyOptions,
+    VerifyUpdate,
+    verify_report
+};
 
 /// Try to list latest game files
 #[cached(result)]
@@ -42,3 +49,19 @@ pub fn try_get_integrity_file<T: Into<PathBuf>>(relative_path: T) -> anyhow::Res
 
     Ok(None)
 }
+
+/// Verify game files and find extraneous ones
+pub fn try_get_verification_report(
+    game_dir: impl AsRef<Path>,
+    skip_rules: &SkipRules,
+    options: VerifyOptions,
+    updater: impl Fn(VerifyUpdate)
+) -> anyhow::Result<VerificationReport> {
+    let game_dir = game_dir.as_ref();
+
+    let mut report = verify_report(game_dir, try_get_integrity_files()?, options, updater);
+
+    report.find_extraneous(game_dir, skip_rules)?;
+
+    Ok(report)
+}
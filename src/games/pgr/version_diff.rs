@@ -14,7 +14,8 @@ use crate::traits::version_diff::VersionDiffExt;
 #[cfg(feature = "install")]
 use crate::installer::{
     downloader::DownloadingError,
-    free_space
+    free_space,
+    hooks::{self, InstallHooks}
 };
 
 #[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -27,6 +28,10 @@ pub enum DiffDownloadingError {
     #[error("{0}")]
     DownloadingError(#[from] DownloadingError),
 
+    /// Installation was vetoed by one of its hooks
+    #[error("{0}")]
+    HookVetoed(String),
+
     /// Installation path wasn't specified. This could happen when you
     /// try to call `install` method on `VersionDiff` that was generated
     /// in `VoicePackage::list_latest`. This method couldn't know
@@ -212,30 +217,35 @@ impl VersionDiffExt for VersionDiff {
         unimplemented!()
     }
 
-    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
-        tracing::debug!("Installing version difference");
+    fn install_to_with_hooks(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        let result = self.install_hooked(path.as_ref(), hooks, updater);
 
-        let path = path.as_ref();
+        if let Err(err) = &result {
+            hooks.on_failure(&err.to_string());
+        }
+
+        result
+    }
+}
+
+impl VersionDiff {
+    fn install_hooked(&self, path: &Path, hooks: &impl InstallHooks, updater: impl Fn(InstallerUpdate) + Clone + Send + 'static) -> Result<(), DiffDownloadingError> {
+        tracing::debug!("Installing version difference");
 
         let url = self.downloading_uri().expect("Failed to retreive downloading url");
         let required = self.unpacked_size().expect("Failed to retreive total size");
         let files = self.files().expect("Failed to retreive list of files for downloading");
         let threads = self.threads().expect("Failed to retreive amount of threads");
 
-        (updater)(InstallerUpdate::CheckingFreeSpace(path.to_path_buf()));
-
-        // Check available free space
-        let Some(space) = free_space::available(&path) else {
-            tracing::error!("Path is not mounted: {:?}", &path);
-
-            return Err(DownloadingError::PathNotMounted(path.to_path_buf()).into());
-        };
+        hooks::run("pre_download", || hooks.pre_download(path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
 
-        if space < required {
-            tracing::error!("No free space available in the installation folder. Required: {required}. Available: {space}");
+        (updater)(InstallerUpdate::CheckingFreeSpace(path.to_path_buf()));
 
-            return Err(DownloadingError::NoSpaceAvailable(path.to_path_buf(), required, space).into());
-        }
+        // Check available free space and reserve it, releasing as the files are downloaded
+        let reservation = free_space::reserve(path, required).inspect_err(|err| {
+            tracing::error!("No free space available in the installation folder: {err}");
+        })?;
 
         // Download updated files
         let mut downloaded = 0;
@@ -297,6 +307,8 @@ impl VersionDiffExt for VersionDiff {
         while let Ok(size) = recv.recv() {
             downloaded += size;
 
+            reservation.shrink_to(required.saturating_sub(downloaded));
+
             (updater)(InstallerUpdate::DownloadingProgress(downloaded, required));
         }
 
@@ -314,11 +326,14 @@ impl VersionDiffExt for VersionDiff {
             let version_path = self.version_file_path()
                 .unwrap_or_else(|| path.join(".version"));
 
-            std::fs::write(version_path, self.latest().version);
+            self.latest().write_file(version_path);
         }
 
         (updater)(InstallerUpdate::DownloadingFinished);
 
+        hooks::run("post_patch", || hooks.post_patch(path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         Ok(())
     }
 }
@@ -45,15 +45,15 @@ impl GameExt for Game {
         tracing::trace!("Trying to get latest game version");
 
         // I assume game's API can't return incorrect version format right? Right?
-        Ok(Version::from_str(api::request(edition)?.data.game.latest.version).unwrap())
+        Ok(api::request(edition)?.data.game.latest.version.parse()?)
     }
 
     #[tracing::instrument(level = "debug", ret)]
     fn get_version(&self) -> anyhow::Result<Version> {
         tracing::debug!("Trying to get installed game version");
 
-        fn bytes_to_num(bytes: &Vec<u8>) -> u8 {
-            bytes.iter().fold(0u8, |acc, &x| acc * 10 + (x - '0' as u8))
+        fn bytes_to_num(bytes: &Vec<u8>) -> u32 {
+            bytes.iter().fold(0u32, |acc, &x| acc.saturating_mul(10).saturating_add((x - b'0') as u32))
         }
 
         let file = File::open(self.path.join(self.edition.data_folder()).join("data.unity3d"))?;
@@ -145,7 +145,7 @@ impl Game {
                         let latest = response.data.game.latest;
 
                         return Ok(VersionDiff::NotInstalled {
-                            latest: Version::from_str(&latest.version).unwrap(),
+                            latest: latest.version.parse::<Version>().unwrap(),
                             uri: latest.path,
                             edition: self.edition,
 
@@ -154,7 +154,8 @@ impl Game {
 
                             installation_path: Some(self.path.clone()),
                             version_file_path: None,
-                            temp_folder: None
+                            temp_folder: None,
+                            transactional: false
                         });
                     }
 
@@ -175,7 +176,7 @@ impl Game {
                         if diff.version == current {
                             return Ok(VersionDiff::Predownload {
                                 current,
-                                latest: Version::from_str(predownload.latest.version).unwrap(),
+                                latest: predownload.latest.version.parse::<Version>().unwrap(),
 
                                 uri: diff.path,
                                 edition: self.edition,
@@ -185,7 +186,8 @@ impl Game {
 
                                 installation_path: Some(self.path.clone()),
                                 version_file_path: None,
-                                temp_folder: None
+                                temp_folder: None,
+                                transactional: false
                             });
                         }
                     }
@@ -204,7 +206,7 @@ impl Game {
                     if diff.version == current {
                         return Ok(VersionDiff::Diff {
                             current,
-                            latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                            latest: response.data.game.latest.version.parse::<Version>().unwrap(),
 
                             uri: diff.path,
                             edition: self.edition,
@@ -214,14 +216,15 @@ impl Game {
 
                             installation_path: Some(self.path.clone()),
                             version_file_path: None,
-                            temp_folder: None
+                            temp_folder: None,
+                            transactional: false
                         });
                     }
                 }
 
                 Ok(VersionDiff::Outdated {
                     current,
-                    latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                    latest: response.data.game.latest.version.parse::<Version>().unwrap(),
                     edition: self.edition
                 })
             }
@@ -233,7 +236,7 @@ impl Game {
             let latest = response.data.game.latest;
 
             Ok(VersionDiff::NotInstalled {
-                latest: Version::from_str(&latest.version).unwrap(),
+                latest: latest.version.parse::<Version>().unwrap(),
                 uri: latest.path,
                 edition: self.edition,
 
@@ -242,7 +245,8 @@ impl Game {
 
                 installation_path: Some(self.path.clone()),
                 version_file_path: None,
-                temp_folder: None
+                temp_folder: None,
+                transactional: false
             })
         }
     }
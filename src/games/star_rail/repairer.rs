@@ -1,12 +1,35 @@
 use std::path::{Path, PathBuf};
 
+use serde::{Serialize, Deserialize};
+
 use cached::proc_macro::cached;
 
 use super::api;
-use super::consts::GameEdition;
+use super::consts::{GameEdition, get_voice_package_path};
+use super::game::Game;
 use super::voice_data::locale::VoiceLocale;
+use super::voice_data::package::VoicePackage;
+
+use crate::traits::prelude::*;
+
+use crate::repairer::{
+    IntegrityFile,
+    PkgVersion,
+    VerificationReport,
+    SkipRules,
+    VerifyOptions,
+    VerifyUpdate,
+    verify_report,
+    pkg_version
+};
 
-use crate::repairer::IntegrityFile;
+use crate::repairer::installation::{
+    InstallationUpdate,
+    InstallationReport,
+    repair_components
+};
+
+use crate::repairer::source::{RepairSource, CdnSource};
 
 fn try_get_some_integrity_files<T: AsRef<str>>(game_edition: GameEdition, file_name: T, timeout: Option<u64>) -> anyhow::Result<Vec<IntegrityFile>> {
     let decompressed_path = api::request(game_edition)?.data.game.latest.decompressed_path;
@@ -15,20 +38,13 @@ fn try_get_some_integrity_files<T: AsRef<str>>(game_edition: GameEdition, file_n
         .with_timeout(timeout.unwrap_or(*crate::REQUESTS_TIMEOUT))
         .send()?;
 
-    let mut files = Vec::new();
+    let (pkg_version, errors) = PkgVersion::parse_lenient(&String::from_utf8_lossy(pkg_version.as_bytes()));
 
-    for line in String::from_utf8_lossy(pkg_version.as_bytes()).lines() {
-        if let Ok(value) = serde_json::from_str::<serde_json::Value>(line) {
-            files.push(IntegrityFile {
-                path: PathBuf::from(value["remoteName"].as_str().unwrap()),
-                md5: value["md5"].as_str().unwrap().to_string(),
-                size: value["fileSize"].as_u64().unwrap(),
-                base_url: decompressed_path.clone()
-            });
-        }
+    for err in errors {
+        tracing::warn!("{err}");
     }
 
-    Ok(files)
+    Ok(pkg_version.to_integrity_files(decompressed_path))
 }
 
 /// Try to list latest game files
@@ -62,23 +78,151 @@ pub fn try_get_integrity_file(game_edition: GameEdition, relative_path: impl AsR
     Ok(None)
 }
 
+/// Get rules for files and folders created by the game itself
+pub fn default_skip_rules() -> SkipRules {
+    SkipRules::from_globs(&[
+        "webCaches",
+        "SDKCaches",
+        "GeneratedSoundBanks",
+        "ScreenShot"
+    ])
+}
+
 /// Try to get list of files that are not more used by the game and can be deleted
 /// 
 /// ⚠️ Be aware that the game can create its own files after downloading, so "unused files" may not be really unused.
 /// It's strongly recommended to use this function only with manual control from user's side, in example to show him
 /// paths to these files and let him choose what to do with them
-pub fn try_get_unused_files<T: Into<PathBuf>>(game_edition: GameEdition, game_dir: T, timeout: Option<u64>) -> anyhow::Result<Vec<PathBuf>> {
+/// 
+/// `skip_rules` are used in addition to `default_skip_rules`
+pub fn try_get_unused_files<T: Into<PathBuf>>(game_edition: GameEdition, game_dir: T, skip_rules: &SkipRules, timeout: Option<u64>) -> anyhow::Result<Vec<PathBuf>> {
     let used_files = try_get_integrity_files(game_edition, timeout)?
         .into_iter()
         .map(|file| file.path)
         .collect::<Vec<PathBuf>>();
 
-    let skip_names = [
-        String::from("webCaches"),
-        String::from("SDKCaches"),
-        String::from("GeneratedSoundBanks"),
-        String::from("ScreenShot"),
-    ];
+    let skip_rules = default_skip_rules().merge(skip_rules);
+
+    crate::repairer::try_get_unused_files(game_dir, used_files, &skip_rules)
+}
+
+/// Verify game and voice package files and find extraneous ones
+/// 
+/// Voice packages of the given locales are verified, files of other
+/// installed locales are reported as extraneous
+pub fn try_get_verification_report(
+    game_edition: GameEdition,
+    game_dir: impl AsRef<Path>,
+    locales: impl IntoIterator<Item = VoiceLocale>,
+    skip_rules: &SkipRules,
+    options: VerifyOptions,
+    timeout: Option<u64>,
+    updater: impl Fn(VerifyUpdate)
+) -> anyhow::Result<VerificationReport> {
+    let game_dir = game_dir.as_ref();
+
+    let mut files = try_get_integrity_files(game_edition, timeout)?;
+
+    for locale in locales {
+        files.extend(try_get_voice_integrity_files(game_edition, locale, timeout)?);
+    }
+
+    let mut report = verify_report(game_dir, files, options, updater);
+
+    report.find_extraneous(game_dir, &default_skip_rules().merge(skip_rules))?;
+
+    Ok(report)
+}
+
+/// Hash files of the local game installation and write `pkg_version` manifests for them
+/// 
+/// Files of the voice packages are listed in the `Audio_<locale>_pkg_version` manifests.
+/// `skip_rules` are used in addition to `default_skip_rules`.
+/// `progress` is called with `(hashed bytes, total bytes)`
+/// 
+/// Written manifests can be read by `PkgVersion` and converted to integrity files
+pub fn generate_pkg_versions(
+    game_edition: GameEdition,
+    game_dir: impl AsRef<Path>,
+    skip_rules: &SkipRules,
+    threads: usize,
+    progress: impl Fn(u64, u64)
+) -> anyhow::Result<()> {
+    let game_dir = game_dir.as_ref();
+
+    let files = pkg_version::list_files(game_dir, &default_skip_rules().merge(skip_rules))?;
+    let mut entries = pkg_version::generate_entries(game_dir, files, threads, progress)?;
+
+    for locale in VoiceLocale::list() {
+        let voice_dir = get_voice_package_path(game_dir, game_edition, *locale);
+
+        let Ok(voice_dir) = voice_dir.strip_prefix(game_dir) else {
+            continue;
+        };
+
+        let (voice_entries, game_entries): (Vec<_>, Vec<_>) = entries.into_iter()
+            .partition(|entry| Path::new(&entry.remote_name).starts_with(voice_dir));
+
+        entries = game_entries;
+
+        if !voice_entries.is_empty() {
+            PkgVersion::new(voice_entries)
+                .write(game_dir.join(format!("Audio_{}_pkg_version", locale.to_folder())))?;
+        }
+    }
+
+    PkgVersion::new(entries).write(game_dir.join(pkg_version::PKG_VERSION_NAME))?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Component {
+    Game,
+    Voice(VoiceLocale)
+}
+
+/// Verify and repair the game with all its installed voice packages
+/// 
+/// Broken files are downloaded from the game's CDN
+#[inline]
+pub fn repair_installation(game: &Game, options: VerifyOptions, updater: impl Fn(InstallationUpdate)) -> anyhow::Result<InstallationReport<Component>> {
+    repair_installation_from(game, options, vec![Box::new(CdnSource::new())], updater)
+}
+
+/// Verify and repair the game with all its installed voice packages
+/// 
+/// Files of the game and voice packages are verified together, and broken ones
+/// are taken from the first source which has their copy. Result is reported per component
+#[tracing::instrument(level = "debug", skip(sources, updater))]
+pub fn repair_installation_from(
+    game: &Game,
+    options: VerifyOptions,
+    sources: Vec<Box<dyn RepairSource>>,
+    updater: impl Fn(InstallationUpdate)
+) -> anyhow::Result<InstallationReport<Component>> {
+    let edition = game.edition();
+
+    let mut components = vec![(Component::Game, try_get_integrity_files(edition, None)?)];
+
+    let packages = match game.get_voice_packages() {
+        Ok(packages) => packages,
+
+        // Voice packages folder doesn't exist if there's no installed voice packages
+        Err(err) if err.downcast_ref::<std::io::Error>().map(std::io::Error::kind) == Some(std::io::ErrorKind::NotFound) => Vec::new(),
+
+        Err(err) => return Err(err.context("Failed to list installed voice packages"))
+    };
+
+    let locales = packages.into_iter()
+        .filter(VoicePackage::is_installed)
+        .map(|package| package.locale());
+
+    for locale in locales {
+        tracing::debug!("Found installed voice package: {locale:?}");
+
+        components.push((Component::Voice(locale), try_get_voice_integrity_files(edition, locale, None)?));
+    }
 
-    crate::repairer::try_get_unused_files(game_dir, used_files, skip_names)
+    repair_components(game.path(), components, &default_skip_rules(), options, sources, updater)
 }
@@ -14,9 +14,13 @@ use crate::{
         downloader::{Downloader, DownloadingError},
         installer::{
             Installer,
+            InstallerError,
             Update as InstallerUpdate
         },
-        free_space
+        transaction::Transaction,
+        space::SpaceRequirements,
+        event_log::{LoggedEvent, EventKind},
+        hooks::{self, InstallHooks}
     },
     external::hpatchz
 };
@@ -31,11 +35,41 @@ pub enum DiffUpdate {
     ApplyingHdiffProgress(u64, u64),
     ApplyingHdiffFinished,
 
+    /// Failed to apply hdiff patch, so the file is repaired instead
+    /// 
+    /// `(relative file path)`
+    RepairingFileStarted(PathBuf),
+
+    RepairingFileFinished,
+    RepairingFileError(String),
+
     RemovingOutdatedStarted,
     RemovingOutdatedProgress(u64, u64),
     RemovingOutdatedFinished
 }
 
+#[cfg(feature = "install")]
+impl LoggedEvent for DiffUpdate {
+    fn kind(&self) -> EventKind {
+        match self {
+            Self::InstallerUpdate(update) => update.kind(),
+
+            Self::ApplyingHdiffProgress(_, _) |
+            Self::RemovingOutdatedProgress(_, _) => EventKind::Progress,
+
+            Self::RepairingFileStarted(_) => EventKind::Retry,
+            Self::RepairingFileError(_) => EventKind::Error,
+
+            Self::CheckingFreeSpace(_) |
+            Self::ApplyingHdiffStarted |
+            Self::ApplyingHdiffFinished |
+            Self::RepairingFileFinished |
+            Self::RemovingOutdatedStarted |
+            Self::RemovingOutdatedFinished => EventKind::Phase
+        }
+    }
+}
+
 impl From<InstallerUpdate> for DiffUpdate {
     #[inline]
     fn from(update: InstallerUpdate) -> Self {
@@ -62,6 +96,19 @@ pub enum DiffDownloadingError {
     #[error("Failed to apply hdiff patch: {0}")]
     HdiffPatch(String),
 
+    /// Failed to unpack downloaded archive
+    #[error("Failed to unpack downloaded archive: {0}")]
+    Unpacking(String),
+
+    /// Failed to apply staged changes to the game folder.
+    /// In transactional mode all the changes are rolled back so the game folder is left untouched
+    #[error("Failed to apply changes: {0}")]
+    Transaction(String),
+
+    /// Installation was vetoed by one of its hooks
+    #[error("{0}")]
+    HookVetoed(String),
+
     /// Installation path wasn't specified. This could happen when you
     /// try to call `install` method on `VersionDiff` that was generated
     /// in `VoicePackage::list_latest`. This method couldn't know
@@ -77,6 +124,17 @@ impl From<minreq::Error> for DiffDownloadingError {
     }
 }
 
+impl From<InstallerError> for DiffDownloadingError {
+    fn from(error: InstallerError) -> Self {
+        match error {
+            InstallerError::Downloading(err) => Self::DownloadingError(err),
+            InstallerError::HookVetoed(err) => Self::HookVetoed(err),
+            InstallerError::Unpacking(err) => Self::Unpacking(err),
+            InstallerError::Transaction(err) => Self::Transaction(err)
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VersionDiff {
     /// Latest version
@@ -105,7 +163,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     },
 
     /// Component should be updated before using it
@@ -128,7 +189,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     },
 
     /// Difference can't be calculated because installed version is too old
@@ -156,7 +220,10 @@ pub enum VersionDiff {
         version_file_path: Option<PathBuf>,
 
         /// Temp folder path
-        temp_folder: Option<PathBuf>
+        temp_folder: Option<PathBuf>,
+
+        /// Apply changes to the game folder only when the whole update succeeded
+        transactional: bool
     }
 }
 
@@ -220,6 +287,42 @@ impl VersionDiff {
             }
         }
     }
+
+    /// Check if changes are staged and applied to the game folder
+    /// only when the whole update succeeded
+    /// 
+    /// Default is `false`, so changes are applied to the game folder directly
+    pub fn is_transactional(&self) -> bool {
+        match self {
+            // Can't be installed
+            Self::Latest { .. } |
+            Self::Outdated { .. } => false,
+
+            // Can be installed
+            Self::Predownload { transactional, .. } |
+            Self::Diff { transactional, .. } |
+            Self::NotInstalled { transactional, .. } => *transactional
+        }
+    }
+
+    /// Specify whether changes should be staged and applied
+    /// to the game folder only when the whole update succeeded
+    pub fn with_transactional(mut self, value: bool) -> Self {
+        match &mut self {
+            // Can't be installed
+            Self::Latest { .. } |
+            Self::Outdated { .. } => self,
+
+            // Can be installed
+            Self::Predownload { transactional, .. } |
+            Self::Diff { transactional, .. } |
+            Self::NotInstalled { transactional, .. } => {
+                *transactional = value;
+
+                self
+            }
+        }
+    }
 }
 
 impl VersionDiffExt for VersionDiff {
@@ -336,19 +439,29 @@ impl VersionDiffExt for VersionDiff {
         Ok(())
     }
 
-    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+    fn install_to_with_hooks(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        let result = self.install_hooked(path.as_ref(), hooks, updater);
+
+        if let Err(err) = &result {
+            hooks.on_failure(&err.to_string());
+        }
+
+        result
+    }
+}
+
+impl VersionDiff {
+    fn install_hooked(&self, path: &Path, hooks: &impl InstallHooks, updater: impl Fn(DiffUpdate) + Clone + Send + 'static) -> Result<(), DiffDownloadingError> {
         tracing::debug!("Installing version difference");
 
         match self {
             // Can't be downloaded
-            Self::Latest { .. } => return Err(Self::Error::AlreadyLatest),
-            Self::Outdated { .. } => return Err(Self::Error::Outdated),
+            Self::Latest { .. } => return Err(DiffDownloadingError::AlreadyLatest),
+            Self::Outdated { .. } => return Err(DiffDownloadingError::Outdated),
 
             _ => ()
         }
 
-        let path = path.as_ref();
-
         let url = self.downloading_uri().expect("Failed to retreive downloading url");
         let downloaded_size = self.downloaded_size().expect("Failed to retreive downloaded size");
         let unpacked_size = self.unpacked_size().expect("Failed to retreive unpacked size");
@@ -357,74 +470,41 @@ impl VersionDiffExt for VersionDiff {
             // Set custom temp folder location
             .with_temp_folder(self.temp_folder())
 
-            // Don't perform space checks in the Installer because we're doing it here
-            .with_free_space_check(false);
+            // Check space required by the downloaded archive's entries
+            .with_free_space_check(true);
 
         (updater)(DiffUpdate::CheckingFreeSpace(installer.temp_folder.to_path_buf()));
-
-        // Check available free space for archive itself
-        let Some(space) = free_space::available(&installer.temp_folder) else {
-            tracing::error!("Path is not mounted: {:?}", installer.temp_folder);
-
-            return Err(DownloadingError::PathNotMounted(installer.temp_folder).into());
-        };
-
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&installer.temp_folder, path) {
-            downloaded_size + unpacked_size
-        } else {
-            downloaded_size
-        };
-
-        if space < required {
-            tracing::error!("No free space available in the temp folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(installer.temp_folder, required, space).into());
-        }
-
         (updater)(DiffUpdate::CheckingFreeSpace(path.to_path_buf()));
 
-        // Check available free space for unpacked archvie data
-        let Some(space) = free_space::available(&path) else {
-            tracing::error!("Path is not mounted: {:?}", &path);
-
-            return Err(DownloadingError::PathNotMounted(path.to_path_buf()).into());
-        };
+        // Check available free space for the archive and its unpacked data
+        // using sizes reported by the API. More accurate check is performed
+        // after downloading when we can read the archive's entries
+        SpaceRequirements::from_sizes(&installer.temp_folder, &path, downloaded_size, unpacked_size).check()?;
 
-        // We can possibly store downloaded archive + unpacked data on the same disk
-        let required = if free_space::is_same_disk(&path, &installer.temp_folder) {
-            unpacked_size + downloaded_size
+        // In transactional mode changes are staged and applied
+        // to the game folder only if everything was successfully installed
+        let transaction = if self.is_transactional() {
+            Transaction::new(path)
         } else {
-            unpacked_size
+            Transaction::direct(path)
         };
 
-        if space < required {
-            tracing::error!("No free space available in the installation folder. Required: {required}. Available: {space}");
-
-            return Err(DownloadingError::NoSpaceAvailable(path.to_path_buf(), required, space).into());
-        }
+        let mut transaction = transaction
+            .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
 
         // Install data
         let installer_updater = updater.clone();
 
-        installer.install(path, move |update| (installer_updater)(update.into()));
-
-        // Create `.version` file here even if hdiff patching is failed because
-        // it's easier to explain user why he should run files repairer than
-        // why he should re-download entire game update because something is failed
-        #[allow(unused_must_use)] {
-            let version_path = self.version_file_path()
-                .unwrap_or_else(|| path.join(".version"));
+        installer.install_staged_with_hooks(&transaction, hooks, move |update| (installer_updater)(update.into()))?;
 
-            std::fs::write(version_path, self.latest().version);
-        }
+        let staging = transaction.staging().to_path_buf();
 
         // Apply hdiff patches
         // We're ignoring Err because in practice it means that hdifffiles.txt is missing
-        if let Ok(files) = std::fs::read_to_string(path.join("hdifffiles.txt")) {
+        if let Ok(files) = std::fs::read_to_string(staging.join("hdifffiles.txt")) {
             tracing::debug!("Applying hdiff patches");
 
-            (updater)(Self::Update::ApplyingHdiffStarted);
+            (updater)(DiffUpdate::ApplyingHdiffStarted);
 
             let files = files.lines().collect::<Vec<&str>>();
             let hdiffs = files.len() as u64;
@@ -433,23 +513,29 @@ impl VersionDiffExt for VersionDiff {
             for (i, file) in files.into_iter().enumerate() {
                 let relative_file = &file[16..file.len() - 2];
 
-                let file = path.join(relative_file);
-                let patch = path.join(format!("{relative_file}.hdiff"));
-                let output = path.join(format!("{relative_file}.hdiff_patched"));
+                // Patched files are written to the staging folder
+                // and will replace original ones on commit
+                let file = transaction.resolve(relative_file);
+                let patch = staging.join(format!("{relative_file}.hdiff"));
+                let output = staging.join(format!("{relative_file}.hdiff_patched"));
 
                 // If failed to apply the patch
                 if let Err(err) = hpatchz::patch(&file, &patch, &output) {
                     tracing::warn!("Failed to apply hdiff patch for {:?}: {err}", file);
                     tracing::debug!("Trying to repair corrupted file");
 
+                    (updater)(DiffUpdate::RepairingFileStarted(PathBuf::from(relative_file)));
+
                     // If we were able to get API response - it shouldn't be impossible
                     // to also get integrity files list from the same API
                     match super::repairer::try_get_integrity_file(self.edition(), relative_file, Some(*crate::REQUESTS_TIMEOUT)) {
                         Ok(Some(integrity)) => {
                             if !integrity.fast_verify(&path) {
-                                if let Err(err) = integrity.repair(&path) {
+                                if let Err(err) = integrity.repair(&staging) {
                                     tracing::error!("Failed to repair corrupted file: {err}");
 
+                                    (updater)(DiffUpdate::RepairingFileError(err.to_string()));
+
                                     return Err(err.into());
                                 }
                             }
@@ -458,69 +544,96 @@ impl VersionDiffExt for VersionDiff {
                         Ok(None) => {
                             tracing::error!("Failed to repair corrupted file: not found");
 
-                            return Err(Self::Error::HdiffPatch(err.to_string()))
+                            (updater)(DiffUpdate::RepairingFileError(String::from("File is not found in the integrity files list")));
+
+                            return Err(DiffDownloadingError::HdiffPatch(err.to_string()))
                         }
 
                         Err(repair_fail) => {
                             tracing::error!("Failed to repair corrupted file: {repair_fail}");
 
-                            return Err(Self::Error::HdiffPatch(err.to_string()))
+                            (updater)(DiffUpdate::RepairingFileError(repair_fail.to_string()));
+
+                            return Err(DiffDownloadingError::HdiffPatch(err.to_string()))
                         }
                     }
 
                     #[allow(unused_must_use)] {
                         std::fs::remove_file(&patch);
+                        std::fs::remove_file(&output);
                     }
+
+                    (updater)(DiffUpdate::RepairingFileFinished);
                 }
 
                 // If patch was successfully applied
                 else {
-                    // FIXME: handle errors properly
-                    std::fs::remove_file(&file)
-                        .expect(&format!("Failed to remove hdiff patch: {:?}", file));
-
                     std::fs::remove_file(&patch)
-                        .expect(&format!("Failed to remove hdiff patch: {:?}", patch));
+                        .map_err(|err| DiffDownloadingError::HdiffPatch(format!("Failed to remove hdiff patch {:?}: {err}", patch)))?;
 
-                    std::fs::rename(&output, &file)
-                        .expect(&format!("Failed to rename hdiff patch: {:?}", file));
+                    std::fs::rename(&output, staging.join(relative_file))
+                        .map_err(|err| DiffDownloadingError::HdiffPatch(format!("Failed to rename hdiff patch {:?}: {err}", output)))?;
                 }
 
-                (updater)(Self::Update::ApplyingHdiffProgress(i as u64 + 1, hdiffs));
+                (updater)(DiffUpdate::ApplyingHdiffProgress(i as u64 + 1, hdiffs));
             }
 
-            std::fs::remove_file(path.join("hdifffiles.txt"))
-                .expect("Failed to remove hdifffiles.txt");
+            std::fs::remove_file(staging.join("hdifffiles.txt"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
 
-            (updater)(Self::Update::ApplyingHdiffFinished);
+            (updater)(DiffUpdate::ApplyingHdiffFinished);
         }
 
         tracing::debug!("Deleting outdated files");
 
         // Remove outdated files
         // We're ignoring Err because in practice it means that deletefiles.txt is missing
-        if let Ok(files) = std::fs::read_to_string(path.join("deletefiles.txt")) {
+        if let Ok(files) = std::fs::read_to_string(staging.join("deletefiles.txt")) {
             let files = files.lines().collect::<Vec<&str>>();
             let files_len = files.len() as u64;
 
-            (updater)(Self::Update::RemovingOutdatedStarted);
+            (updater)(DiffUpdate::RemovingOutdatedStarted);
 
             // AnimeGame_Data/Plugins/metakeeper.dll
             for (i, file) in files.into_iter().enumerate() {
-                let file = path.join(file);
-
-                std::fs::remove_file(&file)
-                    .expect(&format!("Failed to remove outdated file: {:?}", file));
+                transaction.remove(file);
 
-                (updater)(Self::Update::RemovingOutdatedProgress(i as u64 + 1, files_len));
+                (updater)(DiffUpdate::RemovingOutdatedProgress(i as u64 + 1, files_len));
             }
 
-            std::fs::remove_file(path.join("deletefiles.txt"))
-                .expect("Failed to remove deletefiles.txt");
+            std::fs::remove_file(staging.join("deletefiles.txt"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
 
-            (updater)(Self::Update::RemovingOutdatedFinished);
+            (updater)(DiffUpdate::RemovingOutdatedFinished);
         }
 
+        // `.version` file is committed together with the game files
+        // so it's never updated if the game files are not
+        let version_path = self.version_file_path();
+
+        if version_path.is_none() {
+            self.latest().write_file(staging.join(".version"))
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+        }
+
+        (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingStarted(path.to_path_buf())));
+
+        if let Err(err) = transaction.commit() {
+            (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingError(err.to_string())));
+
+            return Err(DiffDownloadingError::Transaction(err.to_string()));
+        }
+
+        (updater)(DiffUpdate::InstallerUpdate(InstallerUpdate::CommittingFinished));
+
+        if let Some(version_path) = version_path {
+            self.latest().write_file(version_path)
+                .map_err(|err| DiffDownloadingError::Transaction(err.to_string()))?;
+        }
+
+        hooks::run("post_patch", || hooks.post_patch(path))
+            .map_err(DiffDownloadingError::HookVetoed)?;
+
         Ok(())
     }
 }
@@ -131,7 +131,7 @@ impl VoicePackage {
 
         Ok(Self::NotInstalled {
             locale,
-            version: Version::from_str(latest.version).unwrap(),
+            version: latest.version.parse::<Version>().unwrap(),
             data: find_voice_pack(latest.voice_packs, locale),
             game_path: None,
             game_edition
@@ -190,7 +190,7 @@ impl VoicePackage {
         let response = api::request(game_edition)?;
 
         let mut packages = Vec::new();
-        let version = Version::from_str(response.data.game.latest.version).unwrap();
+        let version = response.data.game.latest.version.parse::<Version>().unwrap();
 
         for package in response.data.game.latest.voice_packs {
             packages.push(Self::NotInstalled {
@@ -229,9 +229,11 @@ impl VoicePackage {
 
                 match std::fs::read(path.join(".version")) {
                     Ok(curr) => {
-                        tracing::debug!("Found .version file: {}.{}.{}", curr[0], curr[1], curr[2]);
+                        let version = Version::from_bytes(&curr)?;
 
-                        Ok(Version::new(curr[0], curr[1], curr[2]))
+                        tracing::debug!("Found .version file: {version}");
+
+                        Ok(version)
                     },
 
                     // We don't create .version file here because we don't
@@ -257,7 +259,7 @@ impl VoicePackage {
                             if package_size > size - VOICE_PACKAGE_THRESHOLD {
                                 tracing::debug!("Predicted version: {version}");
 
-                                return Ok(Version::from_str(version).unwrap());
+                                return Ok(version.parse()?);
                             }
                         }
 
@@ -350,7 +352,7 @@ impl VoicePackage {
 
                             return Ok(VersionDiff::Predownload {
                                 current,
-                                latest: Version::from_str(predownload.latest.version).unwrap(),
+                                latest: predownload.latest.version.parse::<Version>().unwrap(),
                                 uri: diff.path,
 
                                 downloaded_size: diff.size.parse::<u64>().unwrap(),
@@ -372,6 +374,7 @@ impl VoicePackage {
                                 },
 
                                 temp_folder: None,
+                                transactional: false,
                                 edition: game_edition
                             })
                         }
@@ -393,7 +396,7 @@ impl VoicePackage {
 
                         return Ok(VersionDiff::Diff {
                             current,
-                            latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                            latest: response.data.game.latest.version.parse::<Version>().unwrap(),
                             uri: diff.path,
 
                             downloaded_size: diff.size.parse::<u64>().unwrap(),
@@ -415,6 +418,7 @@ impl VoicePackage {
                             },
 
                             temp_folder: None,
+                            transactional: false,
                             edition: game_edition
                         })
                     }
@@ -422,7 +426,7 @@ impl VoicePackage {
 
                 Ok(VersionDiff::Outdated {
                     current,
-                    latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                    latest: response.data.game.latest.version.parse::<Version>().unwrap(),
                     edition: game_edition
                 })
             }
@@ -434,7 +438,7 @@ impl VoicePackage {
             let latest = find_voice_pack(response.data.game.latest.voice_packs, self.locale());
 
             Ok(VersionDiff::NotInstalled {
-                latest: Version::from_str(response.data.game.latest.version).unwrap(),
+                latest: response.data.game.latest.version.parse::<Version>().unwrap(),
                 uri: latest.path,
 
                 downloaded_size: latest.size.parse::<u64>().unwrap(),
@@ -456,6 +460,7 @@ impl VoicePackage {
                 },
 
                 temp_folder: None,
+                transactional: false,
                 edition: game_edition
             })
         }
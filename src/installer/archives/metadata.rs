@@ -0,0 +1,497 @@
+use std::path::{Path, PathBuf, Component};
+use std::fs::{File, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+/// Unix file type bits of the symlink entries
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SymlinkPolicy {
+    /// Don't create any symlinks
+    Skip,
+
+    /// Create only relative symlinks which point inside of the extraction folder
+    Safe,
+
+    /// Create all the symlinks as they're stored in the archive
+    Allow
+}
+
+/// Rules applied to the metadata of extracted files
+/// 
+/// The same rules are applied by every archive backend,
+/// so extracted folders look the same whether archive was
+/// unpacked natively or by the `unzip` and `7z` binaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MetadataPolicy {
+    /// Apply unix permissions stored in the archive
+    /// 
+    /// Owner is always allowed to read and write extracted files so they
+    /// could be updated later. If `false`, files get `0o644` and folders get `0o755`
+    pub preserve_mode: bool,
+
+    /// Apply modification time stored in the archive
+    pub preserve_mtime: bool,
+
+    pub symlinks: SymlinkPolicy,
+
+    /// Change owner of the extracted files to the given `(uid, gid)`
+    /// 
+    /// Usually requires root permissions
+    pub owner: Option<(u32, u32)>
+}
+
+impl Default for MetadataPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            preserve_mode: true,
+            preserve_mtime: true,
+            symlinks: SymlinkPolicy::Safe,
+            owner: None
+        }
+    }
+}
+
+impl MetadataPolicy {
+    #[inline]
+    /// Specify whether unix permissions from the archive should be applied
+    pub fn with_preserve_mode(mut self, preserve_mode: bool) -> Self {
+        self.preserve_mode = preserve_mode;
+
+        self
+    }
+
+    #[inline]
+    /// Specify whether modification time from the archive should be applied
+    pub fn with_preserve_mtime(mut self, preserve_mtime: bool) -> Self {
+        self.preserve_mtime = preserve_mtime;
+
+        self
+    }
+
+    #[inline]
+    /// Specify how symlinks from the archive should be handled
+    pub fn with_symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+
+        self
+    }
+
+    #[inline]
+    /// Specify `(uid, gid)` the extracted files should belong to
+    pub fn with_owner(mut self, owner: Option<(u32, u32)>) -> Self {
+        self.owner = owner;
+
+        self
+    }
+
+    /// Apply metadata to the extracted file or folder
+    pub(super) fn apply(&self, path: &Path, mode: Option<u32>, mtime: Option<u64>) -> std::io::Result<()> {
+        let is_dir = path.is_dir();
+
+        let mode = match (self.preserve_mode, mode) {
+            (true, Some(mode)) if is_dir => mode & 0o777 | 0o700,
+            (true, Some(mode)) => mode & 0o777 | 0o600,
+
+            _ if is_dir => 0o755,
+            _ => 0o644
+        };
+
+        std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+
+        if let (true, Some(mtime)) = (self.preserve_mtime, mtime) {
+            File::open(path)?.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
+        }
+
+        if let Some((uid, gid)) = self.owner {
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+        }
+
+        Ok(())
+    }
+
+    /// Create symlink if it's allowed by the policy
+    /// 
+    /// Return `false` if the symlink was skipped
+    /// 
+    /// `path` must be the real path returned by `create_parent`
+    pub(super) fn create_symlink(&self, root: &Path, path: &Path, target: &Path) -> std::io::Result<bool> {
+        if !self.allows_symlink(root, path, target) {
+            tracing::warn!("Skipping symlink {:?} -> {:?}", path, target);
+
+            return Ok(false);
+        }
+
+        match path.symlink_metadata() {
+            // Don't replace folder with extracted files by a symlink
+            Ok(metadata) if metadata.is_dir() => {
+                tracing::warn!("Skipping symlink {:?} -> {:?}: folder with the same name exists", path, target);
+
+                return Ok(false);
+            }
+
+            Ok(_) => std::fs::remove_file(path)?,
+            Err(_) => ()
+        }
+
+        std::os::unix::fs::symlink(target, path)?;
+
+        if let Some((uid, gid)) = self.owner {
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Apply policy to the symlink created by an external binary
+    pub(super) fn check_symlink(&self, root: &Path, path: &Path) -> std::io::Result<()> {
+        let target = std::fs::read_link(path)?;
+
+        if !self.allows_symlink(root, path, &target) {
+            tracing::warn!("Removing symlink {:?} -> {:?}", path, target);
+
+            std::fs::remove_file(path)?;
+        }
+
+        else if let Some((uid, gid)) = self.owner {
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+        }
+
+        Ok(())
+    }
+
+    fn allows_symlink(&self, root: &Path, path: &Path, target: &Path) -> bool {
+        match self.symlinks {
+            SymlinkPolicy::Skip => false,
+            SymlinkPolicy::Allow => true,
+
+            // Symlink's position is taken from its real parent folder
+            // because the path itself can go through other symlinks
+            SymlinkPolicy::Safe => {
+                let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                    return false;
+                };
+
+                let (Ok(root), Ok(parent)) = (root.canonicalize(), parent.canonicalize()) else {
+                    return false;
+                };
+
+                let Ok(relative) = parent.strip_prefix(&root) else {
+                    return false;
+                };
+
+                if !is_safe_symlink(&relative.join(name), target) {
+                    return false;
+                }
+
+                // Target can go through other symlinks as well
+                resolve_path(&parent.join(target))
+                    .map(|target| target.starts_with(&root))
+                    .unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// Check if the symlink's target stays inside of the extraction folder
+/// 
+/// `link` is the symlink's path relative to the extraction folder
+/// 
+/// - `bin/game -> ../Game.x86_64` is safe
+/// - `bin/game -> ../../usr/bin/game` is not
+/// - `bin/game -> /usr/bin/game` is not
+pub(super) fn is_safe_symlink(link: &Path, target: &Path) -> bool {
+    let mut depth = link.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .count()
+        .saturating_sub(1);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+
+            Component::ParentDir => {
+                if depth == 0 {
+                    return false;
+                }
+
+                depth -= 1;
+            }
+
+            // Absolute paths
+            Component::RootDir |
+            Component::Prefix(_) => return false
+        }
+    }
+
+    true
+}
+
+/// Error returned when the extracted path leaves the root folder
+fn outside_error(path: &Path) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Path leaves the extraction folder: {:?}", path))
+}
+
+/// Create folder inside of the root folder
+/// 
+/// Unlike `create_dir_all`, every already existing component of the path
+/// is resolved and checked to stay inside of the root folder, so symlinks
+/// from the archive or a previous installation can't redirect extracted
+/// files outside of it
+/// 
+/// Return real path of the folder
+pub(super) fn create_folder(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return Err(outside_error(path));
+    };
+
+    std::fs::create_dir_all(root)?;
+
+    let root = root.canonicalize()?;
+
+    let mut real = root.clone();
+
+    for component in relative.components() {
+        let part = match component {
+            Component::Normal(part) => part,
+            Component::CurDir => continue,
+
+            _ => return Err(outside_error(path))
+        };
+
+        let next = real.join(part);
+
+        // `mkdir` doesn't follow symlinks so the folder
+        // is created inside of the already checked one
+        match std::fs::create_dir(&next) {
+            Ok(()) => {
+                real = next;
+
+                continue;
+            }
+
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => (),
+            Err(err) => return Err(err)
+        }
+
+        let resolved = next.canonicalize()?;
+
+        if !resolved.starts_with(&root) {
+            return Err(outside_error(path));
+        }
+
+        if !resolved.is_dir() {
+            return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("Path is not a folder: {:?}", next)));
+        }
+
+        real = resolved;
+    }
+
+    Ok(real)
+}
+
+/// Create parent folder of the extracted entry using `create_folder`
+/// 
+/// Return real path of the entry
+pub(super) fn create_parent(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(outside_error(path));
+    };
+
+    Ok(create_folder(root, parent)?.join(name))
+}
+
+/// Resolve all the symlinks of the path even if it doesn't exist
+/// 
+/// The longest existing part of the path is canonicalized
+/// and the rest of its components are normalized as they are
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    let (mut resolved, rest) = path.ancestors()
+        .find_map(|ancestor| {
+            let resolved = ancestor.canonicalize().ok()?;
+
+            Some((resolved, path.strip_prefix(ancestor).ok()?))
+        })?;
+
+    for component in rest.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => (),
+
+            Component::ParentDir => {
+                resolved.pop();
+            }
+
+            _ => return None
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Check if unix mode describes a symlink
+#[inline]
+pub(super) fn is_symlink_mode(mode: u32) -> bool {
+    mode & S_IFMT == S_IFLNK
+}
+
+/// Apply metadata policy to the folder extracted by an external binary
+/// 
+/// Folders are processed after all the files because
+/// changing folder's content updates its modification time
+pub(super) fn apply_entries(root: &Path, entries: impl IntoIterator<Item = super::Entry>, policy: &MetadataPolicy) -> std::io::Result<()> {
+    let mut folders = Vec::new();
+
+    for entry in entries {
+        let Some(path) = enclosed_path(root, &entry.name) else {
+            continue;
+        };
+
+        // Don't change metadata of the files outside of the root folder
+        if !is_inside(root, &path) {
+            tracing::warn!("Skipping archive entry outside of the extraction folder: {:?}", path);
+
+            continue;
+        }
+
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue;
+        };
+
+        if metadata.is_symlink() {
+            policy.check_symlink(root, &path)?;
+        }
+
+        else if metadata.is_dir() {
+            folders.push((path, entry.mode, entry.mtime));
+        }
+
+        else {
+            policy.apply(&path, entry.mode, entry.mtime)?;
+        }
+    }
+
+    apply_folders(folders, policy)
+}
+
+/// Check if the real parent folder of the path is inside of the root folder
+fn is_inside(root: &Path, path: &Path) -> bool {
+    let (Ok(root), Some(Ok(parent))) = (root.canonicalize(), path.parent().map(Path::canonicalize)) else {
+        return false;
+    };
+
+    parent.starts_with(root)
+}
+
+/// Create symlinks in the given order, when all the other entries are extracted
+/// 
+/// Every symlink is checked again when all of them are created,
+/// so chains of symlinks can't point outside of the root folder
+pub(super) fn create_symlinks(root: &Path, symlinks: impl IntoIterator<Item = (PathBuf, PathBuf)>, policy: &MetadataPolicy) -> std::io::Result<()> {
+    let mut created = Vec::new();
+
+    for (path, target) in symlinks {
+        let path = create_parent(root, &path)?;
+
+        if policy.create_symlink(root, &path, &target)? {
+            created.push(path);
+        }
+    }
+
+    for path in created {
+        policy.check_symlink(root, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Apply metadata to the folders, deepest ones first
+pub(super) fn apply_folders(mut folders: Vec<(PathBuf, Option<u32>, Option<u64>)>, policy: &MetadataPolicy) -> std::io::Result<()> {
+    folders.sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+
+    for (path, mode, mtime) in folders {
+        policy.apply(&path, mode, mtime)?;
+    }
+
+    Ok(())
+}
+
+/// Join archive entry name to the root folder
+/// 
+/// Return `None` if the entry points outside of the root folder
+pub(super) fn enclosed_path(root: &Path, name: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => (),
+
+            _ => return None
+        }
+    }
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_safe_symlinks() {
+        assert!(is_safe_symlink(Path::new("game"), Path::new("Game.x86_64")));
+        assert!(is_safe_symlink(Path::new("bin/game"), Path::new("../Game.x86_64")));
+        assert!(is_safe_symlink(Path::new("bin/game"), Path::new("./../data/../Game.x86_64")));
+
+        assert!(!is_safe_symlink(Path::new("game"), Path::new("../Game.x86_64")));
+        assert!(!is_safe_symlink(Path::new("bin/game"), Path::new("../../usr/bin/game")));
+        assert!(!is_safe_symlink(Path::new("bin/game"), Path::new("/usr/bin/game")));
+    }
+
+    #[test]
+    pub fn test_real_parents() -> std::io::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-real-parents");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        let root = folder.join("root");
+
+        std::fs::create_dir_all(root.join("sub"))?;
+
+        std::os::unix::fs::symlink(".", root.join("d"))?;
+        std::os::unix::fs::symlink("..", root.join("sub/up"))?;
+        std::os::unix::fs::symlink("../..", root.join("sub/outside"))?;
+
+        let real_root = root.canonicalize()?;
+
+        assert_eq!(create_parent(&root, &root.join("d/d/sub/file"))?, real_root.join("sub/file"));
+        assert_eq!(create_parent(&root, &root.join("sub/up/new/file"))?, real_root.join("new/file"));
+
+        assert!(create_parent(&root, &root.join("sub/outside/file")).is_err());
+        assert!(create_parent(&root, &root.join("d/sub/outside/new/file")).is_err());
+        assert!(!folder.join("new").exists());
+
+        let policy = MetadataPolicy::default();
+
+        // `d/d/link` is at the root level, so `..` leaves the root folder
+        assert!(!policy.create_symlink(&root, &root.join("d/d/link"), Path::new("../escaped"))?);
+
+        // Target goes through another symlink
+        assert!(!policy.create_symlink(&root, &root.join("link"), Path::new("sub/outside/file"))?);
+
+        assert!(policy.create_symlink(&root, &root.join("link"), Path::new("sub/up/sub"))?);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
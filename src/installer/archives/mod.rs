@@ -0,0 +1,1246 @@
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Serialize, Deserialize};
+
+use zip::ZipArchive;
+use tar::Archive as TarArchive;
+// use sevenz_rust::SevenZReader as SevenzArchive;
+
+use xz::read::XzDecoder as XzReader;
+use bzip2::read::BzDecoder as Bz2Reader;
+use flate2::read::GzDecoder as GzReader;
+use zstd::stream::read::Decoder as ZstdReader;
+
+pub mod writer;
+pub mod metadata;
+mod parallel;
+mod reader;
+
+pub use writer::{ArchiveWriter, ArchiveFormat};
+pub use metadata::{MetadataPolicy, SymlinkPolicy};
+pub use reader::EntryReader;
+
+/// Get 7z binary if some is available
+fn get7z() -> anyhow::Result<String> {
+    let result = Command::new("7z")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    if result.is_ok() {
+        return Ok(String::from("7z"));
+    }
+
+    Command::new("7za")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+
+    Ok(String::from("7za"))
+}
+
+/// Get total size of the archive in bytes
+/// 
+/// For multipart archives sizes of all the found parts are summed
+pub(super) fn get_archive_size(path: &Path) -> u64 {
+    let path_str = path.to_string_lossy();
+
+    // archive.zip.001, archive.zip.002, ...
+    let parts = if path_str.ends_with(".001") {
+        let base = &path_str[..path_str.len() - 3];
+
+        (1..).map(|i| PathBuf::from(format!("{base}{i:03}")))
+            .take_while(|part| part.exists())
+            .collect::<Vec<_>>()
+    }
+
+    // archive.z01, archive.z02, ..., archive.zip
+    else if path_str.ends_with(".z01") {
+        let base = &path_str[..path_str.len() - 2];
+
+        (1..).map(|i| PathBuf::from(format!("{base}{i:02}")))
+            .take_while(|part| part.exists())
+            .chain([PathBuf::from(format!("{base}ip"))])
+            .collect::<Vec<_>>()
+    }
+
+    else {
+        vec![path.to_path_buf()]
+    };
+
+    parts.iter()
+        .flat_map(|part| part.metadata())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Amount of days since the unix epoch
+/// 
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// Get `(year, month, day)` from the amount of days since the unix epoch
+/// 
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Convert zip date time to unix timestamp
+fn zip_datetime_to_unix(datetime: zip::DateTime) -> u64 {
+    let days = days_from_civil(datetime.year() as i64, datetime.month() as i64, datetime.day() as i64);
+
+    let timestamp = days * 86400
+        + datetime.hour() as i64 * 3600
+        + datetime.minute() as i64 * 60
+        + datetime.second() as i64;
+
+    timestamp.max(0) as u64
+}
+
+/// Convert unix timestamp to zip date time
+fn unix_to_zip_datetime(timestamp: i64) -> zip::DateTime {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+
+    let secs = timestamp.rem_euclid(86400);
+
+    zip::DateTime::from_date_and_time(
+        year.clamp(1980, 2107) as u16,
+        month as u8,
+        day as u8,
+        (secs / 3600) as u8,
+        (secs % 3600 / 60) as u8,
+        (secs % 60) as u8
+    ).unwrap_or_default()
+}
+
+/// Parse progress percent from the `7z -bsp1` output
+/// 
+/// ```text
+///  42% 17 - GenshinImpact_Data/data.unity3d
+/// ```
+fn parse_7z_progress(line: &str) -> Option<u64> {
+    let (percent, _) = line.trim().split_once('%')?;
+
+    percent.trim().parse().ok()
+}
+
+/// Reader that keeps track of the current position in the underlying stream
+struct PositionReader<R> {
+    reader: R,
+    position: Arc<AtomicU64>
+}
+
+impl<R> PositionReader<R> {
+    #[inline]
+    fn new(reader: R) -> (Self, Arc<AtomicU64>) {
+        let position = Arc::new(AtomicU64::new(0));
+
+        (Self { reader, position: position.clone() }, position)
+    }
+}
+
+impl<R: Read> Read for PositionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        self.position.fetch_add(read as u64, Ordering::Relaxed);
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for PositionReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.reader.seek(pos)?;
+
+        self.position.store(position, Ordering::Relaxed);
+
+        Ok(position)
+    }
+}
+
+/// Read all the data from the reader, reporting archive position after every chunk
+fn test_stream(mut reader: impl Read, position: &AtomicU64, total: u64, progress: &impl Fn(u64, u64)) -> std::io::Result<()> {
+    let mut buf = vec![0; 128 * 1024];
+
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(()),
+            _ => (progress)(position.load(Ordering::Relaxed).min(total), total)
+        }
+    }
+}
+
+/// Read all the entries of the tar archive and then drain the underlying stream
+/// so compression formats could verify their own checksums
+fn test_tar<R: Read>(mut tar: TarArchive<R>, position: &AtomicU64, total: u64, progress: &impl Fn(u64, u64)) -> anyhow::Result<()> {
+    for entry in tar.entries()? {
+        let entry = entry?;
+
+        test_stream(entry, position, total, progress)?;
+    }
+
+    test_stream(tar.into_inner(), position, total, progress)?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Size {
+    Compressed(u64),
+    Uncompressed(u64),
+    Both {
+        compressed: u64,
+        uncompressed: u64
+    }
+}
+
+impl Size {
+    pub fn get_size(&self) -> u64 {
+        match self {
+            Size::Compressed(size) => *size,
+            Size::Uncompressed(size) => *size,
+            Size::Both { compressed, uncompressed: _ } => *compressed
+        }
+    }
+}
+
+/// Archive extraction settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExtractOptions {
+    /// Amount of threads used to extract zip archives
+    /// 
+    /// Other archive formats are always extracted by a single thread
+    pub threads: usize,
+
+    /// Max amount of bytes per second all the threads can write to the disk
+    /// 
+    /// Unlimited if `None`
+    pub io_limit: Option<u64>,
+
+    /// Rules applied to permissions, timestamps and symlinks of the extracted files
+    pub metadata: MetadataPolicy
+}
+
+impl Default for ExtractOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            io_limit: None,
+            metadata: MetadataPolicy::default()
+        }
+    }
+}
+
+impl ExtractOptions {
+    #[inline]
+    /// Specify amount of threads used to extract zip archives
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+
+        self
+    }
+
+    #[inline]
+    /// Specify max amount of bytes per second written to the disk
+    pub fn with_io_limit(mut self, io_limit: Option<u64>) -> Self {
+        self.io_limit = io_limit;
+
+        self
+    }
+
+    #[inline]
+    /// Specify rules applied to the extracted files metadata
+    pub fn with_metadata(mut self, metadata: MetadataPolicy) -> Self {
+        self.metadata = metadata;
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub size: Size,
+
+    /// Unix permissions of the entry if stored by the archive
+    pub mode: Option<u32>,
+
+    /// Modification time of the entry as unix timestamp
+    pub mtime: Option<u64>,
+
+    /// CRC32 checksum of the entry if stored by the archive
+    pub crc32: Option<u32>
+}
+
+impl Entry {
+    fn from_zip(entry: &zip::read::ZipFile) -> Self {
+        Self {
+            name: entry.name().to_string(),
+            size: Size::Both {
+                compressed: entry.compressed_size(),
+                uncompressed: entry.size()
+            },
+            mode: entry.unix_mode(),
+            mtime: Some(zip_datetime_to_unix(entry.last_modified())),
+            crc32: Some(entry.crc32())
+        }
+    }
+
+    fn from_tar<R: Read>(entry: &tar::Entry<R>) -> std::io::Result<Self> {
+        let header = entry.header();
+
+        Ok(Self {
+            name: entry.path()?.to_string_lossy().to_string(),
+            size: Size::Compressed(entry.size()),
+            mode: header.mode().ok(),
+            mtime: header.mtime().ok(),
+            crc32: None
+        })
+    }
+}
+
+/// Lazy iterator over the archive entries, returned by `Archive::entries`
+pub struct Entries(EntriesSource);
+
+enum EntriesSource {
+    /// Zip archive and index of the next entry
+    Zip(ZipArchive<File>, usize),
+
+    /// Tar entries read by another thread
+    Tar(std::sync::mpsc::Receiver<std::io::Result<Entry>>),
+
+    /// Entries listed by 7z
+    List(std::vec::IntoIter<Entry>)
+}
+
+impl Iterator for Entries {
+    type Item = anyhow::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            EntriesSource::Zip(zip, i) => {
+                if *i >= zip.len() {
+                    return None;
+                }
+
+                *i += 1;
+
+                Some(zip.by_index_raw(*i - 1)
+                    .map(|entry| Entry::from_zip(&entry))
+                    .map_err(anyhow::Error::from))
+            }
+
+            EntriesSource::Tar(receiver) => receiver.recv().ok()
+                .map(|entry| entry.map_err(anyhow::Error::from)),
+
+            EntriesSource::List(entries) => entries.next().map(Ok)
+        }
+    }
+}
+
+pub enum Archive {
+    Zip(PathBuf, ZipArchive<File>),
+    Tar(PathBuf, TarArchive<File>),
+    TarXz(PathBuf, TarArchive<XzReader<File>>),
+    TarGz(PathBuf, TarArchive<GzReader<File>>),
+    TarBz2(PathBuf, TarArchive<Bz2Reader<File>>),
+    TarZst(PathBuf, TarArchive<ZstdReader<'static, BufReader<File>>>),
+    SevenZ(PathBuf/*, SevenzArchive<File>*/),
+    ZipMultipart(PathBuf)
+}
+
+impl Archive {
+    pub fn open<T: Into<PathBuf>>(path: T) -> anyhow::Result<Self> {
+        let path: PathBuf = path.into();
+        let file = File::open(&path)?;
+
+        let path_str = path.to_string_lossy();
+
+        if path_str.ends_with(".zip") {
+            Ok(Archive::Zip(path, ZipArchive::new(file)?))
+        }
+
+        else if path_str.ends_with(".tar.xz") {
+            Ok(Archive::TarXz(path, TarArchive::new(XzReader::new(file))))
+        }
+
+        else if path_str.ends_with(".tar.gz") {
+            Ok(Archive::TarGz(path, TarArchive::new(GzReader::new(file))))
+        }
+
+        else if path_str.ends_with(".tar.bz2") {
+            Ok(Archive::TarBz2(path, TarArchive::new(Bz2Reader::new(file))))
+        }
+
+        else if path_str.ends_with(".tar.zst") {
+            Ok(Archive::TarZst(path, TarArchive::new(ZstdReader::new(file)?)))
+        }
+
+        else if path_str.ends_with(".7z") {
+            Ok(Archive::SevenZ(path/*, SevenzArchive::open(path, &[])?*/))
+        }
+
+        else if path_str.ends_with(".tar") {
+            Ok(Archive::Tar(path, TarArchive::new(file)))
+        }
+
+        else if path_str.ends_with(".zip.001") || path_str.ends_with(".7z.001") || path_str.ends_with(".z01") {
+            Ok(Archive::ZipMultipart(path))
+        }
+
+        else {
+            Err(anyhow::anyhow!("Archive format is not supported: {}", path.to_string_lossy()))
+        }
+    }
+
+    /// Get path to the archive file
+    pub fn path(&self) -> &Path {
+        match self {
+            Archive::Zip(path, _) |
+            Archive::Tar(path, _) |
+            Archive::TarXz(path, _) |
+            Archive::TarGz(path, _) |
+            Archive::TarBz2(path, _) |
+            Archive::TarZst(path, _) |
+            Archive::SevenZ(path) |
+            Archive::ZipMultipart(path) => path.as_path()
+        }
+    }
+
+    /// Verify integrity of every archive entry without writing anything to the disk
+    /// 
+    /// Works similarly to `7z t`: all the entries are decompressed in memory
+    /// and compared with their stored checksums. The archive is re-opened
+    /// for this, so it doesn't affect following `extract` call
+    /// 
+    /// `progress` is called with `(current bytes, total bytes)` of the archive file
+    #[tracing::instrument(level = "debug", skip(self, progress))]
+    pub fn test(&self, progress: impl Fn(u64, u64)) -> anyhow::Result<()> {
+        tracing::trace!("Testing archive");
+
+        let path = self.path();
+        let total = get_archive_size(path);
+
+        match self {
+            Archive::Zip(_, _) => {
+                let (reader, position) = PositionReader::new(File::open(path)?);
+
+                let mut zip = ZipArchive::new(reader)?;
+
+                for i in 0..zip.len() {
+                    let entry = zip.by_index(i)?;
+                    let name = entry.name().to_string();
+
+                    // Zip reader compares entry's crc32 when it reaches its end
+                    if let Err(err) = test_stream(entry, &position, total, &progress) {
+                        anyhow::bail!("Archive entry {name} is corrupted: {err}");
+                    }
+                }
+            }
+
+            Archive::Tar(_, _) |
+            Archive::TarXz(_, _) |
+            Archive::TarGz(_, _) |
+            Archive::TarBz2(_, _) |
+            Archive::TarZst(_, _) => {
+                let (reader, position) = PositionReader::new(File::open(path)?);
+
+                if let Some(tar) = self.open_tar(reader)? {
+                    test_tar(tar, &position, total, &progress)?;
+                }
+            }
+
+            Archive::SevenZ(_) |
+            Archive::ZipMultipart(_) => {
+                let mut child = Command::new(get7z()?)
+                    .arg("t")
+                    .arg(path)
+                    .arg("-bsp1")
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+
+                // 7z writes an error per corrupted entry, so stderr is drained
+                // in another thread, otherwise 7z blocks when its pipe is full
+                let stderr = child.stderr.take().map(|mut stderr| {
+                    std::thread::spawn(move || {
+                        let mut errors = String::new();
+
+                        #[allow(unused_must_use)] {
+                            stderr.read_to_string(&mut errors);
+                        }
+
+                        errors
+                    })
+                });
+
+                if let Some(mut stdout) = child.stdout.take() {
+                    let mut buf = [0; 1024];
+                    let mut line = String::new();
+
+                    loop {
+                        let read = stdout.read(&mut buf)?;
+
+                        if read == 0 {
+                            break;
+                        }
+
+                        // 7z redraws its progress using backspaces
+                        for char in String::from_utf8_lossy(&buf[..read]).chars() {
+                            if matches!(char, '\x08' | '\r' | '\n') {
+                                if let Some(percent) = parse_7z_progress(&line) {
+                                    (progress)(total * percent.min(100) / 100, total);
+                                }
+
+                                line.clear();
+                            }
+
+                            else {
+                                line.push(char);
+                            }
+                        }
+                    }
+                }
+
+                let status = child.wait()?;
+
+                let errors = stderr
+                    .and_then(|stderr| stderr.join().ok())
+                    .unwrap_or_default();
+
+                if !status.success() {
+                    anyhow::bail!("Archive is corrupted: {}", errors.trim());
+                }
+            }
+        }
+
+        (progress)(total, total);
+
+        Ok(())
+    }
+
+    /// Open new tar reader over the given archive file reader
+    /// 
+    /// Return `None` if the archive is not a tar one
+    fn open_tar<R: Read + Send + 'static>(&self, reader: R) -> anyhow::Result<Option<TarArchive<Box<dyn Read + Send>>>> {
+        let reader: Box<dyn Read + Send> = match self {
+            Archive::Tar(_, _)    => Box::new(reader),
+            Archive::TarXz(_, _)  => Box::new(XzReader::new(reader)),
+            Archive::TarGz(_, _)  => Box::new(GzReader::new(reader)),
+            Archive::TarBz2(_, _) => Box::new(Bz2Reader::new(reader)),
+            Archive::TarZst(_, _) => Box::new(ZstdReader::new(reader)?),
+
+            _ => return Ok(None)
+        };
+
+        Ok(Some(TarArchive::new(reader)))
+    }
+
+    /// Iterate over the archive entries
+    /// 
+    /// The archive is re-opened for this, so this method can be called
+    /// any amount of times and doesn't affect following `extract` call.
+    /// Zip archives are listed by their central directory, while tar ones
+    /// are decompressed in another thread as the entries are requested,
+    /// so stopping the iteration early skips the rest of the archive
+    pub fn entries(&self) -> anyhow::Result<Entries> {
+        match self {
+            Archive::Zip(path, _) => {
+                let zip = ZipArchive::new(File::open(path)?)?;
+
+                Ok(Entries(EntriesSource::Zip(zip, 0)))
+            }
+
+            Archive::Tar(path, _) |
+            Archive::TarXz(path, _) |
+            Archive::TarGz(path, _) |
+            Archive::TarBz2(path, _) |
+            Archive::TarZst(path, _) => {
+                let (sender, receiver) = std::sync::mpsc::sync_channel(64);
+
+                if let Some(mut tar) = self.open_tar(File::open(path)?)? {
+                    std::thread::spawn(move || {
+                        let entries = match tar.entries() {
+                            Ok(entries) => entries,
+                            Err(err) => {
+                                #[allow(unused_must_use)] {
+                                    sender.send(Err(err));
+                                }
+
+                                return;
+                            }
+                        };
+
+                        for entry in entries {
+                            let entry = entry.and_then(|entry| Entry::from_tar(&entry));
+                            let failed = entry.is_err();
+
+                            // Iterator was dropped so we can stop here
+                            if sender.send(entry).is_err() || failed {
+                                break;
+                            }
+                        }
+                    });
+                }
+
+                Ok(Entries(EntriesSource::Tar(receiver)))
+            }
+
+            Archive::SevenZ(path) |
+            Archive::ZipMultipart(path) => {
+                /*let (send, recv) = std::sync::mpsc::channel();
+
+                sz.for_each_entries(move |entry, _| {
+                    send.send(Entry {
+                        name: entry.name.clone(),
+                        size: Size::Both {
+                            compressed: entry.compressed_size,
+                            uncompressed: entry.size
+                        }
+                    });
+
+                    Ok(true)
+                });
+
+                while let Ok(entry) = recv.recv() {
+                    entries.push(entry);
+                }*/
+
+                let output = Command::new(get7z()?)
+                    .arg("l")
+                    .arg("-slt")
+                    .arg(path)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .output()?;
+
+                if !output.status.success() {
+                    anyhow::bail!("Failed to list archive entries: {:?}", path);
+                }
+
+                let entries = reader::parse_7z_listing(&String::from_utf8(output.stdout)?);
+
+                Ok(Entries(EntriesSource::List(entries.into_iter())))
+            }
+        }
+    }
+
+    /// Visit all the archive entries in a single pass
+    /// 
+    /// `callback` is called for every entry with a reader of its content.
+    /// Content which wasn't read by the callback is skipped, so tar archives
+    /// are decompressed only once no matter how many entries are read
+    /// 
+    /// 7z and multipart archives are not supported
+    pub fn for_each_entry(&self, mut callback: impl FnMut(&Entry, &mut dyn Read) -> std::io::Result<()>) -> anyhow::Result<()> {
+        match self {
+            Archive::Zip(path, _) => {
+                let mut zip = ZipArchive::new(File::open(path)?)?;
+
+                for i in 0..zip.len() {
+                    let mut entry = zip.by_index(i)?;
+                    let info = Entry::from_zip(&entry);
+
+                    (callback)(&info, &mut entry)?;
+                }
+            }
+
+            Archive::SevenZ(path) |
+            Archive::ZipMultipart(path) => anyhow::bail!("Archive entries can't be read in a single pass: {:?}", path),
+
+            _ => {
+                if let Some(mut tar) = self.open_tar(File::open(self.path())?)? {
+                    for entry in tar.entries()? {
+                        let mut entry = entry?;
+                        let info = Entry::from_tar(&entry)?;
+
+                        (callback)(&info, &mut entry)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get list of the archive entries
+    #[inline]
+    pub fn get_entries(&self) -> anyhow::Result<Vec<Entry>> {
+        self.entries()?.collect()
+    }
+
+    /// Read single archive entry without extracting the whole archive
+    /// 
+    /// Entry is read in another thread (or by the 7z process) and streamed
+    /// to the returned reader, so big files don't need to fit in memory
+    /// 
+    /// Every call re-opens the archive and starts a new thread. Zip entries
+    /// are found by the central directory, but tar archives have no index,
+    /// so compressed stream is decompressed from the start until the entry
+    /// is found. Reading many entries of a tar archive this way costs as much
+    /// as decompressing it once per entry, so `for_each_entry` should be used instead
+    /// 
+    /// ```no_run
+    /// use std::io::Read;
+    /// 
+    /// use anime_game_core::installer::archives::Archive;
+    /// 
+    /// let archive = Archive::open("/path/to/update.zip").unwrap();
+    /// 
+    /// let mut hdiff_files = String::new();
+    /// 
+    /// archive.read_entry("hdifffiles.txt").unwrap()
+    ///     .read_to_string(&mut hdiff_files)
+    ///     .unwrap();
+    /// ```
+    pub fn read_entry(&self, name: impl AsRef<str>) -> anyhow::Result<EntryReader> {
+        let name = name.as_ref().to_string();
+
+        match self {
+            Archive::SevenZ(path) |
+            Archive::ZipMultipart(path) => {
+                let child = Command::new(get7z()?)
+                    .arg("x")
+                    .arg("-so")
+                    .arg(path)
+                    .arg(&name)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+
+                Ok(EntryReader::from_process(child)?)
+            }
+
+            _ => {
+                let (sender, receiver) = std::sync::mpsc::sync_channel(4);
+
+                let file = File::open(self.path())?;
+                let tar = self.open_tar(file.try_clone()?)?;
+
+                std::thread::spawn(move || {
+                    let result = (|| -> std::io::Result<()> {
+                        let send_entry = |entry: &mut dyn Read| -> std::io::Result<()> {
+                            loop {
+                                let mut chunk = vec![0; 128 * 1024];
+
+                                let read = entry.read(&mut chunk)?;
+
+                                if read == 0 {
+                                    return Ok(());
+                                }
+
+                                chunk.truncate(read);
+
+                                // Reader was dropped so we can stop here
+                                if sender.send(Ok(chunk)).is_err() {
+                                    return Ok(());
+                                }
+                            }
+                        };
+
+                        match tar {
+                            Some(mut tar) => {
+                                for entry in tar.entries()? {
+                                    let mut entry = entry?;
+
+                                    if entry.path()?.to_string_lossy() == name {
+                                        return send_entry(&mut entry);
+                                    }
+                                }
+                            }
+
+                            None => {
+                                let mut zip = ZipArchive::new(file)?;
+
+                                let result = match zip.by_name(&name) {
+                                    Ok(mut entry) => Some(send_entry(&mut entry)),
+                                    Err(_) => None
+                                };
+
+                                if let Some(result) = result {
+                                    return result;
+                                }
+                            }
+                        }
+
+                        Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Archive entry not found: {name}")))
+                    })();
+
+                    if let Err(err) = result {
+                        #[allow(unused_must_use)] {
+                            sender.send(Err(err));
+                        }
+                    }
+                });
+
+                Ok(EntryReader::from_channel(receiver)?)
+            }
+        }
+    }
+
+    /// Extract archive using default options
+    #[inline]
+    pub fn extract<T: Into<PathBuf> + std::fmt::Debug>(&mut self, folder: T) -> anyhow::Result<()> {
+        self.extract_with(folder, &ExtractOptions::default(), |_, _| {})
+    }
+
+    /// Extract archive using specified options
+    /// 
+    /// Zip archives are extracted natively with `options.threads` workers, and `progress`
+    /// is called with `(current bytes, total bytes)` of the unpacked data.
+    /// Other formats report progress only when finished
+    /// 
+    /// `options.metadata` is applied to the extracted files by every backend,
+    /// including `unzip` and `7z` binaries
+    #[tracing::instrument(level = "debug", skip(self, progress))]
+    pub fn extract_with<T: Into<PathBuf> + std::fmt::Debug>(&mut self, folder: T, options: &ExtractOptions, progress: impl Fn(u64, u64) + Send + Sync) -> anyhow::Result<()> {
+        tracing::trace!("Extracting archive");
+
+        let folder = folder.into();
+
+        let total = match self {
+            Archive::Zip(archive, _) => {
+                if let Err(err) = parallel::extract_zip(archive, &folder, options, &progress) {
+                    tracing::warn!("Failed to extract zip archive natively, falling back to unzip: {err}");
+
+                    let output = Command::new("unzip")
+                        .arg("-q")
+                        .arg("-o")
+                        .arg(&archive)
+                        .arg("-d")
+                        .arg(&folder)
+                        .output()?;
+
+                    if !output.status.success() {
+                        anyhow::bail!("Failed to extract zip archive: {err}");
+                    }
+
+                    metadata::apply_entries(&folder, self.get_entries()?, &options.metadata)?;
+                }
+
+                return Ok(());
+            }
+
+            // Sizes are summed during unpacking so we don't decompress the archive again
+            Archive::Tar(_, tar) => unpack_tar(tar, &folder, &options.metadata)?,
+            Archive::TarXz(_, tar) => unpack_tar(tar, &folder, &options.metadata)?,
+            Archive::TarGz(_, tar) => unpack_tar(tar, &folder, &options.metadata)?,
+            Archive::TarBz2(_, tar) => unpack_tar(tar, &folder, &options.metadata)?,
+            Archive::TarZst(_, tar) => unpack_tar(tar, &folder, &options.metadata)?,
+
+            Archive::SevenZ(archive) |
+            Archive::ZipMultipart(archive) => {
+                // sevenz_rust::decompress_file(archive, folder.into())?;
+
+                // Workaround to allow 7z to overwrite files
+                // Somehow it manages to forbid itself to do this
+                Command::new("chmod")
+                    .arg("-R")
+                    .arg("755")
+                    .arg(&folder)
+                    .output()?;
+
+                // Extract the archive
+                Command::new(get7z()?)
+                    .arg("x")
+                    .arg(&archive)
+                    .arg(format!("-o{}", folder.to_string_lossy()))
+                    .arg("-aoa")
+                    .output()?;
+
+                let entries = self.get_entries()?;
+
+                let total = entries.iter()
+                    .map(|entry| entry.size.get_size())
+                    .sum();
+
+                metadata::apply_entries(&folder, entries, &options.metadata)?;
+
+                total
+            }
+        };
+
+        (progress)(total, total);
+
+        Ok(())
+    }
+}
+
+/// Unpack tar archive applying metadata policy to every entry
+/// 
+/// Return total size of the archive entries
+fn unpack_tar<R: Read>(tar: &mut TarArchive<R>, folder: &Path, policy: &MetadataPolicy) -> anyhow::Result<u64> {
+    tar.set_preserve_permissions(false);
+    tar.set_preserve_mtime(false);
+    tar.set_overwrite(true);
+
+    std::fs::create_dir_all(folder)?;
+
+    let mut folders = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut total = 0;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+
+        total += entry.size();
+
+        let name = entry.path()?.to_path_buf();
+        let header = entry.header();
+
+        let mode = header.mode().ok();
+        let mtime = header.mtime().ok();
+        let entry_type = header.entry_type();
+
+        let Some(path) = metadata::enclosed_path(folder, &name.to_string_lossy()) else {
+            tracing::warn!("Skipping archive entry with unsafe path: {:?}", name);
+
+            continue;
+        };
+
+        // Symlinks are created after all the files so files are never written through them
+        if entry_type.is_symlink() {
+            if let Some(target) = entry.link_name()? {
+                symlinks.push((path, target.to_path_buf()));
+            }
+
+            continue;
+        }
+
+        let path = if entry_type.is_dir() {
+            metadata::create_folder(folder, &path)?
+        } else {
+            metadata::create_parent(folder, &path)?
+        };
+
+        // Remove symlink which could be left by previous installation
+        // so we don't write the file's content to its target
+        if !entry_type.is_dir() && path.symlink_metadata().map(|metadata| metadata.is_symlink()).unwrap_or(false) {
+            std::fs::remove_file(&path)?;
+        }
+
+        if !entry.unpack_in(folder)? {
+            continue;
+        }
+
+        if entry_type.is_dir() {
+            folders.push((path, mode, mtime));
+        }
+
+        else if entry_type.is_file() || entry_type.is_hard_link() {
+            policy.apply(&path, mode, mtime)?;
+        }
+    }
+
+    metadata::create_symlinks(folder, symlinks, policy)?;
+
+    metadata::apply_folders(folders, policy)?;
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    pub fn test_multipart_size() -> std::io::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-archive-size");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        std::fs::write(folder.join("archive.z01"), [0; 10])?;
+        std::fs::write(folder.join("archive.z02"), [0; 20])?;
+        std::fs::write(folder.join("archive.zip"), [0; 5])?;
+
+        std::fs::write(folder.join("archive.7z.001"), [0; 7])?;
+        std::fs::write(folder.join("archive.7z.002"), [0; 3])?;
+
+        assert_eq!(get_archive_size(&folder.join("archive.z01")), 35);
+        assert_eq!(get_archive_size(&folder.join("archive.7z.001")), 10);
+        assert_eq!(get_archive_size(&folder.join("archive.zip")), 5);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_corrupted_archives() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-archive-test");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        let content = "integrity test content ".repeat(64);
+
+        // Zip archive with flipped byte in the stored entry
+        let mut zip = zip::ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        zip.start_file("file.txt", zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored))?;
+        zip.write_all(content.as_bytes())?;
+        zip.finish()?;
+
+        assert!(Archive::open(folder.join("archive.zip"))?.test(|_, _| {}).is_ok());
+
+        let mut bytes = std::fs::read(folder.join("archive.zip"))?;
+
+        let offset = bytes.windows(content.len())
+            .position(|window| window == content.as_bytes())
+            .unwrap();
+
+        bytes[offset + 100] ^= 0xFF;
+
+        std::fs::write(folder.join("archive.zip"), bytes)?;
+
+        assert!(Archive::open(folder.join("archive.zip"))?.test(|_, _| {}).is_err());
+
+        // Truncated tar.gz archive
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(File::create(folder.join("archive.tar.gz"))?, flate2::Compression::default()));
+
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        builder.append_data(&mut header, "file.txt", content.as_bytes())?;
+        builder.into_inner()?.finish()?;
+
+        assert!(Archive::open(folder.join("archive.tar.gz"))?.test(|_, _| {}).is_ok());
+
+        let bytes = std::fs::read(folder.join("archive.tar.gz"))?;
+
+        std::fs::write(folder.join("archive.tar.gz"), &bytes[..bytes.len() - 12])?;
+
+        assert!(Archive::open(folder.join("archive.tar.gz"))?.test(|_, _| {}).is_err());
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_entries() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-archive-entries");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        let files = [("a.txt", "hello"), ("sub/b.txt", "hello world")];
+
+        let mut zip = zip::ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        for (name, content) in files {
+            zip.start_file(name, zip::write::FileOptions::default().unix_permissions(0o755))?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        zip.finish()?;
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(File::create(folder.join("archive.tar.gz"))?, flate2::Compression::default()));
+
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_mtime(1700000000);
+            header.set_cksum();
+
+            builder.append_data(&mut header, name, content.as_bytes())?;
+        }
+
+        builder.into_inner()?.finish()?;
+
+        for name in ["archive.zip", "archive.tar.gz"] {
+            let archive = Archive::open(folder.join(name))?;
+
+            let entries = archive.get_entries()?;
+
+            assert_eq!(entries.len(), files.len());
+
+            for (entry, (name, content)) in entries.iter().zip(files) {
+                assert_eq!(entry.name, name);
+                assert_eq!(entry.mode.map(|mode| mode & 0o777), Some(0o755));
+
+                match entry.size {
+                    Size::Both { uncompressed, .. } => assert_eq!(uncompressed, content.len() as u64),
+                    size => assert_eq!(size.get_size(), content.len() as u64)
+                }
+
+                let mut read = String::new();
+
+                archive.read_entry(name)?.read_to_string(&mut read)?;
+
+                assert_eq!(read, content);
+            }
+
+            assert!(archive.read_entry("missing.txt").is_err());
+
+            // Entries can be taken one by one without listing the whole archive
+            let mut entries = archive.entries()?;
+
+            assert_eq!(entries.next().transpose()?.map(|entry| entry.name).as_deref(), Some(files[0].0));
+        }
+
+        // Broken tar archive is reported by the iterator instead of cutting the list
+        let bytes = std::fs::read(folder.join("archive.tar.gz"))?;
+
+        std::fs::write(folder.join("truncated.tar.gz"), &bytes[..bytes.len() / 2])?;
+
+        assert!(Archive::open(folder.join("truncated.tar.gz"))?.entries()?.any(|entry| entry.is_err()));
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_symlink_chains() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-symlink-chains");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        // d -> .
+        // d/d/x -> ../../escaped
+        // d/d/x/pwned
+        let mut zip = zip::ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        zip.add_symlink("d", ".", zip::write::FileOptions::default())?;
+        zip.add_symlink("d/d/x", "../../escaped", zip::write::FileOptions::default())?;
+        zip.start_file("d/d/x/pwned", zip::write::FileOptions::default())?;
+        zip.write_all(b"pwned")?;
+        zip.finish()?;
+
+        let mut builder = tar::Builder::new(File::create(folder.join("archive.tar"))?);
+
+        for (name, target) in [("d", "."), ("d/d/x", "../../escaped")] {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+
+            builder.append_link(&mut header, name, target)?;
+        }
+
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        builder.append_data(&mut header, "d/d/x/pwned", b"pwned".as_slice())?;
+        builder.into_inner()?;
+
+        for name in ["archive.zip", "archive.tar"] {
+            let output = folder.join(name.replace('.', "_")).join("a/output");
+
+            Archive::open(folder.join(name))?.extract_with(&output, &ExtractOptions::default(), |_, _| {})?;
+
+            assert!(!output.join("../escaped").exists());
+            assert!(!output.join("../../escaped").exists());
+
+            assert_eq!(std::fs::read_to_string(output.join("d/d/x/pwned"))?, "pwned");
+        }
+
+        // Make sure the zip archive wasn't extracted by the unzip fallback
+        let output = folder.join("native/a/output");
+
+        parallel::extract_zip(&folder.join("archive.zip"), &output, &ExtractOptions::default(), &|_, _| {})?;
+
+        assert!(!output.join("../escaped").exists());
+        assert!(!output.join("../../escaped").exists());
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_tar_extraction() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-tar-extraction");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        let mut builder = tar::Builder::new(File::create(folder.join("archive.tar"))?);
+
+        for (name, content) in [("a.txt", "hello"), ("sub/b.txt", "hello world")] {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+
+            builder.append_data(&mut header, name, content.as_bytes())?;
+        }
+
+        builder.into_inner()?;
+
+        let progress = std::sync::Mutex::new(Vec::new());
+
+        Archive::open(folder.join("archive.tar"))?.extract_with(folder.join("output"), &ExtractOptions::default(), |curr, total| {
+            progress.lock().unwrap().push((curr, total));
+        })?;
+
+        assert_eq!(std::fs::read_to_string(folder.join("output/sub/b.txt"))?, "hello world");
+        assert_eq!(progress.into_inner().unwrap(), vec![(16, 16)]);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
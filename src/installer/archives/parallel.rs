@@ -0,0 +1,317 @@
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use zip::ZipArchive;
+
+use super::ExtractOptions;
+use super::metadata;
+
+/// Amount of bytes workers read from the archive at once
+const CHUNK_SIZE: usize = 1024 * 1024; // 1 MB
+
+/// Limits average amount of bytes written per second by all the workers
+struct IoLimiter {
+    limit: Option<u64>,
+    started: Instant,
+    written: AtomicU64
+}
+
+impl IoLimiter {
+    fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            started: Instant::now(),
+            written: AtomicU64::new(0)
+        }
+    }
+
+    /// Register written bytes and sleep if workers are faster than allowed
+    fn consume(&self, bytes: u64) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+
+        let written = self.written.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        let expected = Duration::from_secs_f64(written as f64 / limit.max(1) as f64);
+        let elapsed = self.started.elapsed();
+
+        if expected > elapsed {
+            std::thread::sleep(expected - elapsed);
+        }
+    }
+}
+
+/// Extract zip archive using multiple threads
+/// 
+/// Every worker opens its own archive file handle and takes
+/// next unprocessed entry until all of them are extracted
+pub(super) fn extract_zip(archive: &Path, folder: &Path, options: &ExtractOptions, progress: &(impl Fn(u64, u64) + Send + Sync)) -> anyhow::Result<()> {
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+
+    let mut total = 0;
+
+    for i in 0..zip.len() {
+        total += zip.by_index_raw(i)?.size();
+    }
+
+    let entries = zip.len();
+
+    let next_entry = AtomicUsize::new(0);
+    let current = AtomicU64::new(0);
+    let failed = AtomicBool::new(false);
+    let error = Mutex::new(None);
+
+    // Folders metadata is applied when all the files are extracted
+    let folders = Mutex::new(Vec::new());
+
+    // Symlinks are created when all the files are extracted
+    // so workers never write files through them
+    let symlinks = Mutex::new(Vec::new());
+
+    let limiter = IoLimiter::new(options.io_limit);
+
+    let worker = || -> anyhow::Result<()> {
+        let mut zip = ZipArchive::new(File::open(archive)?)?;
+        let mut buf = vec![0; CHUNK_SIZE];
+
+        loop {
+            if failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let i = next_entry.fetch_add(1, Ordering::Relaxed);
+
+            if i >= entries {
+                return Ok(());
+            }
+
+            let mut entry = zip.by_index(i)?;
+
+            let Some(path) = entry.enclosed_name().map(|path| folder.join(path)) else {
+                anyhow::bail!("Archive entry has unsafe path: {}", entry.name());
+            };
+
+            let mode = entry.unix_mode();
+            let mtime = Some(super::zip_datetime_to_unix(entry.last_modified()));
+
+            if entry.is_dir() {
+                let path = metadata::create_folder(folder, &path)?;
+
+                folders.lock().unwrap().push((path, mode, mtime));
+
+                continue;
+            }
+
+            // Symlinks are stored as files with the target path as content
+            if mode.map(metadata::is_symlink_mode).unwrap_or(false) {
+                let mut target = String::new();
+
+                entry.read_to_string(&mut target)?;
+
+                symlinks.lock().unwrap().push((i, path, PathBuf::from(target)));
+
+                (progress)(current.fetch_add(entry.size(), Ordering::Relaxed) + entry.size(), total);
+
+                continue;
+            }
+
+            let path = metadata::create_parent(folder, &path)?;
+
+            // Remove file which could be left by previous installation instead of
+            // truncating it, so neither symlink's target nor hardlinked copies
+            // (e.g. of another installation) are overwritten
+            if path.symlink_metadata().map(|metadata| !metadata.is_dir()).unwrap_or(false) {
+                std::fs::remove_file(&path)?;
+            }
+
+            let mut file = File::create(&path)?;
+
+            loop {
+                let read = entry.read(&mut buf)?;
+
+                if read == 0 {
+                    break;
+                }
+
+                file.write_all(&buf[..read])?;
+
+                limiter.consume(read as u64);
+
+                (progress)(current.fetch_add(read as u64, Ordering::Relaxed) + read as u64, total);
+            }
+
+            drop(file);
+
+            options.metadata.apply(&path, mode, mtime)?;
+        }
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.clamp(1, entries.max(1)) {
+            scope.spawn(|| {
+                if let Err(err) = worker() {
+                    failed.store(true, Ordering::Relaxed);
+
+                    error.lock().unwrap().get_or_insert(err);
+                }
+            });
+        }
+    });
+
+    if let Some(err) = error.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    let mut symlinks = symlinks.into_inner().unwrap();
+
+    // Keep archive order so links to other links are created after them
+    symlinks.sort_by_key(|(i, _, _)| *i);
+
+    metadata::create_symlinks(folder, symlinks.into_iter().map(|(_, path, target)| (path, target)), &options.metadata)?;
+
+    metadata::apply_folders(folders.into_inner().unwrap(), &options.metadata)?;
+
+    (progress)(total, total);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    /// Read all the files and symlinks of the folder, relative to this folder
+    fn read_tree(root: &Path, path: &Path, tree: &mut BTreeMap<PathBuf, Vec<u8>>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            let relative = entry.path().strip_prefix(root).unwrap().to_path_buf();
+
+            if file_type.is_symlink() {
+                tree.insert(relative, std::fs::read_link(entry.path())?.to_string_lossy().as_bytes().to_vec());
+            }
+
+            else if file_type.is_dir() {
+                read_tree(root, &entry.path(), tree)?;
+            }
+
+            else {
+                tree.insert(relative, std::fs::read(entry.path())?);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_threads_consistency() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-parallel-extraction");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        let mut zip = ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        for i in 0..64 {
+            zip.start_file(format!("folder_{}/file_{i}.txt", i % 4), FileOptions::default())?;
+            zip.write_all(format!("file {i} ").repeat(i * 100).as_bytes())?;
+        }
+
+        zip.add_symlink("link.txt", "folder_0/file_4.txt", FileOptions::default())?;
+        zip.finish()?;
+
+        for threads in [1, 4] {
+            let output = folder.join(format!("output_{threads}"));
+
+            extract_zip(&folder.join("archive.zip"), &output, &ExtractOptions::default().with_threads(threads), &|_, _| {})?;
+        }
+
+        let mut single = BTreeMap::new();
+        let mut multi = BTreeMap::new();
+
+        read_tree(&folder.join("output_1"), &folder.join("output_1"), &mut single)?;
+        read_tree(&folder.join("output_4"), &folder.join("output_4"), &mut multi)?;
+
+        assert_eq!(single.len(), 65);
+        assert_eq!(single, multi);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_io_limit() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-parallel-io-limit");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(&folder)?;
+
+        let mut zip = ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        for i in 0..4 {
+            zip.start_file(format!("file_{i}"), FileOptions::default())?;
+            zip.write_all(&[0; 64 * 1024])?;
+        }
+
+        zip.finish()?;
+
+        // 256 KB with 512 KB/s limit should take at least half a second
+        let started = Instant::now();
+
+        extract_zip(&folder.join("archive.zip"), &folder.join("output"), &ExtractOptions::default().with_threads(2).with_io_limit(Some(512 * 1024)), &|_, _| {})?;
+
+        assert!(started.elapsed() >= Duration::from_millis(450));
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_hardlinked_files() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-parallel-hardlinks");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(folder.join("output"))?;
+
+        std::fs::write(folder.join("peer"), "old content")?;
+        std::fs::hard_link(folder.join("peer"), folder.join("output/file.txt"))?;
+
+        let mut zip = ZipWriter::new(File::create(folder.join("archive.zip"))?);
+
+        zip.start_file("file.txt", FileOptions::default())?;
+        zip.write_all(b"new content")?;
+        zip.finish()?;
+
+        extract_zip(&folder.join("archive.zip"), &folder.join("output"), &ExtractOptions::default(), &|_, _| {})?;
+
+        // File of another installation sharing the same inode is not changed
+        assert_eq!(std::fs::read_to_string(folder.join("output/file.txt"))?, "new content");
+        assert_eq!(std::fs::read_to_string(folder.join("peer"))?, "old content");
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::io::Read;
+use std::process::{Child, ChildStdout};
+use std::sync::mpsc::Receiver;
+
+use super::{Entry, Size};
+
+/// Streaming reader of a single archive entry
+/// 
+/// Returned by `Archive::read_entry`
+pub struct EntryReader {
+    inner: Inner
+}
+
+enum Inner {
+    /// Entry is read by another thread and sent by chunks
+    Channel {
+        receiver: Receiver<std::io::Result<Vec<u8>>>,
+        chunk: Vec<u8>,
+        offset: usize
+    },
+
+    /// Entry is written to the stdout of the 7z process
+    Process {
+        child: Child,
+        stdout: ChildStdout
+    }
+}
+
+impl EntryReader {
+    /// Wait for the first chunk from the reading thread
+    /// 
+    /// Return error if the thread couldn't find the entry
+    pub(super) fn from_channel(receiver: Receiver<std::io::Result<Vec<u8>>>) -> std::io::Result<Self> {
+        let chunk = match receiver.recv() {
+            Ok(chunk) => chunk?,
+            Err(_) => Vec::new()
+        };
+
+        Ok(Self {
+            inner: Inner::Channel {
+                receiver,
+                chunk,
+                offset: 0
+            }
+        })
+    }
+
+    #[inline]
+    pub(super) fn from_process(mut child: Child) -> std::io::Result<Self> {
+        let Some(stdout) = child.stdout.take() else {
+            return Err(std::io::Error::other("7z stdout is not captured"));
+        };
+
+        Ok(Self {
+            inner: Inner::Process {
+                child,
+                stdout
+            }
+        })
+    }
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            Inner::Channel { receiver, chunk, offset } => {
+                while *offset >= chunk.len() {
+                    match receiver.recv() {
+                        Ok(next) => {
+                            *chunk = next?;
+                            *offset = 0;
+                        }
+
+                        // Reading thread is finished
+                        Err(_) => return Ok(0)
+                    }
+                }
+
+                let read = buf.len().min(chunk.len() - *offset);
+
+                buf[..read].copy_from_slice(&chunk[*offset..*offset + read]);
+
+                *offset += read;
+
+                Ok(read)
+            }
+
+            Inner::Process { child, stdout } => {
+                let read = stdout.read(buf)?;
+
+                if read == 0 && !buf.is_empty() && !child.wait()?.success() {
+                    return Err(std::io::Error::other("7z failed to extract archive entry"));
+                }
+
+                Ok(read)
+            }
+        }
+    }
+}
+
+impl Drop for EntryReader {
+    fn drop(&mut self) {
+        if let Inner::Process { child, .. } = &mut self.inner {
+            #[allow(unused_must_use)] {
+                child.kill();
+                child.wait();
+            }
+        }
+    }
+}
+
+/// Convert `YYYY-MM-DD hh:mm:ss` date to unix timestamp
+fn parse_7z_date(date: &str) -> Option<u64> {
+    let date = date.get(..19)?;
+
+    let year = date.get(0..4)?.parse::<i64>().ok()?;
+    let month = date.get(5..7)?.parse::<i64>().ok()?;
+    let day = date.get(8..10)?.parse::<i64>().ok()?;
+
+    let hour = date.get(11..13)?.parse::<i64>().ok()?;
+    let minute = date.get(14..16)?.parse::<i64>().ok()?;
+    let second = date.get(17..19)?.parse::<i64>().ok()?;
+
+    let timestamp = super::days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+
+    u64::try_from(timestamp).ok()
+}
+
+/// Get unix permissions from the 7z attributes
+/// 
+/// `A_ -rw-r--r--` -> `0o644`
+fn parse_7z_mode(attributes: &str) -> Option<u32> {
+    let permissions = attributes.split_whitespace()
+        .find(|word| word.len() == 10 && matches!(word.as_bytes()[0], b'-' | b'd' | b'l'))?;
+
+    let mut mode = 0;
+
+    for (i, char) in permissions.bytes().skip(1).enumerate() {
+        if char != b'-' {
+            mode |= 1 << (8 - i);
+        }
+    }
+
+    Some(mode)
+}
+
+/// Parse technical listing of the archive (`7z l -slt`)
+/// 
+/// ```text
+/// ----------
+/// Path = GenshinImpact_Data/globalgamemanagers
+/// Size = 271564
+/// Packed Size = 75216
+/// Modified = 2023-09-15 10:20:44
+/// Attributes = A_ -rw-r--r--
+/// CRC = 3610A686
+/// ```
+pub(super) fn parse_7z_listing(output: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+
+    // Everything before the separator describes the archive itself
+    let Some((_, listing)) = output.split_once("\n----------") else {
+        return entries;
+    };
+
+    for block in listing.replace('\r', "").split("\n\n") {
+        let mut name = None;
+        let mut size = 0;
+        let mut mode = None;
+        let mut mtime = None;
+        let mut crc32 = None;
+
+        for line in block.lines() {
+            let Some((key, value)) = line.split_once(" = ") else {
+                continue;
+            };
+
+            match key {
+                "Path" => name = Some(value.to_string()),
+                "Size" => size = value.parse().unwrap_or(0),
+                "Modified" => mtime = parse_7z_date(value),
+                "Attributes" => mode = parse_7z_mode(value),
+                "CRC" => crc32 = u32::from_str_radix(value, 16).ok(),
+
+                _ => ()
+            }
+        }
+
+        if let Some(name) = name {
+            entries.push(Entry {
+                name,
+                size: Size::Uncompressed(size),
+                mode,
+                mtime,
+                crc32
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_7z_listing() {
+        let output = [
+            "Listing archive: game.7z",
+            "",
+            "--",
+            "Path = game.7z",
+            "Type = 7z",
+            "Physical Size = 75400",
+            "",
+            "----------",
+            "Path = Game_Data/globalgamemanagers",
+            "Size = 271564",
+            "Packed Size = 75216",
+            "Modified = 2023-09-15 10:20:44.1234567",
+            "Attributes = A_ -rwxr-xr-x",
+            "CRC = 3610A686",
+            "",
+            "Path = Game_Data",
+            "Size = 0",
+            "Packed Size = 0",
+            "Modified = 1970-01-02 00:00:00",
+            "Attributes = D_ drwxr-xr-x",
+            "CRC = ",
+            ""
+        ].join("\n");
+
+        let entries = parse_7z_listing(&output);
+
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].name, "Game_Data/globalgamemanagers");
+        assert_eq!(entries[0].size, Size::Uncompressed(271564));
+        assert_eq!(entries[0].mode, Some(0o755));
+        assert_eq!(entries[0].mtime, Some(1694773244));
+        assert_eq!(entries[0].crc32, Some(0x3610A686));
+
+        assert_eq!(entries[1].name, "Game_Data");
+        assert_eq!(entries[1].mtime, Some(86400));
+        assert_eq!(entries[1].crc32, None);
+    }
+}
@@ -0,0 +1,381 @@
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+
+use serde::{Serialize, Deserialize};
+
+use zip::ZipWriter;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+
+use tar::{Builder as TarBuilder, Header};
+use zstd::stream::write::Encoder as ZstdWriter;
+
+use crate::repairer::{IntegrityFile, PkgVersionEntry};
+use crate::repairer::hash::HashingReader;
+use crate::repairer::{REPAIRER_FILES, is_repairer_temp_file};
+
+/// Name of the manifest file written into the archive root
+pub const MANIFEST_NAME: &str = "pkg_version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    /// `.tar.zst`
+    TarZst,
+
+    /// `.zip`
+    Zip
+}
+
+impl ArchiveFormat {
+    /// Get archive format from the file name
+    /// 
+    /// - `backup.tar.zst` -> `Some(TarZst)`
+    /// - `backup.zip` -> `Some(Zip)`
+    /// - `backup.7z` -> `None`
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref().to_string_lossy();
+
+        if path.ends_with(".tar.zst") {
+            Some(Self::TarZst)
+        }
+
+        else if path.ends_with(".zip") {
+            Some(Self::Zip)
+        }
+
+        else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::TarZst => "tar.zst",
+            Self::Zip => "zip"
+        }
+    }
+}
+
+/// List all the files from the folder, relative to this folder
+/// 
+/// Repairer's files from the game root and its temporary files are not included
+fn list_files(root: &Path, path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+
+        if path == root && REPAIRER_FILES.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+
+        if file_type.is_file() && is_repairer_temp_file(&entry.file_name()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            list_files(root, &entry.path(), files)?;
+        }
+
+        else if file_type.is_file() {
+            if let Ok(path) = entry.path().strip_prefix(root) {
+                files.push(path.to_path_buf());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Packs installed game folder into a new archive
+/// 
+/// Produced archives can be installed back by the `Installer`
+/// 
+/// ```no_run
+/// use anime_game_core::installer::archives::{ArchiveWriter, ArchiveFormat};
+/// 
+/// ArchiveWriter::new(ArchiveFormat::TarZst)
+///     .with_manifest(true)
+///     .write("/path/to/game", "/path/to/backup.tar.zst", |curr, total| {
+///         println!("Packed {curr} of {total} bytes");
+///     })
+///     .expect("Failed to pack the game");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveWriter {
+    pub format: ArchiveFormat,
+
+    /// List of files relative to the game folder which should be packed
+    /// 
+    /// All the files from the game folder are packed if `None`
+    pub files: Option<Vec<PathBuf>>,
+
+    /// Write `pkg_version` manifest with all the packed files into the archive root
+    pub manifest: bool,
+
+    /// Compression level. `1..=22` for tar.zst and `0..=9` for zip
+    /// 
+    /// Format's default level is used if `None`
+    pub compression_level: Option<i32>
+}
+
+impl ArchiveWriter {
+    #[inline]
+    pub fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            files: None,
+            manifest: false,
+            compression_level: None
+        }
+    }
+
+    #[inline]
+    /// Pack only files listed in the game's integrity files
+    /// 
+    /// This way the game's caches and logs will not be packed
+    pub fn with_integrity_files<'a>(mut self, files: impl IntoIterator<Item = &'a IntegrityFile>) -> Self {
+        self.files = Some(files.into_iter().map(|file| file.path.clone()).collect());
+
+        self
+    }
+
+    #[inline]
+    /// Specify list of files relative to the game folder which should be packed
+    pub fn with_files(mut self, files: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.files = Some(files.into_iter().collect());
+
+        self
+    }
+
+    #[inline]
+    /// Specify whether `pkg_version` manifest should be written into the archive
+    pub fn with_manifest(mut self, manifest: bool) -> Self {
+        self.manifest = manifest;
+
+        self
+    }
+
+    #[inline]
+    /// Specify archive compression level
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.compression_level = Some(level);
+
+        self
+    }
+
+    /// Pack `game_dir` into the `archive` file
+    /// 
+    /// `progress` is called with `(current bytes, total bytes)` of the packed files
+    #[tracing::instrument(level = "debug", skip(self, progress))]
+    pub fn write(&self, game_dir: impl AsRef<Path> + std::fmt::Debug, archive: impl AsRef<Path> + std::fmt::Debug, progress: impl Fn(u64, u64)) -> anyhow::Result<()> {
+        tracing::debug!("Packing archive");
+
+        let game_dir = game_dir.as_ref();
+
+        let mut files = match &self.files {
+            Some(files) => files.clone(),
+            None => {
+                let mut files = Vec::new();
+
+                list_files(game_dir, game_dir, &mut files)?;
+
+                files
+            }
+        };
+
+        // Don't pack outdated manifest if we write a new one
+        if self.manifest {
+            files.retain(|file| file != Path::new(MANIFEST_NAME));
+        }
+
+        files.sort();
+
+        let mut total = 0;
+
+        for file in &files {
+            match game_dir.join(file).metadata() {
+                Ok(metadata) => total += metadata.len(),
+                Err(err) => anyhow::bail!("Failed to read metadata of {:?}: {err}", file)
+            }
+        }
+
+        let mut current = 0;
+        let mut manifest = String::new();
+
+        let output = File::create(archive.as_ref())?;
+
+        match self.format {
+            ArchiveFormat::TarZst => {
+                let mut encoder = ZstdWriter::new(output, self.compression_level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))?;
+
+                // Allows `Archive::test` to find corrupted frames
+                encoder.include_checksum(true)?;
+
+                let mut builder = TarBuilder::new(encoder);
+
+                for file in &files {
+                    let source = File::open(game_dir.join(file))?;
+                    let metadata = source.metadata()?;
+
+                    let mut header = Header::new_gnu();
+
+                    header.set_metadata(&metadata);
+
+                    let packed = current;
+
+                    let mut reader = HashingReader::new(source, |hashed| (progress)(packed + hashed, total));
+
+                    builder.append_data(&mut header, file, &mut reader)?;
+
+                    current += reader.hashed();
+                    manifest += &get_manifest_line(file, reader.finalize(), metadata.len());
+                }
+
+                if self.manifest {
+                    let mut header = Header::new_gnu();
+
+                    header.set_size(manifest.len() as u64);
+                    header.set_mode(0o644);
+                    header.set_mtime(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs());
+
+                    builder.append_data(&mut header, MANIFEST_NAME, manifest.as_bytes())?;
+                }
+
+                builder.into_inner()?
+                    .finish()?
+                    .flush()?;
+            }
+
+            ArchiveFormat::Zip => {
+                let mut zip = ZipWriter::new(output);
+
+                for file in &files {
+                    let source = File::open(game_dir.join(file))?;
+                    let metadata = source.metadata()?;
+
+                    let options = FileOptions::default()
+                        .compression_method(CompressionMethod::Deflated)
+                        .compression_level(self.compression_level)
+                        .unix_permissions(metadata.mode() & 0o777)
+                        .last_modified_time(super::unix_to_zip_datetime(metadata.mtime()))
+                        .large_file(metadata.len() >= u32::MAX as u64);
+
+                    zip.start_file(file.to_string_lossy(), options)?;
+
+                    let packed = current;
+
+                    let mut reader = HashingReader::new(source, |hashed| (progress)(packed + hashed, total));
+
+                    std::io::copy(&mut reader, &mut zip)?;
+
+                    current += reader.hashed();
+                    manifest += &get_manifest_line(file, reader.finalize(), metadata.len());
+                }
+
+                if self.manifest {
+                    let options = FileOptions::default()
+                        .compression_method(CompressionMethod::Deflated)
+                        .compression_level(self.compression_level)
+                        .unix_permissions(0o644);
+
+                    zip.start_file(MANIFEST_NAME, options)?;
+                    zip.write_all(manifest.as_bytes())?;
+                }
+
+                zip.finish()?
+                    .flush()?;
+            }
+        }
+
+        (progress)(total, total);
+
+        Ok(())
+    }
+}
+
+/// Format `pkg_version` line for the packed file
+/// 
+/// `{"remoteName": "UnityPlayer.dll", "md5": "8c8c3d845b957e4cb84c662bed44d072", "fileSize": 33466104}`
+fn get_manifest_line(file: &Path, md5: String, size: u64) -> String {
+    let entry = PkgVersionEntry::new(file.to_string_lossy(), md5, size);
+
+    format!("{}\r\n", entry.to_line())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::installer::archives::Archive;
+    use crate::repairer::PkgVersion;
+    use crate::repairer::hash::{md5_file, md5_reader};
+    use crate::repairer::cache::{CACHE_FILE_NAME, CACHE_TEMP_FILE_NAME};
+    use crate::repairer::quarantine::QUARANTINE_FOLDER_NAME;
+
+    use super::*;
+
+    #[test]
+    pub fn test_roundtrip() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-archive-writer");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        let game_dir = folder.join("game");
+
+        std::fs::create_dir_all(game_dir.join("GameData/StreamingAssets"))?;
+        std::fs::create_dir_all(game_dir.join(QUARANTINE_FOLDER_NAME).join("1700000000"))?;
+
+        std::fs::write(game_dir.join("UnityPlayer.dll"), "unity player")?;
+        std::fs::write(game_dir.join("GameData/data.unity3d"), "game data ".repeat(1000))?;
+        std::fs::write(game_dir.join("GameData/StreamingAssets/blocks.blk"), [7; 4096])?;
+
+        std::fs::write(game_dir.join(CACHE_FILE_NAME), "{}")?;
+        std::fs::write(game_dir.join(CACHE_TEMP_FILE_NAME), "{}")?;
+        std::fs::write(game_dir.join(QUARANTINE_FOLDER_NAME).join("1700000000/old.dll"), "old")?;
+
+        let files = ["GameData/StreamingAssets/blocks.blk", "GameData/data.unity3d", "UnityPlayer.dll"];
+
+        for format in [ArchiveFormat::TarZst, ArchiveFormat::Zip] {
+            let path = folder.join(format!("backup.{}", format.extension()));
+
+            ArchiveWriter::new(format)
+                .with_manifest(true)
+                .write(&game_dir, &path, |_, _| {})?;
+
+            let archive = Archive::open(&path)?;
+
+            let mut names = archive.get_entries()?
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect::<Vec<_>>();
+
+            names.sort();
+
+            assert_eq!(names, [files.as_slice(), &[MANIFEST_NAME]].concat());
+
+            let mut manifest = String::new();
+
+            archive.read_entry(MANIFEST_NAME)?.read_to_string(&mut manifest)?;
+
+            let manifest = PkgVersion::parse(&manifest)?;
+
+            assert_eq!(manifest.entries.len(), files.len());
+
+            for entry in manifest.entries {
+                let expected = md5_file(game_dir.join(&entry.remote_name))?;
+
+                assert_eq!(entry.md5, expected);
+                assert_eq!(md5_reader(archive.read_entry(&entry.remote_name)?, |_| {})?, expected);
+            }
+        }
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
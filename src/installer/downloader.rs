@@ -6,6 +6,7 @@ use serde::{Serialize, Deserialize};
 use thiserror::Error;
 
 use super::free_space;
+use super::event_log::{LoggedEvent, EventKind};
 use crate::prettify_bytes::prettify_bytes;
 
 /// Default amount of bytes `Downloader::download` method will send to `downloader` function
@@ -44,6 +45,13 @@ pub enum DownloadingError {
     Minreq(String)
 }
 
+impl LoggedEvent for DownloadingError {
+    #[inline]
+    fn kind(&self) -> EventKind {
+        EventKind::Error
+    }
+}
+
 impl From<minreq::Error> for DownloadingError {
     fn from(error: minreq::Error) -> Self {
         DownloadingError::Minreq(error.to_string())
@@ -194,7 +202,7 @@ impl Downloader {
         if self.check_free_space {
             tracing::debug!("Checking free space availability");
 
-            match free_space::available(&path) {
+            match free_space::unreserved(&path) {
                 Some(space) => {
                     if let Some(mut required) = self.length() {
                         required -= downloaded as u64;
@@ -0,0 +1,434 @@
+use std::path::{Path, PathBuf};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+/// Default max size of the log file before it's rotated
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024; // 10 MB
+
+/// Default amount of rotated log files kept on the disk
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Some operation was started or finished
+    Phase,
+
+    /// Progress of the current operation
+    Progress,
+
+    /// Operation failed
+    Error,
+
+    /// Operation is repeated after a failure
+    Retry
+}
+
+/// Value which can be written to the event log
+/// 
+/// Every enum variant should be mapped to its kind explicitly,
+/// so renaming a variant doesn't change how it's logged
+pub trait LoggedEvent: Serialize {
+    /// Get kind of the event
+    fn kind(&self) -> EventKind;
+}
+
+/// Single line of the log file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// Unix timestamp in milliseconds
+    pub timestamp: u128,
+
+    pub kind: EventKind,
+
+    /// Serialized event value
+    pub event: serde_json::Value
+}
+
+#[derive(Debug)]
+struct State {
+    file: Option<File>,
+    size: u64,
+
+    /// Last time progress events of some name were logged
+    last_progress: HashMap<String, Instant>
+}
+
+/// Appends timestamped events to the JSON Lines log file
+/// 
+/// Any `LoggedEvent` can be logged, including `installer::Update`,
+/// games' `DiffUpdate` and `DownloadingError`. Other serializable values
+/// can be written with an explicitly specified kind
+/// 
+/// ```no_run
+/// use anime_game_core::installer::event_log::EventLog;
+/// use anime_game_core::installer::installer::Installer;
+/// 
+/// let log = EventLog::new("/path/to/install.log");
+/// 
+/// Installer::new("https://example.com/game.zip").unwrap()
+///     .with_event_log(log)
+///     .install("/path/to/game", |update| println!("{update:?}"))
+///     .expect("Failed to install the game");
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    path: PathBuf,
+
+    /// Max size of the log file before it's renamed to `<name>.1`
+    pub max_size: u64,
+
+    /// Amount of rotated log files kept on the disk
+    pub max_files: usize,
+
+    /// Min interval between two logged progress events of the same kind
+    /// 
+    /// All the progress events are logged if `None`
+    pub progress_interval: Option<Duration>,
+
+    state: Arc<Mutex<State>>
+}
+
+impl EventLog {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+            progress_interval: None,
+            state: Arc::new(Mutex::new(State {
+                file: None,
+                size: 0,
+                last_progress: HashMap::new()
+            }))
+        }
+    }
+
+    #[inline]
+    /// Specify max size of the log file before it's rotated
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+
+        self
+    }
+
+    #[inline]
+    /// Specify amount of rotated log files kept on the disk
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+
+        self
+    }
+
+    #[inline]
+    /// Log progress events of the same kind not more often than once per `progress_interval`
+    pub fn with_progress_interval(mut self, progress_interval: Duration) -> Self {
+        self.progress_interval = Some(progress_interval);
+
+        self
+    }
+
+    #[inline]
+    /// Get path to the log file
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Log the event
+    /// 
+    /// If `progress_interval` is set, progress events are skipped
+    /// if the same ones were logged less than `progress_interval` ago
+    pub fn log(&self, event: &impl LoggedEvent) {
+        let kind = event.kind();
+
+        let event = match serde_json::to_value(event) {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!("Failed to serialize event: {err}");
+
+                return;
+            }
+        };
+
+        if let (EventKind::Progress, Some(interval)) = (kind, self.progress_interval) {
+            let name = variant_name(&event);
+
+            let mut state = self.state.lock().unwrap();
+
+            let now = Instant::now();
+
+            if let Some(last) = state.last_progress.get(&name) {
+                if now.duration_since(*last) < interval {
+                    return;
+                }
+            }
+
+            state.last_progress.insert(name, now);
+        }
+
+        if let Err(err) = self.write(kind, event) {
+            tracing::warn!("Failed to write event log: {err}");
+        }
+    }
+
+    /// Append the event to the log file
+    pub fn write(&self, kind: EventKind, event: impl Serialize) -> std::io::Result<()> {
+        let event = Event {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            kind,
+            event: serde_json::to_value(event)?
+        };
+
+        let mut line = serde_json::to_string(&event)?;
+
+        line.push('\n');
+
+        let mut state = self.state.lock().unwrap();
+
+        if state.file.is_some() && state.size + line.len() as u64 > self.max_size {
+            state.file = None;
+
+            self.rotate()?;
+        }
+
+        if state.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+
+            state.size = file.metadata()?.len();
+            state.file = Some(file);
+
+            // Rotate log file left by the previous run if it's too big
+            if state.size + line.len() as u64 > self.max_size && state.size > 0 {
+                state.file = None;
+
+                self.rotate()?;
+
+                state.size = 0;
+                state.file = Some(File::create(&self.path)?);
+            }
+        }
+
+        if let Some(file) = &mut state.file {
+            file.write_all(line.as_bytes())?;
+        }
+
+        state.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Wrap updater function so every update is logged before being passed to it
+    pub fn updater<T: LoggedEvent>(&self, updater: impl Fn(T) + Clone + Send + 'static) -> impl Fn(T) + Clone + Send + 'static {
+        let log = self.clone();
+
+        move |update: T| {
+            log.log(&update);
+
+            (updater)(update);
+        }
+    }
+
+    /// Shift rotated log files: `log` -> `log.1` -> `log.2` ...
+    fn rotate(&self) -> std::io::Result<()> {
+        let rotated = |i: usize| PathBuf::from(format!("{}.{i}", self.path.to_string_lossy()));
+
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+
+        let oldest = rotated(self.max_files);
+
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+
+        for i in (1..self.max_files).rev() {
+            let path = rotated(i);
+
+            if path.exists() {
+                std::fs::rename(path, rotated(i + 1))?;
+            }
+        }
+
+        std::fs::rename(&self.path, rotated(1))
+    }
+}
+
+/// Get names of the nested enum variants of the serialized value
+/// 
+/// Used to throttle progress events of the same kind
+/// 
+/// - `{"InstallerUpdate": {"DownloadingProgress": [1, 2]}}` -> `InstallerUpdate::DownloadingProgress`
+/// - `{"UnpackingError": "Failed to unpack"}` -> `UnpackingError`
+/// - `"UnpackingFinished"` -> `UnpackingFinished`
+fn variant_name(value: &serde_json::Value) -> String {
+    fn is_variant(name: &str) -> bool {
+        name.starts_with(char::is_uppercase) && name.chars().all(char::is_alphanumeric)
+    }
+
+    match value {
+        serde_json::Value::String(name) if is_variant(name) => name.clone(),
+
+        serde_json::Value::Object(object) if object.len() == 1 => {
+            let Some((name, value)) = object.iter().next() else {
+                return String::new();
+            };
+
+            if !is_variant(name) {
+                return String::new();
+            }
+
+            let inner = variant_name(value);
+
+            if inner.is_empty() {
+                name.clone()
+            } else {
+                format!("{name}::{inner}")
+            }
+        }
+
+        _ => String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::installer::installer::Update;
+    use crate::installer::downloader::DownloadingError;
+
+    fn prepare(name: &str) -> std::io::Result<PathBuf> {
+        let folder = std::env::temp_dir().join(format!(".agc-test-event-log-{name}"));
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        Ok(folder)
+    }
+
+    fn read_events(path: &Path) -> std::io::Result<Vec<Event>> {
+        std::fs::read_to_string(path)?
+            .lines()
+            .map(|line| serde_json::from_str(line).map_err(std::io::Error::from))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_event_kinds() -> std::io::Result<()> {
+        let folder = prepare("kinds")?;
+        let log = EventLog::new(folder.join("install.log"));
+
+        log.log(&Update::DownloadingStarted(PathBuf::from("/tmp")));
+        log.log(&Update::DownloadingProgress(1, 2));
+        log.log(&Update::DownloadingProgress(2, 2));
+        log.log(&Update::DownloadingRetry(1, DownloadingError::Minreq(String::from("timeout"))));
+        log.log(&Update::UnpackingError(String::from("error")));
+        log.log(&Update::UpdatingPermissions(1, 2));
+        log.log(&Update::HookVetoed(String::from("vetoed")));
+        log.log(&DownloadingError::PathNotMounted(PathBuf::from("/tmp")));
+
+        let kinds = read_events(log.path())?
+            .into_iter()
+            .map(|event| event.kind)
+            .collect::<Vec<_>>();
+
+        assert_eq!(kinds, vec![
+            EventKind::Phase,
+            EventKind::Progress,
+            EventKind::Progress,
+            EventKind::Retry,
+            EventKind::Error,
+            EventKind::Progress,
+            EventKind::Error,
+            EventKind::Error
+        ]);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_progress_interval() -> std::io::Result<()> {
+        let folder = prepare("interval")?;
+
+        let log = EventLog::new(folder.join("install.log"))
+            .with_progress_interval(Duration::from_secs(60));
+
+        for i in 0..10 {
+            log.log(&Update::DownloadingProgress(i, 10));
+            log.log(&Update::UnpackingProgress(i, 10));
+        }
+
+        log.log(&Update::DownloadingFinished);
+
+        let events = read_events(log.path())?;
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event, serde_json::json!({ "DownloadingProgress": [0, 10] }));
+        assert_eq!(events[1].event, serde_json::json!({ "UnpackingProgress": [0, 10] }));
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_rotation() -> std::io::Result<()> {
+        let folder = prepare("rotation")?;
+
+        let log = EventLog::new(folder.join("install.log"))
+            .with_max_size(100)
+            .with_max_files(2);
+
+        for i in 0..10 {
+            log.write(EventKind::Progress, i)?;
+        }
+
+        let rotated = |i: usize| folder.join(format!("install.log.{i}"));
+
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+
+        for path in [log.path().to_path_buf(), rotated(1), rotated(2)] {
+            assert!(path.metadata()?.len() <= 100);
+        }
+
+        // Last written event is stored in the current log file
+        let events = read_events(log.path())?;
+
+        assert_eq!(events.last().map(|event| event.event.clone()), Some(serde_json::json!(9)));
+
+        // Previous run's log file is rotated when it's too big
+        let log = EventLog::new(folder.join("install.log"))
+            .with_max_size(100)
+            .with_max_files(2);
+
+        log.write(EventKind::Phase, "start")?;
+
+        assert_eq!(read_events(log.path())?.len(), 1);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
@@ -1,67 +1,315 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::collections::HashMap;
+use std::sync::Mutex;
 
-use sysinfo::Disks;
+use serde::{Serialize, Deserialize};
 
-// TODO: support for relative paths
+use super::downloader::DownloadingError;
+
+lazy_static::lazy_static! {
+    /// Space reserved by running operations of the current process
+    static ref LEDGER: Ledger = Ledger::default();
+}
+
+/// Information about the filesystem some path is stored on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DiskInfo {
+    /// Id of the device the filesystem belongs to
+    pub device: u64,
+
+    /// Id of the filesystem reported by `statvfs`
+    /// 
+    /// Unlike device id it's the same for all the paths sharing
+    /// the same free space, so reservations are counted by it
+    pub filesystem: u64,
+
+    /// Size of the filesystem's allocation block
+    pub block_size: u64,
+
+    /// Total size of the filesystem in bytes
+    pub total: u64,
+
+    /// Amount of bytes available for the current user
+    pub available: u64,
+
+    /// Amount of inodes available for the current user
+    /// 
+    /// Some filesystems (e.g. btrfs) don't have a fixed amount of inodes and report 0
+    pub available_inodes: u64,
+
+    /// Filesystem is mounted as read-only
+    pub read_only: bool
+}
+
+/// Get nearest existing path, going up through its parents
+/// 
+/// Relative paths are resolved from the current directory
+pub fn existing_ancestor(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+
+    let path = if path.is_relative() {
+        std::env::current_dir().ok()?.join(path)
+    } else {
+        path.to_path_buf()
+    };
+
+    path.ancestors()
+        .find(|path| path.exists())
+        .map(Path::to_path_buf)
+}
+
+/// Get information about the filesystem the path is stored on
+/// 
+/// If the path doesn't exist yet - its nearest existing parent is used.
+/// Symlinks, bind mounts and btrfs subvolumes are resolved by the system
+/// 
+/// Return `None` if the filesystem couldn't be read
+pub fn info(path: impl AsRef<Path>) -> Option<DiskInfo> {
+    let path = existing_ancestor(path)?;
+
+    let device = path.metadata().ok()?.dev();
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        stat.assume_init()
+    };
+
+    #[allow(clippy::unnecessary_cast)]
+    let block_size = if stat.f_frsize > 0 {
+        stat.f_frsize as u64
+    } else {
+        stat.f_bsize as u64
+    };
+
+    #[allow(clippy::unnecessary_cast)]
+    Some(DiskInfo {
+        device,
+        filesystem: stat.f_fsid as u64,
+        block_size,
+        total: stat.f_blocks as u64 * block_size,
+        available: stat.f_bavail as u64 * block_size,
+        available_inodes: stat.f_favail as u64,
+        read_only: stat.f_flag & libc::ST_RDONLY != 0
+    })
+}
 
 /// Get available free disk space by specified path
 /// 
-/// Can return `None` if path is not prefixed by any available disk
+/// Can return `None` if the filesystem couldn't be read
+#[inline]
 pub fn available(path: impl AsRef<Path>) -> Option<u64> {
-    let mut disks = Disks::new();
+    info(path).map(|info| info.available)
+}
 
-    disks.refresh_list();
-    disks.refresh();
+/// Get id of the device the path is stored on
+/// 
+/// If the path doesn't exist yet - its nearest existing parent is used
+pub fn device_id(path: impl AsRef<Path>) -> Option<u64> {
+    existing_ancestor(path)?
+        .metadata()
+        .ok()
+        .map(|metadata| metadata.dev())
+}
+
+/// Check if two paths exist on the same disk
+pub fn is_same_disk(path1: impl AsRef<Path>, path2: impl AsRef<Path>) -> bool {
+    match (device_id(path1), device_id(path2)) {
+        (Some(device1), Some(device2)) => device1 == device2,
+
+        _ => false
+    }
+}
+
+/// Get id of the filesystem the path is stored on
+/// 
+/// If the path doesn't exist yet - its nearest existing parent is used
+#[inline]
+pub fn filesystem_id(path: impl AsRef<Path>) -> Option<u64> {
+    info(path).map(|info| info.filesystem)
+}
+
+/// Amount of bytes reserved on every filesystem
+#[derive(Debug, Default)]
+struct Ledger {
+    reserved: Mutex<HashMap<u64, u64>>
+}
+
+impl Ledger {
+    fn reserved(&self, filesystem: u64) -> u64 {
+        self.reserved.lock().unwrap()
+            .get(&filesystem)
+            .copied()
+            .unwrap_or(0)
+    }
 
-    disks.sort_by(|a, b| {
-        let a = a.mount_point().as_os_str().len();
-        let b = b.mount_point().as_os_str().len();
+    fn reserve(&'static self, path: &Path, amount: u64) -> Result<Reservation, DownloadingError> {
+        let Some(info) = info(path) else {
+            return Err(DownloadingError::PathNotMounted(path.to_path_buf()));
+        };
 
-        a.cmp(&b).reverse()
-    });
+        let mut reserved = self.reserved.lock().unwrap();
 
-    let path = path.as_ref()
-        .read_link()
-        .unwrap_or_else(|_| path.as_ref().to_path_buf());
+        let filesystem_reserved = reserved.entry(info.filesystem).or_default();
+        let available = info.available.saturating_sub(*filesystem_reserved);
 
-    for disk in disks.iter() {
-        if path.starts_with(disk.mount_point()) {
-            return Some(disk.available_space());
+        if available < amount {
+            return Err(DownloadingError::NoSpaceAvailable(path.to_path_buf(), amount, available));
         }
+
+        *filesystem_reserved += amount;
+
+        tracing::debug!("Reserved {amount} bytes on the disk of {:?}", path);
+
+        Ok(Reservation {
+            ledger: self,
+            filesystem: info.filesystem,
+            amount: Mutex::new(amount)
+        })
     }
+}
 
-    None
+/// Disk space reserved by some operation
+/// 
+/// Reserved space is subtracted from the available one by `unreserved`,
+/// so concurrent operations don't count on the same free space.
+/// Reservation is released when dropped
+#[derive(Debug)]
+pub struct Reservation {
+    ledger: &'static Ledger,
+    filesystem: u64,
+    amount: Mutex<u64>
 }
 
-/// Check if two paths exist on the same disk
-pub fn is_same_disk(path1: impl AsRef<Path>, path2: impl AsRef<Path>) -> bool {
-    let mut disks = Disks::new();
+impl Reservation {
+    #[inline]
+    /// Get id of the filesystem the space is reserved on
+    pub fn filesystem(&self) -> u64 {
+        self.filesystem
+    }
 
-    disks.refresh_list();
-    disks.refresh();
+    #[inline]
+    /// Get amount of reserved bytes
+    pub fn amount(&self) -> u64 {
+        *self.amount.lock().unwrap()
+    }
 
-    disks.sort_by(|a, b| {
-        let a = a.mount_point().as_os_str().len();
-        let b = b.mount_point().as_os_str().len();
+    /// Release some reserved space, e.g. when the data was already written to the disk
+    pub fn release(&self, amount: u64) {
+        let mut reserved = self.ledger.reserved.lock().unwrap();
+        let mut current = self.amount.lock().unwrap();
 
-        a.cmp(&b).reverse()
-    });
+        let amount = amount.min(*current);
 
-    let path1 = path1.as_ref()
-        .read_link()
-        .unwrap_or_else(|_| path1.as_ref().to_path_buf());
+        *current -= amount;
 
-    let path2 = path2.as_ref()
-        .read_link()
-        .unwrap_or_else(|_| path2.as_ref().to_path_buf());
+        if let Some(filesystem) = reserved.get_mut(&self.filesystem) {
+            *filesystem = filesystem.saturating_sub(amount);
+
+            if *filesystem == 0 {
+                reserved.remove(&self.filesystem);
+            }
+        }
+    }
 
-    for disk in disks.iter() {
-        let disk_path = disk.mount_point();
+    /// Release reserved space so only `amount` bytes are left
+    pub fn shrink_to(&self, amount: u64) {
+        let current = self.amount();
 
-        if path1.starts_with(disk_path) && path2.starts_with(disk_path) {
-            return true;
+        if current > amount {
+            self.release(current - amount);
         }
     }
+}
+
+impl Drop for Reservation {
+    #[inline]
+    fn drop(&mut self) {
+        self.release(u64::MAX);
+    }
+}
+
+/// Get amount of bytes reserved on the disk of the given path
+pub fn reserved(path: impl AsRef<Path>) -> u64 {
+    filesystem_id(path)
+        .map(|filesystem| LEDGER.reserved(filesystem))
+        .unwrap_or(0)
+}
+
+/// Get available free disk space by specified path, excluding space reserved by running operations
+/// 
+/// Can return `None` if the filesystem couldn't be read
+pub fn unreserved(path: impl AsRef<Path>) -> Option<u64> {
+    let info = info(path)?;
+
+    Some(info.available.saturating_sub(LEDGER.reserved(info.filesystem)))
+}
 
-    false
+/// Reserve disk space for some operation
+/// 
+/// Fails if there's not enough unreserved space on the disk of the given path
+#[inline]
+pub fn reserve(path: impl AsRef<Path>, amount: u64) -> Result<Reservation, DownloadingError> {
+    LEDGER.reserve(path.as_ref(), amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_missing_paths() {
+        let temp = std::env::temp_dir();
+
+        let info = info(&temp).expect("Failed to read temp folder filesystem");
+
+        let missing = super::info(temp.join("missing/folder")).expect("Failed to read missing folder filesystem");
+
+        // Available space can be changed by other processes between the calls
+        assert_eq!(missing.device, info.device);
+        assert_eq!(missing.total, info.total);
+        assert!(is_same_disk(&temp, temp.join("missing/folder")));
+
+        assert!(available("relative/missing/folder").is_some());
+    }
+
+    #[test]
+    pub fn test_reservations() {
+        let temp = std::env::temp_dir();
+
+        // Own ledger isn't affected by reservations of other tests
+        let ledger: &'static Ledger = Box::leak(Box::default());
+
+        let info = info(&temp).unwrap();
+        let half = info.available / 2;
+
+        let reservation = ledger.reserve(&temp, half).unwrap();
+
+        assert_eq!(reservation.filesystem(), info.filesystem);
+        assert_eq!(ledger.reserved(info.filesystem), half);
+
+        assert!(ledger.reserve(&temp, info.available).is_err());
+
+        // Missing folders share reservations of their existing parent
+        assert!(ledger.reserve(&temp.join("missing/folder"), info.available).is_err());
+
+        reservation.shrink_to(1);
+
+        assert_eq!(reservation.amount(), 1);
+        assert_eq!(ledger.reserved(info.filesystem), 1);
+
+        drop(reservation);
+
+        assert_eq!(ledger.reserved(info.filesystem), 0);
+        assert!(ledger.reserved.lock().unwrap().is_empty());
+    }
 }
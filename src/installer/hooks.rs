@@ -0,0 +1,260 @@
+use std::path::Path;
+
+/// Custom steps performed around the installation
+/// 
+/// All the methods do nothing by default, so only needed ones
+/// should be implemented. Returning an error from any `pre_` or `post_`
+/// hook vetoes the installation: it's stopped, and if the changes were
+/// staged - the installation folder is left untouched
+/// 
+/// ```no_run
+/// use std::path::Path;
+/// 
+/// use anime_game_core::installer::hooks::InstallHooks;
+/// use anime_game_core::installer::installer::Installer;
+/// 
+/// struct StopGame;
+/// 
+/// impl InstallHooks for StopGame {
+///     fn pre_download(&self, _folder: &Path) -> anyhow::Result<()> {
+///         anyhow::ensure!(!is_game_running(), "Game is running");
+/// 
+///         Ok(())
+///     }
+/// 
+///     fn on_failure(&self, error: &str) {
+///         eprintln!("Failed to update the game: {error}");
+///     }
+/// }
+/// 
+/// # fn is_game_running() -> bool { false }
+/// Installer::new("https://example.com/game.zip").unwrap()
+///     .install_with_hooks("/path/to/game", &StopGame, |update| println!("{update:?}"))
+///     .expect("Failed to install the game");
+/// ```
+#[allow(unused_variables)]
+pub trait InstallHooks {
+    /// Called before anything is downloaded
+    /// 
+    /// `folder` is the installation folder
+    fn pre_download(&self, folder: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the archive is downloaded
+    fn post_download(&self, archive: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called before the archive is extracted to the `folder`
+    /// 
+    /// `folder` is the staging folder in transactional installations
+    fn pre_extract(&self, folder: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the archive is extracted to the `folder`
+    fn post_extract(&self, folder: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when all the files are extracted, patched and applied to the installation `folder`
+    fn post_patch(&self, folder: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the installation is failed or vetoed by another hook
+    fn on_failure(&self, error: &str) {}
+}
+
+/// No hooks
+impl InstallHooks for () {}
+
+/// Run the hook, converting its error to the veto message
+pub(crate) fn run(name: &str, hook: impl FnOnce() -> anyhow::Result<()>) -> Result<(), String> {
+    hook().map_err(|err| {
+        tracing::warn!("Installation was vetoed by {name} hook: {err}");
+
+        format!("Installation was vetoed by {name} hook: {err}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::BTreeSet;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::installer::installer::{Installer, InstallerError};
+
+    /// Hooks which record their calls and veto the given one
+    struct Recorder {
+        veto: Option<&'static str>,
+        calls: RefCell<Vec<String>>
+    }
+
+    impl Recorder {
+        fn new(veto: Option<&'static str>) -> Self {
+            Self {
+                veto,
+                calls: RefCell::new(Vec::new())
+            }
+        }
+
+        fn call(&self, name: &'static str) -> anyhow::Result<()> {
+            self.calls.borrow_mut().push(name.to_string());
+
+            anyhow::ensure!(self.veto != Some(name), "vetoed");
+
+            Ok(())
+        }
+    }
+
+    impl InstallHooks for Recorder {
+        fn pre_download(&self, _folder: &Path) -> anyhow::Result<()> {
+            self.call("pre_download")
+        }
+
+        fn post_download(&self, _archive: &Path) -> anyhow::Result<()> {
+            self.call("post_download")
+        }
+
+        fn pre_extract(&self, _folder: &Path) -> anyhow::Result<()> {
+            self.call("pre_extract")
+        }
+
+        fn post_extract(&self, _folder: &Path) -> anyhow::Result<()> {
+            self.call("post_extract")
+        }
+
+        fn post_patch(&self, folder: &Path) -> anyhow::Result<()> {
+            // Files should already be applied to the installation folder
+            let content = std::fs::read_to_string(folder.join("file.txt"))?;
+
+            self.calls.borrow_mut().push(format!("post_patch {content}"));
+
+            Ok(())
+        }
+
+        fn on_failure(&self, error: &str) {
+            self.calls.borrow_mut().push(format!("on_failure {error}"));
+        }
+    }
+
+    /// Serve the archive over http and return its url
+    fn serve(archive: Vec<u8>) -> std::io::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+
+                let Ok(read) = stream.read(&mut request) else {
+                    continue;
+                };
+
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", archive.len());
+
+                #[allow(unused_must_use)] {
+                    stream.write_all(head.as_bytes());
+
+                    if request[..read].starts_with(b"GET") {
+                        stream.write_all(&archive);
+                    }
+                }
+            }
+        });
+
+        Ok(format!("http://{address}/archive.zip"))
+    }
+
+    fn read_folder(path: &Path) -> std::io::Result<BTreeSet<(String, String)>> {
+        let mut files = BTreeSet::new();
+
+        for entry in path.read_dir()? {
+            let entry = entry?;
+
+            files.insert((
+                entry.file_name().to_string_lossy().to_string(),
+                std::fs::read_to_string(entry.path()).unwrap_or_default()
+            ));
+        }
+
+        Ok(files)
+    }
+
+    #[test]
+    pub fn test_hooks() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-hooks");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(path.join("game"))?;
+        std::fs::create_dir_all(path.join("temp"))?;
+
+        std::fs::write(path.join("game/file.txt"), "old")?;
+
+        let mut archive = Vec::new();
+
+        {
+            let mut zip = zip::ZipWriter::new(std::io::Cursor::new(&mut archive));
+
+            zip.start_file("file.txt", zip::write::FileOptions::default())?;
+            zip.write_all(b"new")?;
+
+            zip.start_file("added.txt", zip::write::FileOptions::default())?;
+            zip.write_all(b"added")?;
+
+            zip.finish()?;
+        }
+
+        let url = serve(archive)?;
+
+        let game = read_folder(&path.join("game"))?;
+
+        for (veto, transactional) in [("pre_download", false), ("post_download", true), ("pre_extract", false), ("pre_extract", true), ("post_extract", true)] {
+            let hooks = Recorder::new(Some(veto));
+
+            let result = Installer::new(&url)?
+                .with_temp_folder(path.join("temp"))
+                .with_transactional(transactional)
+                .install_with_hooks(path.join("game"), &hooks, |_| {});
+
+            assert!(matches!(result, Err(InstallerError::HookVetoed(_))), "{veto} hook wasn't vetoed");
+
+            // Vetoed installation leaves the folder untouched
+            assert_eq!(read_folder(&path.join("game"))?, game, "{veto} hook changed the folder");
+
+            let calls = hooks.calls.into_inner();
+
+            assert_eq!(calls.last().map(String::as_str), Some(format!("on_failure Installation was vetoed by {veto} hook: vetoed").as_str()));
+            assert_eq!(calls[calls.len() - 2], veto);
+        }
+
+        // Transactions don't leave any files next to the folder
+        assert_eq!(read_folder(&path)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>(), ["game", "temp"]);
+
+        let hooks = Recorder::new(None);
+
+        Installer::new(&url)?
+            .with_temp_folder(path.join("temp"))
+            .with_transactional(true)
+            .install_with_hooks(path.join("game"), &hooks, |_| {})?;
+
+        assert_eq!(hooks.calls.into_inner(), ["pre_download", "post_download", "pre_extract", "post_extract", "post_patch new"]);
+
+        assert_eq!(read_folder(&path.join("game"))?, BTreeSet::from([
+            (String::from("file.txt"), String::from("new")),
+            (String::from("added.txt"), String::from("added"))
+        ]));
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
-use std::path::PathBuf;
-use std::os::unix::prelude::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde::{Serialize, Deserialize};
 
 use super::downloader::{Downloader, DownloadingError};
-use super::archives::Archive;
+use super::archives::{Archive, ExtractOptions};
+use super::space::{self, SpaceRequirements};
 use super::free_space;
+use super::transaction::Transaction;
+use super::event_log::{EventLog, EventKind, LoggedEvent};
+use super::permissions::{self, PermissionIssue};
+use super::hooks::{self, InstallHooks};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Update {
@@ -20,6 +26,20 @@ pub enum Update {
     DownloadingFinished,
     DownloadingError(DownloadingError),
 
+    /// Downloading failed and will be continued
+    /// 
+    /// `(attempt, error)`
+    DownloadingRetry(u64, DownloadingError),
+
+    /// `(archive path)`
+    TestingArchiveStarted(PathBuf),
+
+    /// `(current bytes, total bytes)`
+    TestingArchiveProgress(u64, u64),
+
+    TestingArchiveFinished,
+    TestingArchiveError(String),
+
     /// `(unpacking path)`
     UpdatingPermissionsStarted(PathBuf),
 
@@ -28,6 +48,9 @@ pub enum Update {
 
     UpdatingPermissionsFinished,
 
+    /// Files which can't be modified without elevated privileges
+    UpdatingPermissionsError(Vec<PermissionIssue>),
+
     /// `(unpacking path)`
     UnpackingStarted(PathBuf),
 
@@ -35,7 +58,69 @@ pub enum Update {
     UnpackingProgress(u64, u64),
 
     UnpackingFinished,
-    UnpackingError(String)
+    UnpackingError(String),
+
+    /// `(installation path)`
+    CommittingStarted(PathBuf),
+
+    CommittingFinished,
+
+    /// Changes were rolled back and installation folder is left untouched
+    CommittingError(String),
+
+    /// Installation was stopped by one of its hooks
+    HookVetoed(String)
+}
+
+impl LoggedEvent for Update {
+    fn kind(&self) -> EventKind {
+        match self {
+            Self::DownloadingProgress(_, _) |
+            Self::TestingArchiveProgress(_, _) |
+            Self::UpdatingPermissions(_, _) |
+            Self::UnpackingProgress(_, _) => EventKind::Progress,
+
+            Self::DownloadingRetry(_, _) => EventKind::Retry,
+
+            Self::DownloadingError(_) |
+            Self::TestingArchiveError(_) |
+            Self::UpdatingPermissionsError(_) |
+            Self::UnpackingError(_) |
+            Self::CommittingError(_) |
+            Self::HookVetoed(_) => EventKind::Error,
+
+            Self::CheckingFreeSpace(_) |
+            Self::DownloadingStarted(_) |
+            Self::DownloadingFinished |
+            Self::TestingArchiveStarted(_) |
+            Self::TestingArchiveFinished |
+            Self::UpdatingPermissionsStarted(_) |
+            Self::UpdatingPermissionsFinished |
+            Self::UnpackingStarted(_) |
+            Self::UnpackingFinished |
+            Self::CommittingStarted(_) |
+            Self::CommittingFinished => EventKind::Phase
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+pub enum InstallerError {
+    /// Failed to download the archive or to reserve free space for it
+    #[error("{0}")]
+    Downloading(#[from] DownloadingError),
+
+    /// Installation was stopped by one of its hooks
+    #[error("{0}")]
+    HookVetoed(String),
+
+    /// Downloaded archive is corrupted or its files can't be unpacked
+    #[error("Failed to unpack downloaded archive: {0}")]
+    Unpacking(String),
+
+    /// Staged changes couldn't be applied to the installation folder
+    #[error("Failed to apply changes: {0}")]
+    Transaction(String)
 }
 
 impl From<DownloadingError> for Update {
@@ -56,7 +141,23 @@ pub struct Installer {
     pub check_free_space: bool,
 
     /// How `Downloader` should save the file before unpacking it
-    pub filename: Option<String>
+    pub filename: Option<String>,
+
+    /// Verify downloaded archive integrity before touching the unpacking folder
+    pub test_archive: bool,
+
+    /// Settings used to extract downloaded archive
+    pub extract_options: ExtractOptions,
+
+    /// Unpack archive into a staging folder and move its files
+    /// to the installation folder only if everything succeeded
+    pub transactional: bool,
+
+    /// Log file all the installation updates are written to
+    pub event_log: Option<EventLog>,
+
+    /// Amount of times failed download is continued before the installation fails
+    pub download_retries: u64
 }
 
 impl Installer {
@@ -68,7 +169,12 @@ impl Installer {
 
             temp_folder: std::env::temp_dir(),
             check_free_space: true,
-            filename: None
+            filename: None,
+            test_archive: false,
+            extract_options: ExtractOptions::default(),
+            transactional: false,
+            event_log: None,
+            download_retries: 0
         })
     }
 
@@ -110,68 +216,195 @@ impl Installer {
         self
     }
 
+    #[inline]
+    /// Specify whether installer should test downloaded archive before unpacking it
+    pub fn with_archive_test(mut self, test_archive: bool) -> Self {
+        self.test_archive = test_archive;
+
+        self
+    }
+
+    #[inline]
+    /// Specify settings used to extract downloaded archive
+    pub fn with_extract_options(mut self, extract_options: ExtractOptions) -> Self {
+        self.extract_options = extract_options;
+
+        self
+    }
+
+    #[inline]
+    /// Specify whether installation folder should be modified only if unpacking succeeded
+    pub fn with_transactional(mut self, transactional: bool) -> Self {
+        self.transactional = transactional;
+
+        self
+    }
+
+    #[inline]
+    /// Write all the installation updates to the given log
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+
+        self
+    }
+
+    #[inline]
+    /// Specify amount of times failed download is continued before the installation fails
+    /// 
+    /// Only network errors are retried
+    pub fn with_download_retries(mut self, download_retries: u64) -> Self {
+        self.download_retries = download_retries;
+
+        self
+    }
+
     /// Download archive from specified uri and unpack it
-    pub fn install(&mut self, unpack_to: impl Into<PathBuf>, updater: impl Fn(Update) + Clone + Send + 'static) {
-        tracing::trace!("Checking free space availability");
+    /// 
+    /// In transactional mode the archive is unpacked into the staging folder
+    /// first, and the installation folder is left untouched if anything fails
+    #[inline]
+    pub fn install(&mut self, unpack_to: impl Into<PathBuf>, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        self.install_with_hooks(unpack_to, &(), updater)
+    }
 
-        let temp_path = self.get_temp_path();
-        let unpack_to = unpack_to.into();
+    /// Download archive from specified uri and unpack it, running given hooks around the installation
+    /// 
+    /// `post_patch` hook is called when all the files are applied to the installation folder,
+    /// so it can't roll back the installation even in transactional mode
+    pub fn install_with_hooks(&mut self, unpack_to: impl Into<PathBuf>, hooks: &impl InstallHooks, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        let result = match self.event_log.clone() {
+            Some(event_log) => self.install_to(unpack_to.into(), hooks, true, event_log.updater(updater)),
+            None => self.install_to(unpack_to.into(), hooks, true, updater)
+        };
+
+        if let Err(err) = &result {
+            hooks.on_failure(&err.to_string());
+        }
 
-        // Perform free space verifications if needed
-        if self.check_free_space {
-            // Check available free space for archive itself
-            (updater)(Update::CheckingFreeSpace(temp_path.clone()));
+        result
+    }
+
+    /// Same as `install_with_hooks`, but `post_patch` and `on_failure` hooks should be called by the caller
+    /// 
+    /// Used by games which need to finish the installation themselves, e.g. write the `.version` file
+    pub(crate) fn install_unpatched(&mut self, unpack_to: impl Into<PathBuf>, hooks: &impl InstallHooks, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        match self.event_log.clone() {
+            Some(event_log) => self.install_to(unpack_to.into(), hooks, false, event_log.updater(updater)),
+            None => self.install_to(unpack_to.into(), hooks, false, updater)
+        }
+    }
+
+    fn install_to(&mut self, unpack_to: PathBuf, hooks: &impl InstallHooks, post_patch: bool, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        if !self.transactional {
+            self.unpack(unpack_to.clone(), &unpack_to, hooks, updater.clone())?;
+        }
 
-            let Some(space) = free_space::available(&temp_path) else {
-                tracing::error!("Path is not mounted: {:?}", temp_path);
+        else {
+            let transaction = match Transaction::new(&unpack_to) {
+                Ok(transaction) => transaction,
+                Err(err) => {
+                    tracing::error!("Failed to start transaction: {err}");
 
-                (updater)(DownloadingError::PathNotMounted(temp_path).into());
+                    (updater)(Update::UnpackingError(err.to_string()));
 
-                return;
+                    return Err(InstallerError::Transaction(err.to_string()));
+                }
             };
 
-            if let Some(required) = self.downloader.length() {
-                // We can possibly store downloaded archive + unpacked data on the same disk
-                let required = if free_space::is_same_disk(&temp_path, &unpack_to) {
-                    (required as f64 * 2.5).ceil() as u64
-                } else {
-                    required
-                };
+            self.unpack(transaction.staging().to_path_buf(), &unpack_to, hooks, updater.clone())?;
 
-                if space < required {
-                    tracing::error!("No free space available in the temp folder. Required: {required}. Available: {space}");
+            (updater)(Update::CommittingStarted(unpack_to.clone()));
 
-                    (updater)(DownloadingError::NoSpaceAvailable(temp_path, required, space).into());
+            if let Err(err) = transaction.commit() {
+                (updater)(Update::CommittingError(err.to_string()));
 
-                    return;
-                }
+                return Err(InstallerError::Transaction(err.to_string()));
             }
 
-            // Check available free space for unpacked archvie data (archive size * 1.5)
-            (updater)(Update::CheckingFreeSpace(unpack_to.clone()));
+            (updater)(Update::CommittingFinished);
+        }
 
-            let Some(space) = free_space::available(&unpack_to) else {
-                tracing::error!("Path is not mounted: {:?}", temp_path);
+        if post_patch {
+            run_hook("post_patch", &updater, || hooks.post_patch(&unpack_to))?;
+        }
 
-                (updater)(DownloadingError::PathNotMounted(unpack_to).into());
+        Ok(())
+    }
 
-                return;
-            };
+    /// Download archive from specified uri and unpack it into the transaction's staging folder
+    /// 
+    /// Return error if the archive wasn't unpacked. Transaction should be committed by the caller
+    #[inline]
+    pub fn install_staged(&mut self, transaction: &Transaction, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        self.install_staged_with_hooks(transaction, &(), updater)
+    }
 
-            if let Some(required) = self.downloader.length() {
-                // We can possibly store downloaded archive + unpacked data on the same disk
-                let required = if free_space::is_same_disk(&unpack_to, &temp_path) {
-                    (required as f64 * 2.5).ceil() as u64
-                } else {
-                    (required as f64 * 1.5).ceil() as u64
-                };
+    /// Download archive from specified uri and unpack it into the transaction's staging folder,
+    /// running given hooks around the installation
+    /// 
+    /// Only downloading and extraction hooks are called. `post_patch` and `on_failure`
+    /// hooks should be called by the caller when the transaction is finished
+    pub fn install_staged_with_hooks(&mut self, transaction: &Transaction, hooks: &impl InstallHooks, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        let unpack_to = transaction.staging().to_path_buf();
+
+        match self.event_log.clone() {
+            Some(event_log) => self.unpack(unpack_to, transaction.root(), hooks, event_log.updater(updater)),
+            None => self.unpack(unpack_to, transaction.root(), hooks, updater)
+        }
+    }
 
-                if space < required {
-                    tracing::error!("No free space available in the installation folder. Required: {required}. Available: {space}");
+    /// Download archive and unpack it to the `unpack_to` folder
+    /// 
+    /// `root` is the installation folder which files will be replaced by the unpacked ones.
+    /// It's the same as `unpack_to` unless the archive is unpacked into the staging folder
+    fn unpack(&mut self, unpack_to: PathBuf, root: &Path, hooks: &impl InstallHooks, updater: impl Fn(Update) + Clone + Send + 'static) -> Result<(), InstallerError> {
+        run_hook("pre_download", &updater, || hooks.pre_download(root))?;
 
-                    (updater)(DownloadingError::NoSpaceAvailable(unpack_to, required, space).into());
+        tracing::trace!("Checking free space availability");
 
-                    return;
+        let temp_path = self.get_temp_path();
+
+        // Space reserved for the archive, released as it's being downloaded
+        let mut download_reservation = None;
+
+        // Check available free space for the archive and roughly for its unpacked data
+        // Unpacked data is checked again when we can read the archive's entries
+        if self.check_free_space {
+            (updater)(Update::CheckingFreeSpace(temp_path.clone()));
+
+            if let Some(length) = self.downloader.length() {
+                // Partially downloaded archive will be continued
+                let downloaded = temp_path.metadata()
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+
+                let required = length.saturating_sub(downloaded);
+
+                // Archive's entries are not known yet, so unpacked data is expected
+                // to take 1.5 times more space than the archive itself. More accurate
+                // requirements are estimated when the archive is downloaded
+                let unpacked = (length as f64 * 1.5).ceil() as u64;
+
+                (updater)(Update::CheckingFreeSpace(root.to_path_buf()));
+
+                if let Err(err) = SpaceRequirements::from_sizes(&temp_path, root, required, unpacked).check() {
+                    tracing::error!("No free space available for the installation: {err}");
+
+                    (updater)(err.clone().into());
+
+                    return Err(err.into());
+                }
+
+                match free_space::reserve(&temp_path, required) {
+                    Ok(reservation) => download_reservation = Some(Arc::new(reservation)),
+
+                    Err(err) => {
+                        tracing::error!("No free space available in the temp folder: {err}");
+
+                        (updater)(err.clone().into());
+
+                        return Err(err.into());
+                    }
                 }
             }
         }
@@ -183,18 +416,99 @@ impl Installer {
 
         (updater)(Update::DownloadingStarted(temp_path.clone()));
 
-        if let Err(err) = self.downloader.download(&temp_path, move |curr, total| (download_progress_updater)(Update::DownloadingProgress(curr, total))) {
-            tracing::error!("Failed to download archive: {err}");
+        let progress_reservation = download_reservation.clone();
+
+        let download_progress = move |curr: u64, total: u64| {
+            // Downloaded data already takes space on the disk
+            if let Some(reservation) = &progress_reservation {
+                reservation.shrink_to(total.saturating_sub(curr));
+            }
+
+            (download_progress_updater)(Update::DownloadingProgress(curr, total));
+        };
+
+        let mut attempt = 0;
+
+        // Already downloaded part is kept between attempts
+        let continue_downloading = self.downloader.continue_downloading;
+
+        loop {
+            match self.downloader.download(&temp_path, download_progress.clone()) {
+                Ok(()) => break,
+
+                Err(err @ DownloadingError::Minreq(_)) if attempt < self.download_retries => {
+                    attempt += 1;
+
+                    tracing::warn!("Failed to download archive, retrying ({attempt}/{}): {err}", self.download_retries);
+
+                    (updater)(Update::DownloadingRetry(attempt, err));
 
-            (updater)(Update::DownloadingError(err));
+                    self.downloader.continue_downloading = true;
+                }
+
+                Err(err) => {
+                    tracing::error!("Failed to download archive: {err}");
+
+                    self.downloader.continue_downloading = continue_downloading;
 
-            return;
+                    (updater)(Update::DownloadingError(err.clone()));
+
+                    return Err(err.into());
+                }
+            }
         }
 
+        self.downloader.continue_downloading = continue_downloading;
+
+        drop(download_reservation);
+
         (updater)(Update::DownloadingFinished);
 
+        run_hook("post_download", &updater, || hooks.post_download(&temp_path))?;
+
         match Archive::open(&temp_path) {
             Ok(mut archive) => {
+                // Test the archive before modifying any files in the unpacking folder
+                if self.test_archive {
+                    tracing::trace!("Testing archive");
+
+                    (updater)(Update::TestingArchiveStarted(temp_path.clone()));
+
+                    if let Err(err) = archive.test(|curr, total| (updater)(Update::TestingArchiveProgress(curr, total))) {
+                        tracing::error!("Downloaded archive is corrupted: {err}");
+
+                        (updater)(Update::TestingArchiveError(err.to_string()));
+
+                        return Err(InstallerError::Unpacking(err.to_string()));
+                    }
+
+                    (updater)(Update::TestingArchiveFinished);
+                }
+
+                // Space reserved for unpacked archive data, released as it's being extracted
+                let mut unpack_reservations = Vec::new();
+
+                // Check available free space for unpacked archive data
+                if self.check_free_space {
+                    (updater)(Update::CheckingFreeSpace(unpack_to.clone()));
+
+                    match space::estimate(&archive, root, unpack_to != root) {
+                        Ok(requirements) => match requirements.reserve() {
+                            Ok(reservations) => unpack_reservations = reservations,
+
+                            Err(err) => {
+                                tracing::error!("No free space available in the installation folder: {err}");
+
+                                (updater)(err.clone().into());
+
+                                return Err(err.into());
+                            }
+                        }
+
+                        Err(err) => tracing::warn!("Failed to estimate required free space: {err}")
+                    }
+                }
+
                 // Temporary workaround as we can't get archive extraction process
                 // directly - we'll spawn it in another thread and check this archive entries appearence in the filesystem
                 let mut total = 0;
@@ -205,33 +519,80 @@ impl Installer {
 
                 let entries_number = entries.len() as u64;
 
-                (updater)(Update::UpdatingPermissionsStarted(unpack_to.clone()));
-
-                for (i, entry) in entries.iter().enumerate() {
+                for entry in &entries {
                     total += entry.size.get_size();
+                }
+
+                run_hook("pre_extract", &updater, || hooks.pre_extract(&unpack_to))?;
+
+                // Make sure all the files which will be overwritten can be modified by the current user.
+                // Files of the previous installation could be made by root or marked as read-only
+                (updater)(Update::UpdatingPermissionsStarted(root.to_path_buf()));
+
+                let names = entries.iter().map(|entry| entry.name.as_str());
 
-                    let path = unpack_to.join(&entry.name);
+                let report = if unpack_to == root {
+                    permissions::scan_files(root, names).fix()
+                }
+
+                // Staged files are moved to the installation folder by the transaction,
+                // so only its folders should be writable. Nothing is changed here
+                // because the installation folder must stay untouched until the commit
+                else {
+                    permissions::scan_folders(root, names)
+                };
 
-                    // Failed to change permissions => likely patch-related file and was made by the sudo, so root
-                    #[allow(unused_must_use)]
-                    if let Err(_) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)) {
-                        // For weird reason we can delete files made by root, but can't modify their permissions
-                        // We're not checking its result because if it's error - then it's either couldn't be removed (which is not the case)
-                        // or the file doesn't exist, which we obviously can just ignore
-                        std::fs::remove_file(&path);
+                (updater)(Update::UpdatingPermissions(entries_number, entries_number));
+
+                if !report.is_ok() {
+                    for issue in &report.issues {
+                        tracing::error!("Can't modify installation file: {issue}");
                     }
 
-                    (updater)(Update::UpdatingPermissions(i as u64 + 1, entries_number));
+                    let message = format!("{} files can't be modified by the current user", report.issues.len());
+
+                    (updater)(Update::UpdatingPermissionsError(report.issues));
+
+                    return Err(InstallerError::Unpacking(message));
                 }
 
                 (updater)(Update::UpdatingPermissionsFinished);
 
                 tracing::trace!("Extracting archive");
 
+                // Unpacked data already takes space on the disk
+                let unpack_reservations = Arc::new(unpack_reservations.into_iter()
+                    .map(|reservation| {
+                        let amount = reservation.amount();
+
+                        (reservation, amount)
+                    })
+                    .collect::<Vec<_>>());
+
+                let shrink_reservations = move |unpacked: u64| {
+                    for (reservation, amount) in unpack_reservations.iter() {
+                        reservation.shrink_to(amount.saturating_sub(unpacked));
+                    }
+                };
+
                 let unpacking_path = unpack_to.clone();
+                // Hooks are called in the current thread after extraction is finished
+                let extracted_path = unpack_to.clone();
+                let hooks_updater = updater.clone();
                 let unpacking_updater = updater.clone();
+                let extract_options = self.extract_options;
 
-                let handle_2 = std::thread::spawn(move || {
+                // Zip archives report their extraction progress by themselves
+                let native_progress = matches!(archive, Archive::Zip(_, _));
+
+                // Some entries could be skipped by the extraction (e.g. unsafe symlinks)
+                // so we can't wait until all of them appear in the filesystem
+                let unpacking_finished = Arc::new(AtomicBool::new(false));
+                let unpacking_finished_2 = unpacking_finished.clone();
+
+                let polling_shrink_reservations = shrink_reservations.clone();
+
+                let handle_2 = (!native_progress).then(|| std::thread::spawn(move || {
                     let mut entries = entries.into_iter()
                         .map(|entry| (unpacking_path.join(&entry.name), entry.size.get_size(), true))
                         .collect::<Vec<_>>();
@@ -255,43 +616,77 @@ impl Installer {
                             }
                         }
 
+                        (polling_shrink_reservations)(unpacked);
                         (unpacking_updater)(Update::UnpackingProgress(unpacked, total));
 
-                        if empty {
+                        if empty || unpacking_finished_2.load(Ordering::Relaxed) {
                             break;
                         }
                     }
-                });
+                }));
 
                 // Run archive extraction in another thread to not to freeze the current one
                 let handle_1 = std::thread::spawn(move || {
                     (updater)(Update::UnpackingStarted(unpack_to.clone()));
 
-                    // We have to create new instance of Archive here
-                    // because otherwise it may not work after get_entries method call
-                    match Archive::open(&temp_path) {
-                        Ok(mut archive) => match archive.extract(unpack_to) {
-                            Ok(_) => {
-                                // TODO error handling
-                                #[allow(unused_must_use)] {
-                                    std::fs::remove_file(temp_path);
-                                }
+                    let progress_updater = Mutex::new(updater.clone());
 
-                                (updater)(Update::UnpackingFinished);
+                    let progress = move |curr, total| {
+                        if native_progress {
+                            (shrink_reservations)(curr);
+                            (progress_updater.lock().unwrap())(Update::UnpackingProgress(curr, total));
+                        }
+                    };
+
+                    let result = archive.extract_with(unpack_to, &extract_options, progress);
+
+                    unpacking_finished.store(true, Ordering::Relaxed);
+
+                    match result {
+                        Ok(_) => {
+                            // TODO error handling
+                            #[allow(unused_must_use)] {
+                                std::fs::remove_file(temp_path);
                             }
 
-                            Err(err) => (updater)(Update::UnpackingError(err.to_string()))
+                            (updater)(Update::UnpackingFinished);
+
+                            Ok(())
                         }
 
-                        Err(err) => (updater)(Update::UnpackingError(err.to_string()))
+                        Err(err) => {
+                            (updater)(Update::UnpackingError(err.to_string()));
+
+                            Err(err.to_string())
+                        }
                     }
                 });
 
-                handle_1.join().unwrap();
-                handle_2.join().unwrap();
+                let unpacked = handle_1.join().unwrap();
+
+                if let Some(handle_2) = handle_2 {
+                    handle_2.join().unwrap();
+                }
+
+                unpacked.map_err(InstallerError::Unpacking)?;
+
+                run_hook("post_extract", &hooks_updater, || hooks.post_extract(&extracted_path))
             }
 
-            Err(err) => (updater)(Update::UnpackingError(err.to_string()))
+            Err(err) => {
+                (updater)(Update::UnpackingError(err.to_string()));
+
+                Err(InstallerError::Unpacking(err.to_string()))
+            }
         }
     }
 }
+
+/// Run the hook, sending `HookVetoed` update if it vetoes the installation
+fn run_hook(name: &str, updater: &impl Fn(Update), hook: impl FnOnce() -> anyhow::Result<()>) -> Result<(), InstallerError> {
+    hooks::run(name, hook).map_err(|err| {
+        (updater)(Update::HookVetoed(err.clone()));
+
+        InstallerError::HookVetoed(err)
+    })
+}
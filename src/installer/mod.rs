@@ -2,10 +2,22 @@ pub mod downloader;
 pub mod archives;
 pub mod installer;
 pub mod free_space;
+pub mod space;
+pub mod transaction;
+pub mod event_log;
+pub mod permissions;
+pub mod hooks;
 
 pub mod prelude {
-    pub use super::archives::Archive;
+    pub use super::archives::{
+        Archive,
+        ArchiveWriter,
+        ArchiveFormat
+    };
     pub use super::free_space;
+    pub use super::transaction::Transaction;
+    pub use super::event_log::EventLog;
+    pub use super::hooks::InstallHooks;
 
     pub use super::downloader::{
         Downloader,
@@ -0,0 +1,505 @@
+use std::path::{Path, PathBuf};
+use std::fs::OpenOptions;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt, OpenOptionsExt};
+use std::os::fd::AsRawFd;
+
+use serde::{Serialize, Deserialize};
+
+/// Inode flags which forbid any file modifications
+const FS_IMMUTABLE_FL: libc::c_int = 0x00000010;
+const FS_APPEND_FL: libc::c_int = 0x00000020;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Problem {
+    /// File or folder belongs to the current user but has no write permission
+    ReadOnly,
+
+    /// File or folder belongs to another user
+    WrongOwner {
+        uid: u32,
+        gid: u32
+    },
+
+    /// File has immutable or append-only attribute (`chattr +i`)
+    Immutable,
+
+    /// Metadata of the file couldn't be read
+    Inaccessible(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionIssue {
+    pub path: PathBuf,
+    pub problem: Problem,
+
+    /// Whether the issue can be fixed without elevated privileges
+    pub fixable: bool
+}
+
+impl std::fmt::Display for PermissionIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.problem {
+            Problem::ReadOnly => write!(f, "{:?} is read-only", self.path),
+            Problem::WrongOwner { uid, gid } => write!(f, "{:?} belongs to another user ({uid}:{gid})", self.path),
+            Problem::Immutable => write!(f, "{:?} has immutable attribute", self.path),
+            Problem::Inaccessible(err) => write!(f, "{:?} is not accessible: {err}", self.path)
+        }
+    }
+}
+
+/// Result of the game folder permissions scan
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionReport {
+    /// Amount of checked files and folders
+    pub checked: u64,
+
+    /// Files and folders current user can't modify
+    pub issues: Vec<PermissionIssue>,
+
+    /// Files and folders which were fixed by `fix` method
+    pub fixed: Vec<PathBuf>
+}
+
+impl PermissionReport {
+    /// Check if current user can modify all the checked files
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Get issues which need elevated privileges to be fixed
+    #[inline]
+    pub fn needs_elevation(&self) -> impl Iterator<Item = &PermissionIssue> {
+        self.issues.iter().filter(|issue| !issue.fixable)
+    }
+
+    /// Fix all the issues which don't need elevated privileges
+    /// 
+    /// - Read-only files and folders of the current user get write permission
+    /// - Files of other users in writable folders are removed, so they can be
+    ///   created again by the current user. Their content is lost, so this
+    ///   should only be used for files which are going to be overwritten
+    /// 
+    /// Return report with the issues left unfixed
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn fix(&self) -> Self {
+        let mut report = Self {
+            checked: self.checked,
+            issues: Vec::new(),
+            fixed: self.fixed.clone()
+        };
+
+        for issue in &self.issues {
+            if !issue.fixable {
+                report.issues.push(issue.clone());
+
+                continue;
+            }
+
+            let result = match issue.problem {
+                Problem::ReadOnly => add_write_permission(&issue.path),
+                Problem::WrongOwner { .. } => std::fs::remove_file(&issue.path),
+
+                _ => Ok(())
+            };
+
+            match result {
+                Ok(()) => report.fixed.push(issue.path.clone()),
+
+                Err(err) => {
+                    tracing::warn!("Failed to fix permissions of {:?}: {err}", issue.path);
+
+                    report.issues.push(PermissionIssue {
+                        path: issue.path.clone(),
+                        problem: issue.problem.clone(),
+                        fixable: false
+                    });
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Scan the whole folder for files and folders current user can't modify
+#[tracing::instrument(level = "debug", ret)]
+pub fn scan(folder: impl AsRef<Path> + std::fmt::Debug) -> PermissionReport {
+    fn walk(path: &Path, report: &mut PermissionReport) {
+        check(path, report);
+
+        let is_dir = path.symlink_metadata()
+            .map(|metadata| metadata.is_dir())
+            .unwrap_or(false);
+
+        if is_dir {
+            if let Ok(entries) = path.read_dir() {
+                for entry in entries.flatten() {
+                    walk(&entry.path(), report);
+                }
+            }
+        }
+    }
+
+    let mut report = PermissionReport::default();
+
+    walk(folder.as_ref(), &mut report);
+
+    report
+}
+
+/// Scan only given files relative to the folder, and their parent folders
+/// 
+/// Files which don't exist are skipped
+#[tracing::instrument(level = "debug", skip(files))]
+pub fn scan_files(folder: impl AsRef<Path> + std::fmt::Debug, files: impl IntoIterator<Item = impl AsRef<Path>>) -> PermissionReport {
+    let folder = folder.as_ref();
+
+    let mut report = PermissionReport::default();
+    let mut checked_folders = std::collections::HashSet::new();
+
+    for file in files {
+        let path = folder.join(file);
+
+        if path.symlink_metadata().is_err() {
+            continue;
+        }
+
+        // Folders are checked only once
+        let mut parent = path.parent();
+
+        while let Some(folder_path) = parent {
+            if !folder_path.starts_with(folder) || !checked_folders.insert(folder_path.to_path_buf()) {
+                break;
+            }
+
+            check(folder_path, &mut report);
+
+            parent = folder_path.parent();
+        }
+
+        check(&path, &mut report);
+    }
+
+    report
+}
+
+/// Scan only folders which will contain given files relative to the folder
+/// 
+/// Files themselves are not checked: they're replaced by `rename` when a staged
+/// installation is committed, which needs only their folders to be writable.
+/// Folders which don't exist yet are checked by their nearest existing parent
+#[tracing::instrument(level = "debug", skip(files))]
+pub fn scan_folders(folder: impl AsRef<Path> + std::fmt::Debug, files: impl IntoIterator<Item = impl AsRef<Path>>) -> PermissionReport {
+    let folder = folder.as_ref();
+
+    let mut report = PermissionReport::default();
+    let mut checked_folders = std::collections::HashSet::new();
+
+    for file in files {
+        let path = folder.join(file);
+
+        let mut parent = path.parent();
+
+        // Skip folders which will be created by the installation
+        while let Some(folder_path) = parent {
+            if folder_path.symlink_metadata().is_ok() {
+                break;
+            }
+
+            parent = folder_path.parent();
+        }
+
+        while let Some(folder_path) = parent {
+            if !folder_path.starts_with(folder) || !checked_folders.insert(folder_path.to_path_buf()) {
+                break;
+            }
+
+            check(folder_path, &mut report);
+
+            parent = folder_path.parent();
+        }
+    }
+
+    report
+}
+
+/// Check single file or folder
+fn check(path: &Path, report: &mut PermissionReport) {
+    report.checked += 1;
+
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            report.issues.push(PermissionIssue {
+                path: path.to_path_buf(),
+                problem: Problem::Inaccessible(err.to_string()),
+                fixable: false
+            });
+
+            return;
+        }
+    };
+
+    // Symlinks are replaced, not modified
+    if metadata.is_symlink() {
+        return;
+    }
+
+    if is_immutable(path) {
+        report.issues.push(PermissionIssue {
+            path: path.to_path_buf(),
+            problem: Problem::Immutable,
+            fixable: false
+        });
+
+        return;
+    }
+
+    if is_writable(path) {
+        return;
+    }
+
+    let uid = unsafe { libc::geteuid() };
+
+    if metadata.uid() == uid {
+        report.issues.push(PermissionIssue {
+            path: path.to_path_buf(),
+            problem: Problem::ReadOnly,
+            fixable: true
+        });
+    }
+
+    else {
+        // Files of other users can be removed if we can write their folder
+        let fixable = metadata.is_file() && path.parent()
+            .map(|parent| can_remove_from(parent, metadata.uid()))
+            .unwrap_or(false);
+
+        report.issues.push(PermissionIssue {
+            path: path.to_path_buf(),
+            problem: Problem::WrongOwner {
+                uid: metadata.uid(),
+                gid: metadata.gid()
+            },
+            fixable
+        });
+    }
+}
+
+/// Check if current user can write to the path
+fn is_writable(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    unsafe {
+        libc::access(path.as_ptr(), libc::W_OK) == 0
+    }
+}
+
+/// Check if current user can remove a file of the given owner from the folder
+fn can_remove_from(folder: &Path, owner: u32) -> bool {
+    if !is_writable(folder) {
+        return false;
+    }
+
+    let Ok(metadata) = folder.metadata() else {
+        return false;
+    };
+
+    let uid = unsafe { libc::geteuid() };
+
+    // Only owners can remove files from folders with sticky bit
+    metadata.mode() & libc::S_ISVTX == 0 || metadata.uid() == uid || owner == uid
+}
+
+/// Check if the path has immutable or append-only attribute
+fn is_immutable(path: &Path) -> bool {
+    // Non-blocking mode prevents hanging on fifos
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path);
+
+    match file {
+        Ok(file) => {
+            // Buffer is as wide as the ioctl's declared argument,
+            // but the kernel writes only an int into it
+            let mut flags: libc::c_long = 0;
+
+            let result = unsafe {
+                libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS as _, &mut flags)
+            };
+
+            result == 0 && flags as libc::c_int & (FS_IMMUTABLE_FL | FS_APPEND_FL) != 0
+        }
+
+        // Attributes of unreadable files can still be read by statx
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => is_immutable_statx(path),
+
+        Err(_) => false
+    }
+}
+
+/// Check immutable and append-only attributes using `statx`, which doesn't need to open the file
+fn is_immutable_statx(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    let mut stat = std::mem::MaybeUninit::<libc::statx>::uninit();
+
+    let result = unsafe {
+        libc::statx(libc::AT_FDCWD, path.as_ptr(), libc::AT_SYMLINK_NOFOLLOW, 0, stat.as_mut_ptr())
+    };
+
+    if result != 0 {
+        return false;
+    }
+
+    let stat = unsafe { stat.assume_init() };
+
+    let attributes = (libc::STATX_ATTR_IMMUTABLE | libc::STATX_ATTR_APPEND) as u64;
+
+    stat.stx_attributes & stat.stx_attributes_mask & attributes != 0
+}
+
+fn add_write_permission(path: &Path) -> std::io::Result<()> {
+    let metadata = path.metadata()?;
+
+    // Folders also need execute permission to access their content
+    let mode = if metadata.is_dir() {
+        metadata.permissions().mode() | 0o700
+    } else {
+        metadata.permissions().mode() | 0o600
+    };
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_permissions() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-permissions");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(path.join("folder"))?;
+
+        std::fs::write(path.join("folder/readonly"), "readonly")?;
+        std::fs::write(path.join("folder/foreign"), "foreign")?;
+
+        std::fs::set_permissions(path.join("folder/readonly"), std::fs::Permissions::from_mode(0o444))?;
+        std::fs::set_permissions(path.join("folder/foreign"), std::fs::Permissions::from_mode(0o600))?;
+
+        // Missing files are skipped, the root and its subfolders are checked only once
+        let report = scan_files(&path, ["folder/readonly", "folder/foreign", "folder/missing"]);
+
+        assert_eq!(report.checked, 4);
+
+        // Superuser can write anything, so only non-root users see the issue
+        if unsafe { libc::geteuid() } != 0 {
+            assert_eq!(report.issues, vec![PermissionIssue {
+                path: path.join("folder/readonly"),
+                problem: Problem::ReadOnly,
+                fixable: true
+            }]);
+        }
+
+        // Issues can't be made for other users without root, so they're constructed by hand
+        let report = PermissionReport {
+            checked: 2,
+            issues: vec![
+                PermissionIssue {
+                    path: path.join("folder/readonly"),
+                    problem: Problem::ReadOnly,
+                    fixable: true
+                },
+                PermissionIssue {
+                    path: path.join("folder/foreign"),
+                    problem: Problem::WrongOwner { uid: 65534, gid: 65534 },
+                    fixable: true
+                },
+                PermissionIssue {
+                    path: path.join("folder/immutable"),
+                    problem: Problem::Immutable,
+                    fixable: false
+                }
+            ],
+            fixed: Vec::new()
+        };
+
+        let fixed = report.fix();
+
+        assert_eq!(fixed.fixed, vec![path.join("folder/readonly"), path.join("folder/foreign")]);
+        assert_eq!(fixed.issues, vec![report.issues[2].clone()]);
+        assert_eq!(fixed.needs_elevation().count(), 1);
+
+        assert_eq!(path.join("folder/readonly").metadata()?.permissions().mode() & 0o777, 0o644);
+        assert!(!path.join("folder/foreign").exists());
+
+        // Issues which failed to be fixed can't be fixed without elevation
+        let fixed = PermissionReport {
+            checked: 1,
+            issues: vec![PermissionIssue {
+                path: path.join("folder/missing"),
+                problem: Problem::WrongOwner { uid: 65534, gid: 65534 },
+                fixable: true
+            }],
+            fixed: Vec::new()
+        }.fix();
+
+        assert!(fixed.fixed.is_empty());
+        assert!(!fixed.issues[0].fixable);
+
+        // Only folders are checked for staged installations
+        let report = scan_folders(&path, ["folder/readonly", "folder/missing/file", "missing/file"]);
+
+        assert_eq!(report.checked, 2);
+
+        let report = scan(&path);
+
+        assert!(report.is_ok());
+        assert_eq!(report.checked, 3);
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_foreign_files() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-permissions-foreign");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(&path)?;
+        std::fs::write(path.join("file"), "file")?;
+
+        let uid = unsafe { libc::geteuid() };
+
+        assert!(can_remove_from(&path, uid));
+        assert!(can_remove_from(&path, uid + 1));
+
+        // Folder of the current user with sticky bit still allows removing files
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o1777))?;
+
+        assert!(can_remove_from(&path, uid + 1));
+
+        assert!(!is_immutable(&path.join("file")));
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
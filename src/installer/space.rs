@@ -0,0 +1,378 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::{Serialize, Deserialize};
+
+use super::archives::{Archive, Entry, Size};
+use super::downloader::DownloadingError;
+use super::free_space;
+
+/// Block size used if the real one couldn't be read
+const DEFAULT_BLOCK_SIZE: u64 = 4096;
+
+/// Space needed by the installation on a single disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskSpace {
+    /// Some path on this disk
+    pub path: PathBuf,
+
+    /// Max amount of bytes the installation will take at once
+    pub peak: u64,
+
+    /// Difference of used space after the installation is finished
+    /// 
+    /// Negative if the installation frees some space
+    pub delta: i64
+}
+
+/// Space requirements of the installation, grouped by disks
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceRequirements {
+    pub disks: Vec<DiskSpace>
+}
+
+impl SpaceRequirements {
+    /// Requirements for downloading and unpacking the archive of known sizes
+    /// 
+    /// Used when only the sizes reported by the API are available
+    pub fn from_sizes(temp_folder: impl AsRef<Path>, unpack_to: impl AsRef<Path>, downloaded_size: u64, unpacked_size: u64) -> Self {
+        let mut requirements = Self::default();
+
+        requirements.add(temp_folder, downloaded_size, 0);
+        requirements.add(unpack_to, unpacked_size, unpacked_size as i64);
+
+        requirements
+    }
+
+    /// Add requirements of some path, merging them with the ones of the same disk
+    /// 
+    /// Peaks of the same disk are summed since all of them could happen at the same time
+    pub fn add(&mut self, path: impl AsRef<Path>, peak: u64, delta: i64) {
+        let path = path.as_ref();
+        let filesystem = free_space::filesystem_id(path);
+
+        match self.disks.iter_mut().find(|disk| filesystem.is_some() && free_space::filesystem_id(&disk.path) == filesystem) {
+            Some(disk) => {
+                disk.peak += peak;
+                disk.delta += delta;
+            }
+
+            None => self.disks.push(DiskSpace {
+                path: path.to_path_buf(),
+                peak,
+                delta
+            })
+        }
+    }
+
+    /// Get requirements of the disk with the given path
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&DiskSpace> {
+        let filesystem = free_space::filesystem_id(path)?;
+
+        self.disks.iter().find(|disk| free_space::filesystem_id(&disk.path) == Some(filesystem))
+    }
+
+    /// Check that all the disks have enough free space
+    /// 
+    /// Space reserved by other running operations is not counted as free
+    pub fn check(&self) -> Result<(), DownloadingError> {
+        for disk in &self.disks {
+            let Some(available) = free_space::unreserved(&disk.path) else {
+                return Err(DownloadingError::PathNotMounted(disk.path.clone()));
+            };
+
+            if available < disk.peak {
+                tracing::error!("No free space available for {:?}. Required: {}. Available: {available}", disk.path, disk.peak);
+
+                return Err(DownloadingError::NoSpaceAvailable(disk.path.clone(), disk.peak, available));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that all the disks have enough free space and reserve it
+    /// 
+    /// Space is reserved until returned reservations are dropped
+    pub fn reserve(&self) -> Result<Vec<free_space::Reservation>, DownloadingError> {
+        self.disks.iter()
+            .filter(|disk| disk.peak > 0)
+            .map(|disk| {
+                free_space::reserve(&disk.path, disk.peak).inspect_err(|err| {
+                    tracing::error!("Failed to reserve free space for {:?}: {err}", disk.path);
+                })
+            })
+            .collect()
+    }
+}
+
+/// Get block size of the filesystem the path is stored on
+fn block_size(path: &Path) -> u64 {
+    free_space::info(path)
+        .map(|info| info.block_size)
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BLOCK_SIZE)
+}
+
+/// Round file size up to the amount of blocks it takes on the disk
+#[inline]
+fn on_disk(size: u64, block_size: u64) -> u64 {
+    size.div_ceil(block_size) * block_size
+}
+
+/// Read text entry of the archive, returning empty string if it's missing
+fn read_text_entry(archive: &Archive, name: &str) -> String {
+    let mut content = String::new();
+
+    if let Ok(mut reader) = archive.read_entry(name) {
+        #[allow(unused_must_use)] {
+            reader.read_to_string(&mut content);
+        }
+    }
+
+    content
+}
+
+/// Read archive entries with the content of `hdifffiles.txt` and `deletefiles.txt`
+/// 
+/// Natively supported archives are read in a single pass
+fn read_entries(archive: &Archive) -> anyhow::Result<(Vec<Entry>, String, String)> {
+    if let Archive::SevenZ(_) | Archive::ZipMultipart(_) = archive {
+        return Ok((
+            archive.get_entries()?,
+            read_text_entry(archive, "hdifffiles.txt"),
+            read_text_entry(archive, "deletefiles.txt")
+        ));
+    }
+
+    let mut entries = Vec::new();
+
+    let mut hdiff_files = String::new();
+    let mut delete_files = String::new();
+
+    archive.for_each_entry(|entry, reader| {
+        match entry.name.as_str() {
+            "hdifffiles.txt" => reader.read_to_string(&mut hdiff_files)?,
+            "deletefiles.txt" => reader.read_to_string(&mut delete_files)?,
+
+            _ => 0
+        };
+
+        entries.push(entry.clone());
+
+        Ok(())
+    })?;
+
+    Ok((entries, hdiff_files, delete_files))
+}
+
+/// Estimate space needed to unpack already downloaded archive into the given folder
+/// 
+/// Counts sizes of the archive entries, files they overwrite, files listed in the
+/// `deletefiles.txt` and `hdifffiles.txt`, and the filesystem's block size.
+/// `transactional` installations keep original files until all the new ones are unpacked
+/// 
+/// Archive is expected to be removed after unpacking
+pub fn estimate(archive: &Archive, unpack_to: impl AsRef<Path>, transactional: bool) -> anyhow::Result<SpaceRequirements> {
+    let unpack_to = unpack_to.as_ref();
+    let block_size = block_size(unpack_to);
+
+    let existing_size = |relative: &str| {
+        unpack_to.join(relative)
+            .metadata()
+            .map(|metadata| on_disk(metadata.len(), block_size))
+            .unwrap_or(0)
+    };
+
+    let (entries, hdiff_files, delete_files) = read_entries(archive)?;
+
+    // Uncompressed sizes of the archive files, in the extraction order
+    let entries = entries.into_iter()
+        .filter(|entry| !entry.name.ends_with('/'))
+        .map(|entry| {
+            let size = match entry.size {
+                Size::Both { uncompressed, .. } => uncompressed,
+                Size::Compressed(size) | Size::Uncompressed(size) => size
+            };
+
+            (entry.name, on_disk(size, block_size))
+        })
+        .collect::<Vec<_>>();
+
+    // Files which will be replaced by the new ones
+    let mut replaced = Vec::new();
+
+    let mut current = 0;
+    let mut peak = 0;
+
+    // Unpacking archive entries
+    for (name, size) in &entries {
+        current += *size as i64;
+
+        if !name.ends_with(".hdiff") {
+            replaced.push(name.clone());
+        }
+
+        // Overwritten file is truncated before the new one is written
+        if !transactional {
+            current -= existing_size(name) as i64;
+        }
+
+        peak = peak.max(current);
+    }
+
+    let patches = entries.iter()
+        .filter(|(name, _)| name.ends_with(".hdiff"))
+        .map(|(name, size)| (name.as_str(), *size))
+        .collect::<HashMap<_, _>>();
+
+    // Applying hdiff patches
+    // {"remoteName": "AnimeGame_Data/StreamingAssets/Audio/GeneratedSoundBanks/Windows/Japanese/1001.pck"}
+    for line in hdiff_files.lines() {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+
+        let Some(file) = value.get("remoteName").and_then(|name| name.as_str()) else {
+            continue;
+        };
+
+        // Patched file is expected to be about the same size as the original one
+        let original = existing_size(file) as i64;
+
+        current += original;
+
+        peak = peak.max(current);
+
+        // Patch is removed when applied
+        current -= patches.get(format!("{file}.hdiff").as_str())
+            .copied()
+            .unwrap_or(0) as i64;
+
+        if transactional {
+            replaced.push(file.to_string());
+        }
+
+        // Original file is replaced by the patched one
+        else {
+            current -= original;
+        }
+    }
+
+    // Staged files replace original ones on commit
+    if transactional {
+        for file in &replaced {
+            current -= existing_size(file) as i64;
+        }
+    }
+
+    // Removing outdated files
+    for file in delete_files.lines() {
+        current -= existing_size(file.trim()) as i64;
+    }
+
+    let mut requirements = SpaceRequirements::default();
+
+    requirements.add(unpack_to, peak.max(0) as u64, current);
+
+    // Downloaded archive is already stored on the disk and will be removed
+    requirements.add(archive.path(), 0, -(super::archives::get_archive_size(archive.path()) as i64));
+
+    Ok(requirements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_archive_order() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-space-estimate");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(folder.join("game"))?;
+
+        let block_size = block_size(&folder) as usize;
+
+        // Overwritten file frees its space only after the new one is unpacked
+        std::fs::write(folder.join("game/big"), vec![0; block_size * 2])?;
+
+        let mut builder = tar::Builder::new(std::fs::File::create(folder.join("archive.tar"))?);
+
+        for name in ["new", "big"] {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(block_size as u64);
+            header.set_cksum();
+
+            builder.append_data(&mut header, name, vec![0; block_size].as_slice())?;
+        }
+
+        builder.into_inner()?;
+
+        let archive = Archive::open(folder.join("archive.tar"))?;
+
+        let requirements = estimate(&archive, folder.join("game"), false)?;
+
+        assert_eq!(requirements.get(folder.join("game")).unwrap().peak, block_size as u64);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_text_entries() -> anyhow::Result<()> {
+        let folder = std::env::temp_dir().join(".agc-test-space-text-entries");
+
+        if folder.exists() {
+            std::fs::remove_dir_all(&folder)?;
+        }
+
+        std::fs::create_dir_all(folder.join("game"))?;
+
+        let block_size = block_size(&folder) as usize;
+
+        std::fs::write(folder.join("game/patched"), vec![0; block_size * 2])?;
+        std::fs::write(folder.join("game/old"), vec![0; block_size * 3])?;
+
+        let encoder = flate2::write::GzEncoder::new(std::fs::File::create(folder.join("archive.tar.gz"))?, flate2::Compression::default());
+
+        let mut builder = tar::Builder::new(encoder);
+
+        let hdiff_files = "{\"remoteName\": \"patched\"}\n";
+        let patch = vec![0; block_size];
+
+        for (name, content) in [("new", b"new".as_slice()), ("hdifffiles.txt", hdiff_files.as_bytes()), ("patched.hdiff", &patch), ("deletefiles.txt", b"old\n")] {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+
+            builder.append_data(&mut header, name, content)?;
+        }
+
+        builder.into_inner()?.finish()?;
+
+        let archive = Archive::open(folder.join("archive.tar.gz"))?;
+
+        let requirements = estimate(&archive, folder.join("game"), false)?;
+        let disk = requirements.get(folder.join("game")).unwrap();
+
+        // 4 unpacked entries and a copy of the patched file
+        assert_eq!(disk.peak, block_size as u64 * 6);
+
+        // New files take as much space as the removed outdated file
+        let archive_size = std::fs::metadata(folder.join("archive.tar.gz"))?.len() as i64;
+
+        assert_eq!(disk.delta, -archive_size);
+
+        std::fs::remove_dir_all(folder)?;
+
+        Ok(())
+    }
+}
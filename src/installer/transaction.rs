@@ -0,0 +1,661 @@
+use std::path::{Path, PathBuf};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+
+/// Name of the file inside of the backup folder
+/// which lists all the changes made by the commit
+/// 
+/// Every change is written as its kind byte followed by the raw
+/// bytes of the file path relative to the root folder and a NUL byte
+const JOURNAL_NAME: &str = ".journal";
+
+/// File was created by the transaction
+const JOURNAL_CREATED: u8 = b'C';
+
+/// Folder was created by the transaction
+const JOURNAL_CREATED_FOLDER: u8 = b'D';
+
+/// File was replaced or removed by the transaction
+const JOURNAL_REPLACED: u8 = b'R';
+
+/// Set of changes to the game folder which are applied all at once
+/// 
+/// New files are written to the staging folder (a sibling of the game folder),
+/// and outdated ones are scheduled for removal. `commit` moves staged files
+/// to the game folder, backing up the files they replace, and if anything fails
+/// the game folder is restored to its previous state
+/// 
+/// Dropped transaction is rolled back, so early returns and panics
+/// don't leave the game folder in a mixed state
+/// 
+/// Only one transaction can modify the folder at a time, e.g. the game update
+/// and the voice package installation wait for each other
+/// 
+/// `Transaction::direct` makes a transaction which writes new files right
+/// into the game folder and can't be rolled back. It allows to use the same
+/// code for both transactional and direct installations
+/// 
+/// ```no_run
+/// use anime_game_core::installer::transaction::Transaction;
+/// 
+/// let mut transaction = Transaction::new("/path/to/game").unwrap();
+/// 
+/// std::fs::write(transaction.staging().join(".version"), [4, 0, 0]).unwrap();
+/// 
+/// transaction.remove("Game_Data/outdated.dll");
+/// 
+/// transaction.commit().expect("Failed to update the game");
+/// ```
+#[derive(Debug)]
+pub struct Transaction {
+    root: PathBuf,
+    staging: PathBuf,
+    backup: PathBuf,
+
+    /// Committed backup folder which is being removed
+    trash: PathBuf,
+
+    /// Files relative to the root folder which should be removed
+    removed: Vec<PathBuf>,
+
+    /// Changes are applied to the root folder directly
+    direct: bool,
+
+    finished: bool,
+
+    lock_path: PathBuf,
+
+    /// Exclusive lock of the folder, released when the transaction is dropped
+    _lock: File
+}
+
+impl Transaction {
+    #[inline]
+    /// Start new transaction for the given folder
+    /// 
+    /// Blocks until another transaction of this folder is finished.
+    /// Leftovers of the previous unfinished transaction are rolled back first
+    pub fn new(root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        Self::open(root.into(), false)
+    }
+
+    #[inline]
+    /// Start new direct transaction for the given folder
+    /// 
+    /// Staging folder is the root folder itself, so all the new files
+    /// are written to it immediately. Scheduled files are removed on commit,
+    /// and nothing is reverted if the transaction is rolled back
+    pub fn direct(root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        Self::open(root.into(), true)
+    }
+
+    fn open(root: PathBuf, direct: bool) -> std::io::Result<Self> {
+
+        let name = root.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Wrong transaction folder: {:?}", root)))?;
+
+        let parent = root.parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(&parent)?;
+        }
+
+        let lock_path = parent.join(format!(".{name}.lock"));
+        let lock = lock_file(&lock_path)?;
+
+        let transaction = Self {
+            staging: if direct { root.clone() } else { parent.join(format!(".{name}.staging")) },
+            backup: parent.join(format!(".{name}.backup")),
+            trash: parent.join(format!(".{name}.trash")),
+            root,
+            removed: Vec::new(),
+            direct,
+            finished: false,
+            lock_path,
+            _lock: lock
+        };
+
+        // Previous transaction was committed but its backup wasn't removed
+        if transaction.trash.exists() {
+            std::fs::remove_dir_all(&transaction.trash)?;
+        }
+
+        if transaction.backup.exists() {
+            tracing::warn!("Found unfinished transaction, restoring {:?}", transaction.root);
+
+            transaction.restore()?;
+        }
+
+        if !direct && transaction.staging.exists() {
+            std::fs::remove_dir_all(&transaction.staging)?;
+        }
+
+        std::fs::create_dir_all(&transaction.root)?;
+        std::fs::create_dir_all(&transaction.staging)?;
+
+        Ok(transaction)
+    }
+
+    #[inline]
+    /// Get path to the folder this transaction modifies
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    #[inline]
+    /// Check if changes are applied to the root folder directly
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    #[inline]
+    /// Get path to the folder where new files should be written
+    /// 
+    /// It's the root folder itself for direct transactions
+    pub fn staging(&self) -> &Path {
+        self.staging.as_path()
+    }
+
+    /// Get current path of the file relative to the root folder
+    /// 
+    /// Return staged file if it exists, or the original one otherwise
+    pub fn resolve(&self, relative: impl AsRef<Path>) -> PathBuf {
+        let staged = self.staging.join(relative.as_ref());
+
+        if staged.symlink_metadata().is_ok() {
+            staged
+        } else {
+            self.root.join(relative)
+        }
+    }
+
+    #[inline]
+    /// Schedule file relative to the root folder for removal
+    pub fn remove(&mut self, relative: impl Into<PathBuf>) {
+        self.removed.push(relative.into());
+    }
+
+    /// Move staged files to the root folder and remove scheduled ones
+    /// 
+    /// If anything fails, all the changes are reverted
+    #[tracing::instrument(level = "debug", skip(self), fields(root = ?self.root))]
+    pub fn commit(mut self) -> anyhow::Result<()> {
+        tracing::debug!("Committing transaction");
+
+        if self.direct {
+            self.finished = true;
+
+            for relative in &self.removed {
+                match std::fs::remove_file(self.root.join(relative)) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                        anyhow::bail!("Failed to remove {:?}: {err}", relative);
+                    }
+
+                    _ => ()
+                }
+            }
+
+            return Ok(());
+        }
+
+        let mut staged = Vec::new();
+
+        list_entries(&self.staging, &self.staging, &mut staged)?;
+
+        if let Err(err) = self.apply(&staged) {
+            tracing::error!("Failed to commit transaction: {err}");
+
+            self.finished = true;
+
+            if let Err(restore_err) = self.restore() {
+                anyhow::bail!("Failed to commit transaction: {err}. Failed to restore previous files: {restore_err}");
+            }
+
+            #[allow(unused_must_use)] {
+                std::fs::remove_dir_all(&self.staging);
+            }
+
+            anyhow::bail!("Failed to commit transaction: {err}");
+        }
+
+        self.finished = true;
+
+        // Removing folder is not atomic, so the journal could be left without some backups
+        // and restored on the next transaction. Renaming makes the commit final at once
+        std::fs::rename(&self.backup, &self.trash)?;
+        std::fs::remove_dir_all(&self.trash)?;
+        std::fs::remove_dir_all(&self.staging)?;
+
+        Ok(())
+    }
+
+    /// Discard all the staged changes
+    #[tracing::instrument(level = "debug", skip(self), fields(root = ?self.root))]
+    pub fn rollback(mut self) -> std::io::Result<()> {
+        tracing::debug!("Rolling back transaction");
+
+        self.finished = true;
+
+        self.discard()
+    }
+
+    fn discard(&self) -> std::io::Result<()> {
+        if !self.direct && self.staging.exists() {
+            std::fs::remove_dir_all(&self.staging)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move files, writing every change to the journal before making it
+    fn apply(&self, staged: &[PathBuf]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.backup)?;
+
+        let mut journal = File::create(self.backup.join(JOURNAL_NAME))?;
+
+        for relative in staged {
+            let target = self.root.join(relative);
+
+            // Create missing parent folders one by one
+            // so they could be removed by the rollback
+            if let Some(parent) = relative.parent() {
+                let mut folders = parent.ancestors()
+                    .filter(|folder| !folder.as_os_str().is_empty())
+                    .collect::<Vec<_>>();
+
+                folders.reverse();
+
+                for folder in folders {
+                    if self.root.join(folder).symlink_metadata().is_err() {
+                        write_change(&mut journal, JOURNAL_CREATED_FOLDER, folder)?;
+
+                        std::fs::create_dir(self.root.join(folder))?;
+                    }
+                }
+            }
+
+            let replaced = target.symlink_metadata().is_ok();
+
+            write_change(&mut journal, if replaced { JOURNAL_REPLACED } else { JOURNAL_CREATED }, relative)?;
+
+            if replaced {
+                move_file(&target, &self.backup.join(relative))?;
+            }
+
+            move_file(&self.staging.join(relative), &target)?;
+        }
+
+        for relative in &self.removed {
+            let target = self.root.join(relative);
+
+            if target.symlink_metadata().is_ok() {
+                write_change(&mut journal, JOURNAL_REPLACED, relative)?;
+
+                move_file(&target, &self.backup.join(relative))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Revert changes listed in the backup journal
+    fn restore(&self) -> std::io::Result<()> {
+        let journal = std::fs::read(self.backup.join(JOURNAL_NAME))
+            .unwrap_or_default();
+
+        for change in journal.split(|byte| *byte == 0).rev() {
+            let Some((kind, relative)) = change.split_first() else {
+                continue;
+            };
+
+            let relative = Path::new(OsStr::from_bytes(relative));
+
+            let target = self.root.join(relative);
+            let backup = self.backup.join(relative);
+
+            let exists = target.symlink_metadata().is_ok();
+
+            match *kind {
+                JOURNAL_CREATED if exists => std::fs::remove_file(&target)?,
+
+                // Folder could get other files after the interrupted transaction
+                JOURNAL_CREATED_FOLDER if exists => match std::fs::remove_dir(&target) {
+                    Err(err) if err.kind() != std::io::ErrorKind::DirectoryNotEmpty => return Err(err),
+                    _ => ()
+                }
+
+                JOURNAL_REPLACED if backup.symlink_metadata().is_ok() => {
+                    if exists {
+                        std::fs::remove_file(&target)?;
+                    }
+
+                    move_file(&backup, &target)?;
+                }
+
+                _ => ()
+            }
+        }
+
+        std::fs::remove_dir_all(&self.backup)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            tracing::warn!("Transaction wasn't committed, discarding staged changes of {:?}", self.root);
+
+            if let Err(err) = self.discard() {
+                tracing::error!("Failed to discard staged changes: {err}");
+            }
+        }
+
+        // Lock file is removed while it's still locked,
+        // so waiting transactions will lock a new one
+        #[allow(unused_must_use)] {
+            std::fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+/// Create and exclusively lock the file
+/// 
+/// Blocks until the file is unlocked. If it was removed by the previous
+/// transaction while we were waiting, the newly created file is locked instead
+fn lock_file(path: &Path) -> std::io::Result<File> {
+    loop {
+        let lock = File::create(path)?;
+
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let locked = lock.metadata()?;
+
+        if let Ok(current) = path.metadata() {
+            if current.dev() == locked.dev() && current.ino() == locked.ino() {
+                return Ok(lock);
+            }
+        }
+    }
+}
+
+/// Write change to the journal before making it
+fn write_change(journal: &mut File, kind: u8, relative: &Path) -> std::io::Result<()> {
+    journal.write_all(&[kind])?;
+    journal.write_all(relative.as_os_str().as_bytes())?;
+    journal.write_all(&[0])?;
+
+    journal.sync_data()
+}
+
+/// Rename file creating its parent folders
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::rename(from, to)
+}
+
+/// List all the files and symlinks from the folder, relative to this folder
+fn list_entries(root: &Path, path: &Path, entries: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            list_entries(root, &entry.path(), entries)?;
+        }
+
+        else if let Ok(path) = entry.path().strip_prefix(root) {
+            entries.push(path.to_path_buf());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(name: &str) -> std::io::Result<PathBuf> {
+        let parent = std::env::temp_dir().join(format!(".agc-test-transaction-{name}"));
+
+        if parent.exists() {
+            std::fs::remove_dir_all(&parent)?;
+        }
+
+        let root = parent.join("game");
+
+        std::fs::create_dir_all(&root)?;
+
+        std::fs::write(root.join("replaced"), "old")?;
+        std::fs::write(root.join("removed"), "old")?;
+
+        Ok(root)
+    }
+
+    fn leftovers(root: &Path) -> std::io::Result<Vec<String>> {
+        let mut entries = std::fs::read_dir(root.parent().unwrap())?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        entries.sort();
+
+        Ok(entries)
+    }
+
+    #[test]
+    pub fn test_commit() -> anyhow::Result<()> {
+        let root = prepare("commit")?;
+
+        let mut transaction = Transaction::new(&root)?;
+
+        std::fs::create_dir_all(transaction.staging().join("folder"))?;
+
+        std::fs::write(transaction.staging().join("replaced"), "new")?;
+        std::fs::write(transaction.staging().join("folder/created"), "new")?;
+
+        transaction.remove("removed");
+
+        assert_eq!(transaction.resolve("replaced"), transaction.staging().join("replaced"));
+        assert_eq!(transaction.resolve("removed"), root.join("removed"));
+
+        transaction.commit()?;
+
+        assert_eq!(std::fs::read_to_string(root.join("replaced"))?, "new");
+        assert_eq!(std::fs::read_to_string(root.join("folder/created"))?, "new");
+        assert!(!root.join("removed").exists());
+
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        std::fs::remove_dir_all(root.parent().unwrap())?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_rollback() -> anyhow::Result<()> {
+        let root = prepare("rollback")?;
+
+        let mut transaction = Transaction::new(&root)?;
+
+        std::fs::write(transaction.staging().join("replaced"), "new")?;
+        std::fs::write(transaction.staging().join("created"), "new")?;
+
+        transaction.remove("removed");
+        transaction.rollback()?;
+
+        // Dropped transaction is rolled back as well
+        let transaction = Transaction::new(&root)?;
+
+        std::fs::write(transaction.staging().join("created"), "new")?;
+
+        drop(transaction);
+
+        assert_eq!(std::fs::read_to_string(root.join("replaced"))?, "old");
+        assert_eq!(std::fs::read_to_string(root.join("removed"))?, "old");
+        assert!(!root.join("created").exists());
+
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        std::fs::remove_dir_all(root.parent().unwrap())?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_recovery() -> anyhow::Result<()> {
+        let root = prepare("recovery")?;
+        let parent = root.parent().unwrap();
+
+        // Interrupted commit: "created" was moved, "replaced" was backed up
+        // but its new version wasn't moved yet
+        std::fs::create_dir_all(parent.join(".game.backup"))?;
+        std::fs::create_dir_all(parent.join(".game.staging"))?;
+
+        std::fs::write(root.join("created"), "new")?;
+        std::fs::rename(root.join("replaced"), parent.join(".game.backup/replaced"))?;
+        std::fs::write(parent.join(".game.staging/replaced"), "new")?;
+        std::fs::write(parent.join(".game.backup/.journal"), "Ccreated\0Rreplaced\0")?;
+
+        Transaction::new(&root)?.rollback()?;
+
+        assert_eq!(std::fs::read_to_string(root.join("replaced"))?, "old");
+        assert!(!root.join("created").exists());
+
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        // Interrupted cleanup of the committed transaction
+        std::fs::create_dir_all(parent.join(".game.trash"))?;
+
+        std::fs::write(root.join("created"), "new")?;
+        std::fs::write(parent.join(".game.trash/.journal"), "Ccreated\0")?;
+
+        Transaction::new(&root)?.rollback()?;
+
+        assert_eq!(std::fs::read_to_string(root.join("created"))?, "new");
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        std::fs::remove_dir_all(parent)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_raw_names() -> anyhow::Result<()> {
+        let root = prepare("raw-names")?;
+        let parent = root.parent().unwrap();
+
+        let name = OsStr::from_bytes(b"file-\xff");
+
+        // Interrupted commit: "folder/sub" was created for the new file,
+        // and file with non-UTF-8 name was backed up and replaced
+        std::fs::create_dir_all(parent.join(".game.backup"))?;
+        std::fs::create_dir_all(root.join("folder/sub"))?;
+
+        std::fs::write(root.join("folder/sub").join(name), "new")?;
+        std::fs::write(parent.join(".game.backup").join(name), "old")?;
+        std::fs::write(root.join(name), "new")?;
+
+        let mut journal = File::create(parent.join(".game.backup/.journal"))?;
+
+        write_change(&mut journal, JOURNAL_CREATED_FOLDER, Path::new("folder"))?;
+        write_change(&mut journal, JOURNAL_CREATED_FOLDER, Path::new("folder/sub"))?;
+        write_change(&mut journal, JOURNAL_CREATED, &Path::new("folder/sub").join(name))?;
+        write_change(&mut journal, JOURNAL_REPLACED, Path::new(name))?;
+
+        drop(journal);
+
+        Transaction::new(&root)?.rollback()?;
+
+        assert_eq!(std::fs::read_to_string(root.join(name))?, "old");
+        assert!(!root.join("folder").exists());
+
+        // Committed file with non-UTF-8 name
+        let mut transaction = Transaction::new(&root)?;
+
+        std::fs::create_dir_all(transaction.staging().join("folder"))?;
+        std::fs::write(transaction.staging().join("folder").join(name), "new")?;
+
+        transaction.remove(name);
+        transaction.commit()?;
+
+        assert_eq!(std::fs::read_to_string(root.join("folder").join(name))?, "new");
+        assert!(!root.join(name).exists());
+
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        std::fs::remove_dir_all(parent)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_direct() -> anyhow::Result<()> {
+        let root = prepare("direct")?;
+
+        let mut transaction = Transaction::direct(&root)?;
+
+        assert_eq!(transaction.staging(), root);
+
+        std::fs::write(transaction.staging().join("replaced"), "new")?;
+
+        transaction.remove("removed");
+
+        // Scheduled files are removed only on commit
+        assert!(root.join("removed").exists());
+
+        transaction.commit()?;
+
+        assert_eq!(std::fs::read_to_string(root.join("replaced"))?, "new");
+        assert!(!root.join("removed").exists());
+
+        assert_eq!(leftovers(&root)?, vec!["game"]);
+
+        std::fs::remove_dir_all(root.parent().unwrap())?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_lock() -> anyhow::Result<()> {
+        let root = prepare("lock")?;
+
+        let transaction = Transaction::new(&root)?;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let thread = std::thread::spawn({
+            let root = root.clone();
+
+            move || -> std::io::Result<()> {
+                let transaction = Transaction::new(root)?;
+
+                sender.send(()).unwrap();
+
+                transaction.rollback()
+            }
+        });
+
+        assert!(receiver.recv_timeout(std::time::Duration::from_millis(200)).is_err());
+
+        transaction.rollback()?;
+
+        assert!(receiver.recv_timeout(std::time::Duration::from_secs(5)).is_ok());
+
+        thread.join().unwrap()?;
+
+        std::fs::remove_dir_all(root.parent().unwrap())?;
+
+        Ok(())
+    }
+}
@@ -48,7 +48,7 @@ impl From<&JsonValue> for JadeitePatchMetadata {
         Self {
             version: value.get("version")
                 .and_then(|version| version.as_str())
-                .and_then(Version::from_str)
+                .and_then(|version| version.parse().ok())
                 .unwrap_or(default.version)
         }
     }
@@ -172,7 +172,7 @@ impl From<&JsonValue> for JadeitePatchStatus {
 
             version: value.get("version")
                 .and_then(|version| version.as_str())
-                .and_then(Version::from_str)
+                .and_then(|version| version.parse().ok())
                 .unwrap_or(default.version)
         }
     }
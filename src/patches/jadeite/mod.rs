@@ -24,9 +24,7 @@ pub fn is_installed(folder: impl AsRef<Path>) -> bool {
 }
 
 pub fn get_version(folder: impl AsRef<Path>) -> anyhow::Result<Version> {
-    let bytes = std::fs::read(folder.as_ref().join(".version"))?;
-
-    Ok(Version::new(bytes[0], bytes[1], bytes[2]))
+    Version::read_file(folder.as_ref().join(".version"))
 }
 
 #[cfg(feature = "install")]
@@ -37,7 +35,7 @@ pub fn get_latest() -> anyhow::Result<JadeiteLatest> {
     let version = response.get("tag_name")
         .and_then(|tag| tag.as_str())
         .map(|tag| tag.strip_prefix('v').unwrap_or(tag))
-        .and_then(Version::from_str);
+        .and_then(|version| version.parse().ok());
 
     let Some(version) = version else {
         anyhow::bail!("Failed to request latest patch version");
@@ -91,9 +89,9 @@ impl JadeiteLatest {
         Installer::new(&self.download_uri)?
             .with_filename("jadeite.zip")
             .with_free_space_check(false)
-            .install(folder.as_ref(), updater);
+            .install(folder.as_ref(), updater)?;
 
-        std::fs::write(folder.as_ref().join(".version"), self.version.version)?;
+        self.version.write_file(folder.as_ref().join(".version"))?;
 
         Ok(())
     }
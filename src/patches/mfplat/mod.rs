@@ -2,7 +2,7 @@ use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use std::process::{Command, Stdio};
 
-use md5::{Md5, Digest};
+use crate::repairer::hash;
 
 #[cfg(feature = "install")]
 use crate::installer::{
@@ -24,7 +24,7 @@ pub fn is_applied(prefix_path: impl AsRef<Path>) -> anyhow::Result<bool> {
         return Ok(false);
     }
 
-    Ok(format!("{:x}", Md5::digest(std::fs::read(mfplat_path)?)) == MFPLAT_DLL_HASH)
+    Ok(hash::md5_file(mfplat_path)? == MFPLAT_DLL_HASH)
 }
 
 #[cfg(feature = "install")]
@@ -39,7 +39,7 @@ pub fn apply(prefix_path: impl AsRef<OsStr>, temp_folder: impl AsRef<Path>) -> a
     Downloader::new(PATCH_URI)?.download(&mfplat, |_, _| {})?;
 
     // Verify archive hash
-    if format!("{:x}", Md5::digest(std::fs::read(&mfplat)?)) != PATCH_HASH {
+    if hash::md5_file(&mfplat)? != PATCH_HASH {
         anyhow::bail!("Incorrect mfplat patch hash");
     }
 
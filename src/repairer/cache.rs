@@ -0,0 +1,270 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::version::Version;
+
+use super::IntegrityFile;
+
+/// Name of the cache file stored in the game folder
+pub const CACHE_FILE_NAME: &str = ".integrity_cache.json";
+
+/// Name of the temporary file the default cache is written to before being renamed
+pub const CACHE_TEMP_FILE_NAME: &str = ".integrity_cache.tmp";
+
+/// Metadata of the file which was verified by its md5 hash
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    pub inode: u64,
+
+    /// Verified md5 hash of the file
+    pub md5: String
+}
+
+impl CacheEntry {
+    /// Get cache entry for the file with given hash
+    /// 
+    /// Return `None` if the file's metadata couldn't be read
+    pub fn new(path: impl AsRef<Path>, md5: impl ToString) -> Option<Self> {
+        let metadata = path.as_ref().metadata().ok()?;
+
+        Some(Self {
+            size: metadata.len(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+            inode: metadata.ino(),
+            md5: md5.to_string().to_ascii_lowercase()
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheFile {
+    version: Option<Version>,
+    entries: HashMap<PathBuf, CacheEntry>
+}
+
+/// Files of the game installation which were already verified
+/// 
+/// Files whose size, modification time and inode weren't changed
+/// since their verification are trusted and not hashed again.
+/// Cache is cleared when the game version is changed
+/// 
+/// ```no_run
+/// use anime_game_core::version::Version;
+/// use anime_game_core::repairer::IntegrityFile;
+/// use anime_game_core::repairer::cache::VerificationCache;
+/// use anime_game_core::repairer::verify::*;
+/// 
+/// let files: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let mut cache = VerificationCache::open("/path/to/game", Version::new(4, 0, 0));
+/// 
+/// let broken = verify_all_cached("/path/to/game", files, VerifyOptions::default(), &mut cache, |_| {});
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationCache {
+    path: PathBuf,
+    cache: CacheFile
+}
+
+impl VerificationCache {
+    /// Open cache file of the game installation
+    /// 
+    /// Cache is empty if the file doesn't exist, is corrupted,
+    /// or was made for another game version
+    #[inline]
+    pub fn open(game_dir: impl AsRef<Path>, version: Version) -> Self {
+        Self::open_file(game_dir.as_ref().join(CACHE_FILE_NAME), version)
+    }
+
+    /// Open cache stored in the custom file
+    pub fn open_file(path: impl Into<PathBuf>, version: Version) -> Self {
+        let path = path.into();
+
+        let cache = std::fs::read(&path).ok()
+            .and_then(|cache| serde_json::from_slice::<CacheFile>(&cache).ok())
+            .filter(|cache| cache.version == Some(version))
+            .unwrap_or_else(|| CacheFile {
+                version: Some(version),
+                entries: HashMap::new()
+            });
+
+        Self {
+            path,
+            cache
+        }
+    }
+
+    #[inline]
+    /// Get path to the cache file
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    #[inline]
+    /// Get game version this cache was made for
+    pub fn version(&self) -> Option<Version> {
+        self.cache.version
+    }
+
+    #[inline]
+    /// Get amount of cached files
+    pub fn len(&self) -> usize {
+        self.cache.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cache.entries.is_empty()
+    }
+
+    #[inline]
+    /// Get cache entry of the file relative to the game folder
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&CacheEntry> {
+        self.cache.entries.get(path.as_ref())
+    }
+
+    /// Check if the file was verified with the same hash and wasn't changed since then
+    pub fn is_verified(&self, game_dir: impl AsRef<Path>, file: &IntegrityFile) -> bool {
+        let Some(entry) = self.cache.entries.get(&file.path) else {
+            return false;
+        };
+
+        if entry.size != file.size || !entry.md5.eq_ignore_ascii_case(&file.md5) {
+            return false;
+        }
+
+        CacheEntry::new(game_dir.as_ref().join(&file.path), &entry.md5).as_ref() == Some(entry)
+    }
+
+    /// Remember that the file matches its integrity info
+    /// 
+    /// `entry` must be made before the file was hashed, so changes
+    /// made during the hashing invalidate the cached entry
+    pub fn insert(&mut self, file: &IntegrityFile, entry: CacheEntry) {
+        self.cache.entries.insert(file.path.clone(), entry);
+    }
+
+    #[inline]
+    /// Forget the file relative to the game folder
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<CacheEntry> {
+        self.cache.entries.remove(path.as_ref())
+    }
+
+    /// Forget all the cached files and set new game version
+    pub fn clear(&mut self, version: Version) {
+        self.cache = CacheFile {
+            version: Some(version),
+            entries: HashMap::new()
+        };
+    }
+
+    /// Save cache to the file
+    pub fn save(&self) -> std::io::Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+
+        std::fs::write(&temp_path, serde_json::to_vec(&self.cache)?)?;
+        std::fs::rename(temp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_changed_during_hashing() -> std::io::Result<()> {
+        let game_dir = std::env::temp_dir().join(".agc-test-verification-cache");
+
+        if game_dir.exists() {
+            std::fs::remove_dir_all(&game_dir)?;
+        }
+
+        std::fs::create_dir_all(&game_dir)?;
+        std::fs::write(game_dir.join("file"), b"hello")?;
+
+        let file = IntegrityFile {
+            path: PathBuf::from("file"),
+            md5: String::from("5d41402abc4b2a76b9719d911017c592"),
+            size: 5,
+            base_url: String::new()
+        };
+
+        let mut cache = VerificationCache::open(&game_dir, Version::new(1, 0, 0));
+
+        // Metadata is captured before hashing, and the file is replaced while it's hashed
+        let entry = CacheEntry::new(game_dir.join("file"), &file.md5).unwrap();
+
+        std::fs::write(game_dir.join("file.new"), b"world")?;
+        std::fs::rename(game_dir.join("file.new"), game_dir.join("file"))?;
+
+        cache.insert(&file, entry);
+
+        assert!(!cache.is_verified(&game_dir, &file));
+
+        let entry = CacheEntry::new(game_dir.join("file"), &file.md5).unwrap();
+
+        cache.insert(&file, entry);
+
+        assert!(cache.is_verified(&game_dir, &file));
+
+        std::fs::remove_dir_all(game_dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_invalidation() -> std::io::Result<()> {
+        let game_dir = std::env::temp_dir().join(".agc-test-verification-cache-invalidation");
+
+        if game_dir.exists() {
+            std::fs::remove_dir_all(&game_dir)?;
+        }
+
+        std::fs::create_dir_all(&game_dir)?;
+        std::fs::write(game_dir.join("file"), b"hello")?;
+
+        let file = IntegrityFile {
+            path: PathBuf::from("file"),
+            md5: String::from("5d41402abc4b2a76b9719d911017c592"),
+            size: 5,
+            base_url: String::new()
+        };
+
+        let mut cache = VerificationCache::open(&game_dir, Version::new(1, 0, 0));
+
+        cache.insert(&file, CacheEntry::new(game_dir.join("file"), &file.md5).unwrap());
+        cache.save()?;
+
+        // Same version keeps the entries
+        let cache = VerificationCache::open(&game_dir, Version::new(1, 0, 0));
+
+        assert_eq!(cache.len(), 1);
+        assert!(cache.is_verified(&game_dir, &file));
+
+        // Integrity info of the new game version isn't trusted
+        let changed = IntegrityFile {
+            md5: String::from("7d793037a0760186574b0282f2f435e7"),
+            ..file.clone()
+        };
+
+        assert!(!cache.is_verified(&game_dir, &changed));
+
+        // Another version drops the entries
+        let cache = VerificationCache::open(&game_dir, Version::new(1, 1, 0));
+
+        assert!(cache.is_empty());
+        assert_eq!(cache.version(), Some(Version::new(1, 1, 0)));
+        assert!(!cache.is_verified(&game_dir, &file));
+
+        std::fs::remove_dir_all(game_dir)?;
+
+        Ok(())
+    }
+}
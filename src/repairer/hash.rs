@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+
+use md5::{Md5, Digest};
+
+/// Size of the buffer files are read with
+pub const BUFFER_SIZE: usize = 1024 * 1024; // 1 MB
+
+/// Reader which calculates md5 hash of all the data read through it
+/// 
+/// `progress` is called with amount of already hashed bytes after every read chunk
+pub struct HashingReader<R, F> {
+    reader: R,
+    hasher: Md5,
+    hashed: u64,
+    progress: F
+}
+
+impl<R: Read, F: FnMut(u64)> HashingReader<R, F> {
+    #[inline]
+    pub fn new(reader: R, progress: F) -> Self {
+        Self {
+            reader,
+            hasher: Md5::new(),
+            hashed: 0,
+            progress
+        }
+    }
+
+    #[inline]
+    /// Get amount of already hashed bytes
+    pub fn hashed(&self) -> u64 {
+        self.hashed
+    }
+
+    #[inline]
+    /// Get hash of the read data as a lowercase hex string
+    pub fn finalize(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<R: Read, F: FnMut(u64)> Read for HashingReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        if read > 0 {
+            self.hasher.update(&buf[..read]);
+
+            self.hashed += read as u64;
+
+            (self.progress)(self.hashed);
+        }
+
+        Ok(read)
+    }
+}
+
+/// Calculate md5 hash of the data without loading it into memory
+/// 
+/// `progress` is called with amount of already hashed bytes after every read chunk.
+/// Return hash as a lowercase hex string
+pub fn md5_reader(reader: impl Read, progress: impl FnMut(u64)) -> std::io::Result<String> {
+    let mut reader = HashingReader::new(reader, progress);
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => (),
+
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(reader.finalize())
+}
+
+#[inline]
+/// Calculate md5 hash of the file
+/// 
+/// Return hash as a lowercase hex string
+pub fn md5_file(path: impl AsRef<Path>) -> std::io::Result<String> {
+    md5_reader(File::open(path)?, |_| {})
+}
+
+/// Calculate md5 hash of the file, reporting `(hashed bytes, file size)` progress
+/// 
+/// Return hash as a lowercase hex string
+pub fn md5_file_with_progress(path: impl AsRef<Path>, progress: impl Fn(u64, u64)) -> std::io::Result<String> {
+    let file = File::open(path)?;
+    let total = file.metadata()?.len();
+
+    md5_reader(file, |hashed| (progress)(hashed, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_md5_reader() -> std::io::Result<()> {
+        assert_eq!(md5_reader(&b""[..], |_| {})?, "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_reader(&b"hello"[..], |_| {})?, "5d41402abc4b2a76b9719d911017c592");
+
+        // Data larger than the buffer
+        let data = vec![0u8; BUFFER_SIZE * 2 + 1];
+
+        let mut hashed = 0;
+
+        assert_eq!(md5_reader(data.as_slice(), |curr| hashed = curr)?, format!("{:x}", Md5::digest(&data)));
+        assert_eq!(hashed, data.len() as u64);
+
+        Ok(())
+    }
+}
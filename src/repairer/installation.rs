@@ -0,0 +1,236 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use super::IntegrityFile;
+use super::verify::{verify_report, VerifyOptions, VerifyUpdate};
+use super::report::VerificationReport;
+use super::repair::{repair_all_from, RepairUpdate, RepairSummary, RepairFailure};
+use super::source::RepairSource;
+use super::skip_rules::SkipRules;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstallationUpdate {
+    /// Files of all the components are being verified
+    Verifying(VerifyUpdate),
+
+    /// Broken files of all the components are being repaired
+    Repairing(RepairUpdate)
+}
+
+/// Verification and repairing results of the installation's component
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentReport<C> {
+    /// Component of the installation, e.g. the game itself or its voice package
+    pub component: C,
+
+    pub verification: VerificationReport,
+    pub repair: RepairSummary
+}
+
+impl<C> ComponentReport<C> {
+    #[inline]
+    /// Check if the component has no broken files left
+    pub fn is_ok(&self) -> bool {
+        self.repair.is_ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallationReport<C> {
+    pub components: Vec<ComponentReport<C>>
+}
+
+impl<C> InstallationReport<C> {
+    #[inline]
+    /// Check if all the components have no broken files left
+    pub fn is_ok(&self) -> bool {
+        self.components.iter().all(ComponentReport::is_ok)
+    }
+
+    #[inline]
+    /// Get files which couldn't be repaired
+    pub fn failed(&self) -> impl Iterator<Item = &RepairFailure> {
+        self.components.iter().flat_map(|component| component.repair.failed.iter())
+    }
+}
+
+/// Verify files of all the installation's components together and repair the broken ones
+/// 
+/// Extraneous files are reported by the first component.
+/// Both verification and repairing use `options.threads` workers
+#[tracing::instrument(level = "debug", skip(components, skip_rules, sources, updater))]
+pub fn repair_components<C>(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    components: Vec<(C, Vec<IntegrityFile>)>,
+    skip_rules: &SkipRules,
+    options: VerifyOptions,
+    sources: Vec<Box<dyn RepairSource>>,
+    updater: impl Fn(InstallationUpdate)
+) -> anyhow::Result<InstallationReport<C>> {
+    let game_dir = game_dir.as_ref();
+
+    let mut owners = HashMap::<PathBuf, usize>::new();
+    let mut files = Vec::new();
+
+    let mut components = components.into_iter()
+        .enumerate()
+        .map(|(i, (component, component_files))| {
+            for file in component_files {
+                owners.insert(file.path.clone(), i);
+                files.push(file);
+            }
+
+            ComponentReport {
+                component,
+                verification: VerificationReport::default(),
+                repair: RepairSummary::default()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let owner = |path: &Path| owners.get(path).copied().unwrap_or(0);
+
+    let mut report = verify_report(game_dir, files, options, |update| {
+        (updater)(InstallationUpdate::Verifying(update));
+    });
+
+    report.find_extraneous(game_dir, skip_rules)?;
+
+    let broken = report.broken()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let VerificationReport { ok, missing, size_mismatch, hash_mismatch, extraneous } = report;
+
+    for file in ok.into_iter().chain(missing).chain(size_mismatch).chain(hash_mismatch) {
+        if let Some(component) = components.get_mut(owner(&file.file.path)) {
+            component.verification.push(file);
+        }
+    }
+
+    if let Some(component) = components.first_mut() {
+        component.verification.extraneous = extraneous;
+    }
+
+    tracing::debug!("Found {} broken files", broken.len());
+
+    if !broken.is_empty() {
+        let summary = repair_all_from(game_dir, broken, sources, options.threads, |update| {
+            (updater)(InstallationUpdate::Repairing(update));
+        });
+
+        for file in summary.repaired {
+            if let Some(component) = components.get_mut(owner(&file.path)) {
+                component.repair.repaired.push(file);
+            }
+        }
+
+        for failure in summary.failed {
+            if let Some(component) = components.get_mut(owner(&failure.file.path)) {
+                component.repair.failed.push(failure);
+            }
+        }
+    }
+
+    Ok(InstallationReport {
+        components
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::hash::md5_reader;
+    use super::super::report::ExtraneousFile;
+    use super::super::source::DirectorySource;
+
+    fn integrity_file(path: &str, content: &str) -> std::io::Result<IntegrityFile> {
+        Ok(IntegrityFile {
+            path: PathBuf::from(path),
+            md5: md5_reader(content.as_bytes(), |_| {})?,
+            size: content.len() as u64,
+            base_url: String::new()
+        })
+    }
+
+    #[test]
+    pub fn test_repair_components() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-repair-components");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        for folder in ["game/data", "game/voice", "game/ScreenShot", "source/data", "source/voice"] {
+            std::fs::create_dir_all(path.join(folder))?;
+        }
+
+        std::fs::write(path.join("game/data/ok"), "ok")?;
+        std::fs::write(path.join("game/data/broken"), "BROKEN")?;
+        std::fs::write(path.join("game/voice/broken"), "VOICE")?;
+        std::fs::write(path.join("game/extra"), "extra")?;
+        std::fs::write(path.join("game/ScreenShot/image.png"), "image")?;
+
+        std::fs::write(path.join("source/data/broken"), "broken")?;
+        std::fs::write(path.join("source/voice/broken"), "voice")?;
+
+        let components = vec![
+            ("game", vec![
+                integrity_file("data/ok", "ok")?,
+                integrity_file("data/broken", "broken")?
+            ]),
+            ("voice", vec![
+                integrity_file("voice/broken", "voice")?,
+                integrity_file("voice/missing", "missing")?
+            ])
+        ];
+
+        let report = repair_components(
+            path.join("game"),
+            components,
+            &SkipRules::from_globs(&["ScreenShot"]),
+            VerifyOptions::default().with_threads(2),
+            vec![Box::new(DirectorySource::new(path.join("source")))],
+            |_| {}
+        )?;
+
+        let paths = |files: &[IntegrityFile]| files.iter()
+            .map(|file| file.path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        let [game, voice] = report.components.as_slice() else {
+            panic!("Wrong amount of components: {}", report.components.len());
+        };
+
+        assert_eq!(game.component, "game");
+        assert_eq!(game.verification.checked(), 2);
+        assert_eq!(game.verification.hash_mismatch.len(), 1);
+        assert_eq!(paths(&game.repair.repaired), ["data/broken"]);
+        assert!(game.is_ok());
+
+        // Extraneous files are reported only by the first component
+        assert_eq!(game.verification.extraneous, vec![ExtraneousFile {
+            path: PathBuf::from("extra"),
+            size: 5
+        }]);
+
+        assert_eq!(voice.component, "voice");
+        assert_eq!(voice.verification.checked(), 2);
+        assert_eq!(voice.verification.missing.len(), 1);
+        assert_eq!(paths(&voice.repair.repaired), ["voice/broken"]);
+        assert!(voice.verification.extraneous.is_empty());
+        assert!(!voice.is_ok());
+
+        assert!(!report.is_ok());
+        assert_eq!(report.failed().map(|failure| failure.file.path.clone()).collect::<Vec<_>>(), [PathBuf::from("voice/missing")]);
+
+        assert_eq!(std::fs::read_to_string(path.join("game/data/broken"))?, "broken");
+        assert_eq!(std::fs::read_to_string(path.join("game/voice/broken"))?, "voice");
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
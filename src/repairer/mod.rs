@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashSet;
+
+use super::installer::downloader::{Downloader, DownloadingError};
+
+pub mod hash;
+pub mod verify;
+pub mod cache;
+pub mod report;
+pub mod repair;
+pub mod quarantine;
+pub mod skip_rules;
+pub mod pkg_version;
+pub mod source;
+pub mod installation;
+
+pub use verify::{
+    verify_all,
+    verify_all_cached,
+    verify_report,
+    verify_report_cached,
+    VerifyMode,
+    VerifyOptions,
+    VerifyUpdate
+};
+
+pub use cache::VerificationCache;
+
+pub use report::{
+    VerificationReport,
+    FileReport,
+    FileStatus,
+    ExtraneousFile
+};
+
+pub use repair::{
+    repair_all,
+    repair_all_from,
+    RepairUpdate,
+    RepairFailure,
+    RepairSummary
+};
+
+pub use quarantine::Quarantine;
+pub use skip_rules::{SkipRule, SkipRules};
+pub use pkg_version::{PkgVersion, PkgVersionEntry, PkgVersionError};
+
+pub use source::{
+    RepairSource,
+    CdnSource,
+    DirectorySource,
+    InstallationSource,
+    ArchiveSource
+};
+
+pub use installation::{
+    InstallationUpdate,
+    InstallationReport,
+    ComponentReport
+};
+
+/// Files and folders of the game root created by the repairer
+/// 
+/// They're not the game files, so they're never listed as unused or packed
+pub(crate) const REPAIRER_FILES: &[&str] = &[
+    cache::CACHE_FILE_NAME,
+    cache::CACHE_TEMP_FILE_NAME,
+    quarantine::QUARANTINE_FOLDER_NAME
+];
+
+/// Suffixes of temporary files created by the repairer next to the game files
+/// 
+/// They can be left in any game subfolder if the repairing was interrupted
+pub(crate) const REPAIRER_TEMP_SUFFIXES: &[&str] = &[
+    ".archive",
+    ".repair"
+];
+
+/// Check if the file is a temporary file left by the repairer
+pub(crate) fn is_repairer_temp_file(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+
+    REPAIRER_TEMP_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+// {"remoteName": "UnityPlayer.dll", "md5": "8c8c3d845b957e4cb84c662bed44d072", "fileSize": 33466104}
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityFile {
+    pub path: PathBuf,
+    pub md5: String,
+    pub size: u64,
+    pub base_url: String
+}
+
+impl IntegrityFile {
+    /// Compare files' sizes and (if needed) hashes
+    #[inline]
+    pub fn verify<T: Into<PathBuf> + std::fmt::Debug>(&self, game_path: T) -> bool {
+        self.verify_with_progress(game_path, |_, _| {})
+    }
+
+    /// Compare files' sizes and (if needed) hashes, reporting `(hashed bytes, file size)` progress
+    /// 
+    /// File is hashed by chunks so it's never loaded into memory entirely
+    #[tracing::instrument(level = "trace", ret, skip(progress))]
+    pub fn verify_with_progress<T: Into<PathBuf> + std::fmt::Debug>(&self, game_path: T, progress: impl Fn(u64, u64)) -> bool {
+        tracing::trace!("Verifying file");
+
+        let file_path: PathBuf = game_path.into().join(&self.path);
+
+        // Get file metadata, or return false if it's unavailable
+        let Ok(metadata) = file_path.metadata() else {
+            return false;
+        };
+
+        // Immediately return false if sizes aren't equal
+        if metadata.len() != self.size {
+            false
+        }
+
+        // Otherwise compare file hashes
+        else {
+            tracing::trace!("Comparing hashes");
+
+            match hash::md5_file_with_progress(&file_path, progress) {
+                Ok(hash) => hash.eq_ignore_ascii_case(&self.md5),
+                Err(_) => false
+            }
+        }
+    }
+
+    /// Compare file with its expected size and (if needed) hash, reporting the found difference
+    /// 
+    /// `VerifyMode::Fast` compares only sizes
+    pub fn check(&self, game_path: impl AsRef<Path>, mode: VerifyMode) -> FileReport {
+        let file_path = game_path.as_ref().join(&self.path);
+
+        let report = |status, actual_size, actual_md5| FileReport {
+            file: self.clone(),
+            status,
+            actual_size,
+            actual_md5
+        };
+
+        let Ok(metadata) = file_path.metadata() else {
+            return report(FileStatus::Missing, None, None);
+        };
+
+        let size = metadata.len();
+
+        if size != self.size {
+            return report(FileStatus::SizeMismatch, Some(size), None);
+        }
+
+        if mode == VerifyMode::Fast {
+            return report(FileStatus::Ok, Some(size), None);
+        }
+
+        match hash::md5_file(&file_path) {
+            Ok(hash) if hash.eq_ignore_ascii_case(&self.md5) => report(FileStatus::Ok, Some(size), Some(hash)),
+            Ok(hash) => report(FileStatus::HashMismatch, Some(size), Some(hash)),
+
+            Err(err) => {
+                tracing::warn!("Failed to hash file {:?}: {err}", file_path);
+
+                report(FileStatus::HashMismatch, Some(size), None)
+            }
+        }
+    }
+
+    /// Compare files' sizes and do not compare files' hashes. Works lots faster than `verify`
+    #[tracing::instrument(level = "trace", ret)]
+    pub fn fast_verify<T: Into<PathBuf> + std::fmt::Debug>(&self, game_path: T) -> bool {
+        tracing::trace!("Verifying file");
+
+        std::fs::metadata(game_path.into().join(&self.path))
+            .and_then(|metadata| Ok(metadata.len() == self.size))
+            .unwrap_or(false)
+    }
+
+    /// Replace remote file with the latest one
+    /// 
+    /// This method doesn't compare them, so you should do it manually
+    #[inline]
+    pub fn repair<T: Into<PathBuf> + std::fmt::Debug>(&self, game_path: T) -> Result<(), DownloadingError> {
+        self.repair_with_progress(game_path, |_, _| {})
+    }
+
+    /// Replace remote file with the latest one, reporting `(downloaded bytes, file size)` progress
+    /// 
+    /// This method doesn't compare them, so you should do it manually
+    #[tracing::instrument(level = "debug", ret, skip(progress))]
+    pub fn repair_with_progress<T: Into<PathBuf> + std::fmt::Debug>(&self, game_path: T, progress: impl Fn(u64, u64) + Send + 'static) -> Result<(), DownloadingError> {
+        tracing::debug!("Repairing file");
+
+        let mut downloader = Downloader::new(format!("{}/{}", self.base_url, self.path.to_string_lossy()))?;
+
+        // Obviously re-download file entirely
+        downloader.continue_downloading = false;
+
+        downloader.download(game_path.into().join(&self.path), progress)
+    }
+
+    /// Replace the file with a copy from the first source which has it
+    /// 
+    /// This method doesn't compare them, so you should do it manually.
+    /// Return name of the used source
+    #[tracing::instrument(level = "debug", skip(sources))]
+    pub fn repair_from(&self, game_path: impl AsRef<Path> + std::fmt::Debug, sources: &[Box<dyn RepairSource>]) -> anyhow::Result<String> {
+        tracing::debug!("Repairing file");
+
+        source::fetch_file(sources, self, &game_path.as_ref().join(&self.path), |_, _| {})
+    }
+}
+
+/// Calculate difference between actual files stored in `game_dir`, and files listed in `used_files`
+/// 
+/// Returned difference will contain files that are not used by the game and should (or just can) be deleted
+/// 
+/// `used_files` can be both absolute and relative to `game_dir`.
+/// Files and folders matching `skip_rules` are not listed.
+/// Quarantine folder and verification cache files of the game root,
+/// as well as temporary files left by the repairer, are never listed
+pub fn try_get_unused_files<T, F>(game_dir: T, used_files: F, skip_rules: &SkipRules) -> anyhow::Result<Vec<PathBuf>>
+where
+    T: Into<PathBuf>,
+    F: IntoIterator<Item = PathBuf>
+{
+    fn list_files(game_dir: &Path, path: PathBuf, skip_rules: &SkipRules) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in std::fs::read_dir(&path)? {
+            let entry = entry?;
+            let entry_path = path.join(entry.file_name());
+
+            // Skip files created by the repairer itself
+            let mut should_skip = path == game_dir &&
+                REPAIRER_FILES.iter().any(|name| entry.file_name() == *name);
+
+            should_skip |= entry.file_type()?.is_file() && is_repairer_temp_file(&entry.file_name());
+
+            if let Ok(relative_path) = entry_path.strip_prefix(game_dir) {
+                should_skip |= skip_rules.is_match(relative_path);
+            }
+
+            if !should_skip {
+                if entry.file_type()?.is_dir() {
+                    files.append(&mut list_files(game_dir, entry_path, skip_rules)?);
+                }
+    
+                else {
+                    files.push(entry_path);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    let used_files = used_files.into_iter()
+        .map(|path| path.into())
+        .collect::<HashSet<PathBuf>>();
+
+    let game_dir = game_dir.into();
+
+    Ok(list_files(&game_dir, game_dir.clone(), skip_rules)?
+        .into_iter()
+        .filter(move |path| {
+            // File persist in used_files => unused
+            if used_files.contains(path) {
+                return false;
+            }
+
+            // File persist in used_files => unused
+            if let Ok(path) = path.strip_prefix(&game_dir) {
+                if used_files.contains(path) {
+                    return false;
+                }
+            }
+
+            // File not persist in used_files => not unused
+            return true;
+        })
+        .collect())
+}
@@ -0,0 +1,589 @@
+use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use serde_json::value::RawValue;
+
+use super::IntegrityFile;
+use super::skip_rules::{SkipRule, SkipRules};
+
+/// Name of the game files manifest
+pub const PKG_VERSION_NAME: &str = "pkg_version";
+
+/// Skip rules for files which are never listed in the `pkg_version` manifests:
+/// manifests themselves and `.version` files written by this library
+pub fn manifest_skip_rules() -> SkipRules {
+    SkipRules::from_rules([
+        SkipRule::glob(PKG_VERSION_NAME),
+        SkipRule::glob("Audio_*_pkg_version"),
+        SkipRule::glob(".version")
+    ]).expect("Failed to compile skip rules")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+#[error("Failed to parse pkg_version line {line}: {message}")]
+pub struct PkgVersionError {
+    /// Line number, starting from 1
+    pub line: usize,
+
+    /// Content of the line
+    pub content: String,
+
+    pub message: String
+}
+
+/// Entry of the `pkg_version` file
+/// 
+/// `{"remoteName": "UnityPlayer.dll", "md5": "8c8c3d845b957e4cb84c662bed44d072", "fileSize": 33466104}`
+/// 
+/// Entries are compared by their fields, not by their original formatting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PkgVersionEntry {
+    /// Path relative to the game folder
+    pub remote_name: String,
+
+    pub md5: String,
+    pub file_size: u64,
+
+    /// Other fields of the entry with their raw JSON values, in their original order
+    pub extra: Vec<(String, String)>,
+
+    /// Original order of all the entry's fields
+    /// 
+    /// Used to write the entry the same way it was read
+    #[serde(skip)]
+    order: Vec<String>,
+
+    /// Original line of the parsed entry
+    /// 
+    /// Written instead of the formatted entry if the entry wasn't changed
+    #[serde(skip)]
+    line: Option<String>
+}
+
+impl PkgVersionEntry {
+    #[inline]
+    pub fn new(remote_name: impl ToString, md5: impl ToString, file_size: u64) -> Self {
+        Self {
+            remote_name: remote_name.to_string(),
+            md5: md5.to_string(),
+            file_size,
+            extra: Vec::new(),
+            order: Vec::new(),
+            line: None
+        }
+    }
+
+    /// Parse entry from the `pkg_version` line
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let Fields(fields) = serde_json::from_str::<Fields>(line)
+            .map_err(|err| err.to_string())?;
+
+        let mut remote_name = None;
+        let mut md5 = None;
+        let mut file_size = None;
+
+        let mut extra = Vec::new();
+        let mut order = Vec::with_capacity(fields.len());
+
+        for (key, value) in fields {
+            match key.as_str() {
+                "remoteName" => remote_name = Some(serde_json::from_str::<String>(value.get()).map_err(|_| "remoteName is not a string")?),
+                "md5" => md5 = Some(serde_json::from_str::<String>(value.get()).map_err(|_| "md5 is not a string")?),
+                "fileSize" => file_size = Some(serde_json::from_str::<u64>(value.get()).map_err(|_| "fileSize is not an unsigned integer")?),
+
+                _ => extra.push((key.clone(), value.get().to_string()))
+            }
+
+            order.push(key);
+        }
+
+        Ok(Self {
+            remote_name: remote_name.ok_or("remoteName is missing")?,
+            md5: md5.ok_or("md5 is missing")?,
+            file_size: file_size.ok_or("fileSize is missing")?,
+            extra,
+            order,
+            line: Some(line.to_string())
+        })
+    }
+
+    #[inline]
+    /// Get extra field's raw JSON value
+    pub fn get_raw(&self, key: &str) -> Option<&str> {
+        self.extra.iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[inline]
+    /// Get extra field's value
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.get_raw(key).and_then(|value| serde_json::from_str(value).ok())
+    }
+
+    /// Set extra field's value, keeping its position if it already exists
+    pub fn set(&mut self, key: impl ToString, value: &Value) {
+        let key = key.to_string();
+        let value = value.to_string();
+
+        match self.extra.iter_mut().find(|(name, _)| name == &key) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.extra.push((key, value))
+        }
+    }
+
+    /// Get `pkg_version` line of the entry, without line ending
+    /// 
+    /// Unchanged parsed entries are written exactly as they were read.
+    /// Changed ones keep their fields order, new ones are written
+    /// as `remoteName`, `md5`, `fileSize` and then extra fields
+    pub fn to_line(&self) -> String {
+        if let Some(line) = &self.line {
+            if Self::parse(line).as_ref() == Ok(self) {
+                return line.clone();
+            }
+        }
+
+        let mut fields = Vec::with_capacity(self.extra.len() + 3);
+
+        let mut keys = self.order.iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        for key in ["remoteName", "md5", "fileSize"].into_iter().chain(self.extra.iter().map(|(key, _)| key.as_str())) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        for key in keys {
+            let value = match key {
+                "remoteName" => Value::from(self.remote_name.as_str()).to_string(),
+                "md5" => Value::from(self.md5.as_str()).to_string(),
+                "fileSize" => self.file_size.to_string(),
+
+                _ => match self.get_raw(key) {
+                    Some(value) => value.to_string(),
+                    None => continue
+                }
+            };
+
+            fields.push(format!("{}: {value}", Value::from(key)));
+        }
+
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    #[inline]
+    /// Convert entry to the integrity file with given base url
+    pub fn to_integrity_file(&self, base_url: impl ToString) -> IntegrityFile {
+        IntegrityFile {
+            path: PathBuf::from(&self.remote_name),
+            md5: self.md5.clone(),
+            size: self.file_size,
+            base_url: base_url.to_string()
+        }
+    }
+}
+
+impl PartialEq for PkgVersionEntry {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.remote_name == other.remote_name &&
+            self.md5 == other.md5 &&
+            self.file_size == other.file_size &&
+            self.extra == other.extra
+    }
+}
+
+impl Eq for PkgVersionEntry {}
+
+impl From<&IntegrityFile> for PkgVersionEntry {
+    #[inline]
+    fn from(file: &IntegrityFile) -> Self {
+        Self::new(file.path.to_string_lossy(), &file.md5, file.size)
+    }
+}
+
+/// Parsed `pkg_version` file
+/// 
+/// ```
+/// use anime_game_core::repairer::pkg_version::PkgVersion;
+/// 
+/// let content = "{\"remoteName\": \"UnityPlayer.dll\", \"md5\": \"8c8c3d845b957e4cb84c662bed44d072\", \"fileSize\": 33466104}\r\n";
+/// 
+/// let (pkg_version, errors) = PkgVersion::parse_lenient(content);
+/// 
+/// assert!(errors.is_empty());
+/// assert_eq!(pkg_version.entries[0].file_size, 33466104);
+/// assert_eq!(pkg_version.to_string(), content);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PkgVersion {
+    pub entries: Vec<PkgVersionEntry>,
+
+    /// Lines are separated by `\r\n` instead of `\n`
+    pub crlf: bool,
+
+    /// Last line ends with a line separator
+    pub trailing_newline: bool,
+
+    /// Empty and malformed lines with indexes of the entries they precede
+    /// 
+    /// Used to write the file the same way it was read
+    #[serde(skip)]
+    unparsed: Vec<(usize, String)>
+}
+
+impl Default for PkgVersion {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            crlf: true,
+            trailing_newline: true,
+            unparsed: Vec::new()
+        }
+    }
+}
+
+impl PkgVersion {
+    #[inline]
+    pub fn new(entries: impl IntoIterator<Item = PkgVersionEntry>) -> Self {
+        Self {
+            entries: entries.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Parse `pkg_version` content, skipping malformed lines
+    /// 
+    /// Return parsed file and list of the skipped lines errors. Empty lines are ignored.
+    /// Skipped and empty lines are kept and written back in their original places
+    pub fn parse_lenient(content: &str) -> (Self, Vec<PkgVersionError>) {
+        let mut entries = Vec::new();
+        let mut unparsed = Vec::new();
+        let mut errors = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                unparsed.push((entries.len(), line.to_string()));
+
+                continue;
+            }
+
+            match PkgVersionEntry::parse(line) {
+                Ok(entry) => entries.push(entry),
+
+                Err(message) => {
+                    unparsed.push((entries.len(), line.to_string()));
+
+                    errors.push(PkgVersionError {
+                        line: i + 1,
+                        content: line.to_string(),
+                        message
+                    });
+                }
+            }
+        }
+
+        let pkg_version = Self {
+            entries,
+            crlf: content.contains("\r\n"),
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+            unparsed
+        };
+
+        (pkg_version, errors)
+    }
+
+    /// Parse `pkg_version` content, failing on the first malformed line
+    pub fn parse(content: &str) -> Result<Self, PkgVersionError> {
+        let (pkg_version, mut errors) = Self::parse_lenient(content);
+
+        if errors.is_empty() {
+            Ok(pkg_version)
+        }
+
+        else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parse `pkg_version` file, skipping malformed lines
+    /// 
+    /// Invalid UTF-8 characters are replaced
+    #[inline]
+    pub fn read_lenient(path: impl AsRef<Path>) -> std::io::Result<(Self, Vec<PkgVersionError>)> {
+        Ok(Self::parse_lenient(&String::from_utf8_lossy(&std::fs::read(path)?)))
+    }
+
+    /// Write `pkg_version` file
+    #[inline]
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    #[inline]
+    /// Convert entries to the integrity files with given base url
+    pub fn to_integrity_files(&self, base_url: impl AsRef<str>) -> Vec<IntegrityFile> {
+        self.entries.iter()
+            .map(|entry| entry.to_integrity_file(base_url.as_ref()))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for PkgVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line_ending = if self.crlf { "\r\n" } else { "\n" };
+
+        let mut unparsed = self.unparsed.iter().peekable();
+        let mut lines = Vec::with_capacity(self.entries.len() + self.unparsed.len());
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            while let Some((_, line)) = unparsed.next_if(|(index, _)| *index <= i) {
+                lines.push(line.clone());
+            }
+
+            lines.push(entry.to_line());
+        }
+
+        lines.extend(unparsed.map(|(_, line)| line.clone()));
+
+        for (i, line) in lines.iter().enumerate() {
+            f.write_str(line)?;
+
+            if self.trailing_newline || i + 1 < lines.len() {
+                f.write_str(line_ending)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// List files of the game folder relative to it, sorted by their paths
+/// 
+/// Files matching `skip_rules` or `manifest_skip_rules` are not listed
+pub fn list_files(game_dir: impl AsRef<Path>, skip_rules: &SkipRules) -> anyhow::Result<Vec<PathBuf>> {
+    let game_dir = game_dir.as_ref();
+
+    let skip_rules = manifest_skip_rules().merge(skip_rules);
+
+    let mut files = super::try_get_unused_files(game_dir, [], &skip_rules)?
+        .into_iter()
+        .filter_map(|path| path.strip_prefix(game_dir).map(Path::to_path_buf).ok())
+        .collect::<Vec<_>>();
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Hash files of the game folder using multiple threads and make `pkg_version` entries for them
+/// 
+/// `files` must be relative to `game_dir`. Entries are sorted by their paths.
+/// `progress` is called with `(hashed bytes, total bytes)` from the current thread
+#[tracing::instrument(level = "debug", skip(files, progress))]
+pub fn generate_entries(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = PathBuf>,
+    threads: usize,
+    progress: impl Fn(u64, u64)
+) -> anyhow::Result<Vec<PkgVersionEntry>> {
+    let game_dir = game_dir.as_ref().to_path_buf();
+
+    let mut files = files.into_iter()
+        .map(|path| {
+            let size = game_dir.join(&path).metadata()?.len();
+
+            Ok::<_, std::io::Error>((path, size))
+        })
+        .collect::<Result<VecDeque<_>, _>>()?;
+
+    files.make_contiguous().sort();
+
+    let total = files.iter().map(|(_, size)| size).sum::<u64>();
+
+    let queue = Arc::new(Mutex::new(files));
+
+    let (send, recv) = std::sync::mpsc::channel();
+
+    let workers = (0..threads.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let send = send.clone();
+            let game_dir = game_dir.clone();
+
+            std::thread::spawn(move || {
+                loop {
+                    let Some((path, size)) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+
+                    let entry = super::hash::md5_file(game_dir.join(&path))
+                        .map(|md5| PkgVersionEntry::new(path.to_string_lossy(), md5, size))
+                        .map_err(|err| anyhow::anyhow!("Failed to hash file {:?}: {err}", path));
+
+                    if send.send(entry).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    drop(send);
+
+    let mut entries = Vec::new();
+    let mut hashed = 0;
+
+    (progress)(0, total);
+
+    while let Ok(entry) = recv.recv() {
+        let entry = match entry {
+            Ok(entry) => entry,
+
+            Err(err) => {
+                // Stop other workers
+                queue.lock().unwrap().clear();
+
+                return Err(err);
+            }
+        };
+
+        hashed += entry.file_size;
+
+        (progress)(hashed, total);
+
+        entries.push(entry);
+    }
+
+    for worker in workers {
+        if let Err(err) = worker.join() {
+            anyhow::bail!("Hashing worker panicked: {err:?}");
+        }
+    }
+
+    entries.sort_by(|a, b| a.remote_name.cmp(&b.remote_name));
+
+    Ok(entries)
+}
+
+/// JSON object fields with their raw values, in their original order
+struct Fields(Vec<(String, Box<RawValue>)>);
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("JSON object")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = Vec::new();
+
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_roundtrip() {
+        let content = concat!(
+            "{\"remoteName\": \"UnityPlayer.dll\", \"md5\": \"8c8c3d845b957e4cb84c662bed44d072\", \"fileSize\": 33466104}\r\n",
+            "{\"remoteName\": \"Game_Data/level0\", \"fileSize\": 10, \"md5\": \"5d41402abc4b2a76b9719d911017c592\", \"hash\": \"abc\", \"isPatch\": false}\r\n"
+        );
+
+        let pkg_version = PkgVersion::parse(content).unwrap();
+
+        assert_eq!(pkg_version.entries.len(), 2);
+        assert_eq!(pkg_version.entries[1].get("hash"), Some(Value::from("abc")));
+        assert_eq!(pkg_version.to_string(), content);
+
+        let content = content.replace("\r\n", "\n");
+
+        assert_eq!(PkgVersion::parse(content.trim_end()).unwrap().to_string(), content.trim_end());
+    }
+
+    #[test]
+    pub fn test_malformed_lines() {
+        let content = concat!(
+            "{\"remoteName\": \"a\", \"md5\": \"b\", \"fileSize\": 1}\n",
+            "\n",
+            "{\"remoteName\": \"a\", \"md5\": \"b\"}\n",
+            "{\"remoteName\": \"a\", \"md5\": \"b\", \"fileSize\": -1}\n",
+            "not a json\n"
+        );
+
+        let (pkg_version, errors) = PkgVersion::parse_lenient(content);
+
+        assert_eq!(pkg_version.entries.len(), 1);
+        assert_eq!(errors.iter().map(|err| err.line).collect::<Vec<_>>(), [3, 4, 5]);
+
+        assert_eq!(PkgVersion::parse(content).unwrap_err().line, 3);
+        assert_eq!(pkg_version.to_string(), content);
+    }
+
+    #[test]
+    pub fn test_raw_values() {
+        let content = concat!(
+            "{\"remoteName\":\"a\",\"md5\":\"b\",\"fileSize\":1,\"weight\":1.50,\"name\":\"\\u00e9\"}\r\n",
+            "{ \"remoteName\": \"c\", \"md5\": \"d\", \"fileSize\": 2, \"big\": 1e3 }\r\n"
+        );
+
+        let mut pkg_version = PkgVersion::parse(content).unwrap();
+
+        assert_eq!(pkg_version.entries[0].get_raw("weight"), Some("1.50"));
+        assert_eq!(pkg_version.entries[0].get("name"), Some(Value::from("é")));
+        assert_eq!(pkg_version.to_string(), content);
+
+        // Changed entries are formatted again, keeping raw values of other fields
+        pkg_version.entries[1].file_size = 3;
+        pkg_version.entries[1].set("hash", &Value::from("e"));
+
+        assert_eq!(pkg_version.entries[1].to_line(), "{\"remoteName\": \"c\", \"md5\": \"d\", \"fileSize\": 3, \"big\": 1e3, \"hash\": \"e\"}");
+    }
+
+    #[test]
+    pub fn test_new_entries() {
+        let pkg_version = PkgVersion::new([
+            PkgVersionEntry::new("file \"quoted\"", "b", 1)
+        ]);
+
+        assert_eq!(pkg_version.to_string(), "{\"remoteName\": \"file \\\"quoted\\\"\", \"md5\": \"b\", \"fileSize\": 1}\r\n");
+    }
+
+    #[test]
+    pub fn test_entries_equality() {
+        let entry = PkgVersionEntry::parse("{ \"fileSize\": 1, \"md5\": \"b\", \"remoteName\": \"a\" }").unwrap();
+
+        // Formatting of the parsed entry is ignored
+        assert_eq!(entry, PkgVersionEntry::new("a", "b", 1));
+        assert_eq!(entry, PkgVersionEntry::parse("{\"remoteName\": \"a\", \"md5\": \"b\", \"fileSize\": 1}").unwrap());
+
+        assert_ne!(entry, PkgVersionEntry::new("a", "B", 1));
+        assert_ne!(entry, PkgVersionEntry::parse("{\"remoteName\": \"a\", \"md5\": \"b\", \"fileSize\": 1, \"hash\": \"c\"}").unwrap());
+
+        // But the original line is still written only if it's byte-exact
+        assert_eq!(entry.to_line(), "{ \"fileSize\": 1, \"md5\": \"b\", \"remoteName\": \"a\" }");
+    }
+}
@@ -0,0 +1,463 @@
+use std::path::{Path, PathBuf, Component};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+
+use serde::{Serialize, Deserialize};
+
+/// Name of the folder inside the game folder where quarantined files are stored
+pub const QUARANTINE_FOLDER_NAME: &str = ".quarantine";
+
+/// Name of the manifest file stored in every quarantine folder
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Name of the folder inside every quarantine folder where quarantined files are stored
+/// 
+/// Files are kept separately from the manifest, so a game file with the same name can't replace it
+pub const FILES_FOLDER_NAME: &str = "files";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    /// Path relative to the game folder
+    pub path: PathBuf,
+
+    pub size: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    created_at: u64,
+    files: Vec<QuarantinedFile>
+}
+
+/// Files moved away from the game folder which can be restored or purged later
+/// 
+/// Every quarantine is a `.quarantine/<timestamp>` folder inside the game folder.
+/// Files keep their relative layout in its `files` folder, and are listed in its `manifest.json`
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::quarantine::Quarantine;
+/// 
+/// let unused = anime_game_core::repairer::try_get_unused_files("/path/to/game", [], &Default::default()).unwrap();
+/// 
+/// let quarantine = Quarantine::create("/path/to/game", unused).unwrap();
+/// 
+/// // Game doesn't work anymore
+/// quarantine.restore().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantine {
+    game_dir: PathBuf,
+    path: PathBuf,
+    manifest: Manifest
+}
+
+impl Quarantine {
+    /// Move files into a new quarantine folder
+    /// 
+    /// `files` can be both absolute and relative to `game_dir`. Nothing is created
+    /// if some path can't be quarantined. If some file couldn't be moved,
+    /// already moved ones stay in the quarantine
+    #[tracing::instrument(level = "debug", skip(files))]
+    pub fn create(game_dir: impl AsRef<Path> + std::fmt::Debug, files: impl IntoIterator<Item = impl AsRef<Path>>) -> anyhow::Result<Self> {
+        let game_dir = game_dir.as_ref();
+
+        let mut relative_files = Vec::new();
+
+        for file in files {
+            let file = file.as_ref();
+
+            let relative = file.strip_prefix(game_dir).unwrap_or(file);
+
+            if !is_quarantinable(relative) {
+                anyhow::bail!("File {:?} can't be quarantined", file);
+            }
+
+            relative_files.push(relative.to_path_buf());
+        }
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let quarantine_dir = game_dir.join(QUARANTINE_FOLDER_NAME);
+
+        let mut path = quarantine_dir.join(created_at.to_string());
+        let mut i = 1;
+
+        while path.exists() {
+            path = quarantine_dir.join(format!("{created_at}-{i}"));
+
+            i += 1;
+        }
+
+        std::fs::create_dir_all(path.join(FILES_FOLDER_NAME))?;
+
+        let mut quarantine = Self {
+            game_dir: game_dir.to_path_buf(),
+            path,
+            manifest: Manifest {
+                created_at,
+                files: Vec::new()
+            }
+        };
+
+        for relative in relative_files {
+            let source = game_dir.join(&relative);
+
+            let moved = source.metadata()
+                .and_then(|metadata| {
+                    move_file(&source, &quarantine.files_path().join(&relative))?;
+
+                    Ok(metadata.len())
+                });
+
+            match moved {
+                Ok(size) => {
+                    tracing::trace!("Quarantined file {:?}", relative);
+
+                    quarantine.manifest.files.push(QuarantinedFile {
+                        path: relative,
+                        size
+                    });
+                }
+
+                Err(err) => {
+                    quarantine.save()?;
+
+                    anyhow::bail!("Failed to quarantine file {:?}: {err}", source);
+                }
+            }
+        }
+
+        quarantine.save()?;
+
+        tracing::debug!("Quarantined {} files into {:?}", quarantine.manifest.files.len(), quarantine.path);
+
+        Ok(quarantine)
+    }
+
+    /// Open existing quarantine folder
+    pub fn open(game_dir: impl Into<PathBuf>, path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let manifest = serde_json::from_slice(&std::fs::read(path.join(MANIFEST_FILE_NAME))?)?;
+
+        Ok(Self {
+            game_dir: game_dir.into(),
+            path,
+            manifest
+        })
+    }
+
+    /// List quarantines of the game folder, from the oldest to the newest
+    /// 
+    /// Folders without readable manifest are skipped
+    pub fn list(game_dir: impl AsRef<Path>) -> anyhow::Result<Vec<Self>> {
+        let game_dir = game_dir.as_ref();
+        let quarantine_dir = game_dir.join(QUARANTINE_FOLDER_NAME);
+
+        if !quarantine_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut quarantines = Vec::new();
+
+        for entry in std::fs::read_dir(quarantine_dir)? {
+            let entry = entry?;
+
+            match Self::open(game_dir, entry.path()) {
+                Ok(quarantine) => quarantines.push(quarantine),
+                Err(err) => tracing::warn!("Failed to open quarantine {:?}: {err}", entry.path())
+            }
+        }
+
+        quarantines.sort_by(|a, b| {
+            a.manifest.created_at.cmp(&b.manifest.created_at)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        Ok(quarantines)
+    }
+
+    #[inline]
+    /// Get path to the quarantine folder
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    #[inline]
+    /// Get path to the folder with quarantined files
+    pub fn files_path(&self) -> PathBuf {
+        self.path.join(FILES_FOLDER_NAME)
+    }
+
+    #[inline]
+    /// Get UNIX timestamp of the quarantine creation
+    pub fn created_at(&self) -> u64 {
+        self.manifest.created_at
+    }
+
+    #[inline]
+    /// Get quarantined files
+    pub fn files(&self) -> &[QuarantinedFile] {
+        &self.manifest.files
+    }
+
+    #[inline]
+    /// Get total size of the quarantined files
+    pub fn size(&self) -> u64 {
+        self.manifest.files.iter().map(|file| file.size).sum()
+    }
+
+    /// Move quarantined files back to the game folder and remove the quarantine
+    /// 
+    /// Files which were re-created in the game folder since quarantining are not overwritten
+    /// and fail the restoration. Already restored files are removed from the manifest
+    #[tracing::instrument(level = "debug", skip(self), fields(path = ?self.path))]
+    pub fn restore(mut self) -> anyhow::Result<()> {
+        while let Some(file) = self.manifest.files.last() {
+            // Manifest could be edited by hand, so don't trust its paths
+            if !is_quarantinable(&file.path) {
+                self.save()?;
+
+                anyhow::bail!("File {:?} can't be restored", file.path);
+            }
+
+            let target = self.game_dir.join(&file.path);
+
+            if let Err(err) = move_file(&self.files_path().join(&file.path), &target) {
+                self.save()?;
+
+                if err.kind() == std::io::ErrorKind::AlreadyExists {
+                    anyhow::bail!("Can't restore file {:?}: it already exists", target);
+                }
+
+                anyhow::bail!("Failed to restore file {:?}: {err}", target);
+            }
+
+            self.manifest.files.pop();
+        }
+
+        self.purge()
+    }
+
+    /// Permanently delete quarantined files
+    pub fn purge(self) -> anyhow::Result<()> {
+        std::fs::remove_dir_all(&self.path)?;
+
+        // Remove quarantine folder if there's no more quarantines
+        let _ = std::fs::remove_dir(self.game_dir.join(QUARANTINE_FOLDER_NAME));
+
+        Ok(())
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        std::fs::write(self.path.join(MANIFEST_FILE_NAME), serde_json::to_vec_pretty(&self.manifest)?)
+    }
+}
+
+/// Check that the path is relative to the game folder, doesn't leave it
+/// and doesn't point to the quarantine folder itself
+fn is_quarantinable(path: &Path) -> bool {
+    let mut components = path.components().peekable();
+
+    if components.peek().is_none() || path.starts_with(QUARANTINE_FOLDER_NAME) {
+        return false;
+    }
+
+    components.all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Move file creating its parent folders
+/// 
+/// Unlike `std::fs::rename` existing target file is never replaced.
+/// `AlreadyExists` error is returned instead
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match rename_noreplace(from, to) {
+        Ok(()) => Ok(()),
+
+        // Some filesystems (e.g. exFAT) don't support the flag
+        Err(err) if matches!(err.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS | libc::EOPNOTSUPP)) => {
+            rename_placeholder(from, to)
+        }
+
+        Err(err) => Err(err)
+    }
+}
+
+/// Take the target's name by a placeholder first, and then replace it by the moved file
+fn rename_placeholder(from: &Path, to: &Path) -> std::io::Result<()> {
+    let is_dir = from.symlink_metadata()?.is_dir();
+
+    if is_dir {
+        std::fs::create_dir(to)?;
+    } else {
+        std::fs::OpenOptions::new().write(true).create_new(true).open(to)?;
+    }
+
+    if let Err(err) = std::fs::rename(from, to) {
+        #[allow(unused_must_use)] {
+            if is_dir {
+                std::fs::remove_dir(to);
+            } else {
+                std::fs::remove_file(to);
+            }
+        }
+
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Rename the file with `RENAME_NOREPLACE` flag, failing if the target exists
+fn rename_noreplace(from: &Path, to: &Path) -> std::io::Result<()> {
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+
+    let result = unsafe {
+        libc::renameat2(libc::AT_FDCWD, from.as_ptr(), libc::AT_FDCWD, to.as_ptr(), libc::RENAME_NOREPLACE)
+    };
+
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_quarantine() -> anyhow::Result<()> {
+        let game_dir = std::env::temp_dir().join(".agc-test-quarantine");
+
+        if game_dir.exists() {
+            std::fs::remove_dir_all(&game_dir)?;
+        }
+
+        std::fs::create_dir_all(game_dir.join("folder"))?;
+
+        std::fs::write(game_dir.join("file"), "file")?;
+        std::fs::write(game_dir.join("folder/file"), "folder file")?;
+
+        // Game file with the manifest's name doesn't replace the manifest
+        std::fs::write(game_dir.join(MANIFEST_FILE_NAME), "game manifest")?;
+
+        let quarantine = Quarantine::create(&game_dir, [game_dir.join("file"), PathBuf::from("folder/file"), PathBuf::from(MANIFEST_FILE_NAME)])?;
+
+        assert!(!game_dir.join("file").exists());
+        assert!(!game_dir.join("folder/file").exists());
+        assert_eq!(quarantine.size(), 28);
+
+        assert_eq!(Quarantine::list(&game_dir)?, vec![quarantine.clone()]);
+
+        quarantine.restore()?;
+
+        assert_eq!(std::fs::read_to_string(game_dir.join("folder/file"))?, "folder file");
+        assert_eq!(std::fs::read_to_string(game_dir.join(MANIFEST_FILE_NAME))?, "game manifest");
+        assert!(!game_dir.join(QUARANTINE_FOLDER_NAME).exists());
+
+        Quarantine::create(&game_dir, ["file"])?.purge()?;
+
+        assert!(!game_dir.join("file").exists());
+        assert!(Quarantine::list(&game_dir)?.is_empty());
+
+        std::fs::remove_dir_all(game_dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_quarantine_paths() -> anyhow::Result<()> {
+        let game_dir = std::env::temp_dir().join(".agc-test-quarantine-paths");
+
+        if game_dir.exists() {
+            std::fs::remove_dir_all(&game_dir)?;
+        }
+
+        std::fs::create_dir_all(game_dir.join("folder"))?;
+
+        std::fs::write(game_dir.join("file"), "file")?;
+
+        assert!(Quarantine::create(&game_dir, ["folder/../file"]).is_err());
+        assert!(Quarantine::create(&game_dir, ["./file"]).is_err());
+        assert!(Quarantine::create(&game_dir, ["/etc/hostname"]).is_err());
+        assert!(Quarantine::create(&game_dir, [""]).is_err());
+        assert!(Quarantine::create(&game_dir, [QUARANTINE_FOLDER_NAME]).is_err());
+
+        // Rejected paths don't leave empty quarantines
+        assert!(Quarantine::create(&game_dir, ["file", "../file"]).is_err());
+
+        assert!(game_dir.join("file").exists());
+        assert!(!game_dir.join(QUARANTINE_FOLDER_NAME).exists());
+
+        // Re-created files are not overwritten
+        let quarantine = Quarantine::create(&game_dir, ["file"])?;
+
+        std::fs::write(game_dir.join("file"), "new file")?;
+
+        assert!(Quarantine::open(&game_dir, quarantine.path())?.restore().is_err());
+        assert_eq!(std::fs::read_to_string(game_dir.join("file"))?, "new file");
+        assert_eq!(std::fs::read_to_string(quarantine.files_path().join("file"))?, "file");
+
+        // Manifest paths leaving the game folder are rejected
+        let mut tampered = quarantine.clone();
+
+        tampered.manifest.files[0].path = PathBuf::from("../file");
+
+        assert!(tampered.restore().is_err());
+        assert!(quarantine.files_path().join("file").exists());
+
+        std::fs::remove_dir_all(game_dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_move_file() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-quarantine-move");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(path.join("folder"))?;
+
+        std::fs::write(path.join("file"), "file")?;
+        std::fs::write(path.join("folder/file"), "folder file")?;
+        std::fs::write(path.join("existing"), "existing")?;
+
+        for rename in [move_file, rename_placeholder] {
+            let err = rename(&path.join("file"), &path.join("existing")).unwrap_err();
+
+            assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+            assert_eq!(std::fs::read_to_string(path.join("existing"))?, "existing");
+            assert_eq!(std::fs::read_to_string(path.join("file"))?, "file");
+        }
+
+        move_file(&path.join("file"), &path.join("moved/file"))?;
+        rename_placeholder(&path.join("moved/file"), &path.join("file"))?;
+
+        assert_eq!(std::fs::read_to_string(path.join("file"))?, "file");
+
+        // Folders can be moved as well
+        move_file(&path.join("folder"), &path.join("moved/folder"))?;
+        rename_placeholder(&path.join("moved/folder"), &path.join("folder"))?;
+
+        assert_eq!(std::fs::read_to_string(path.join("folder/file"))?, "folder file");
+        assert!(!path.join("moved/folder").exists());
+
+        std::fs::remove_dir_all(path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::path::Path;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::cell::Cell;
+
+use serde::{Serialize, Deserialize};
+
+use super::IntegrityFile;
+use super::source::{RepairSource, CdnSource, fetch_file};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepairUpdate {
+    /// `(repaired files, total files, downloaded bytes, total bytes)`
+    /// 
+    /// Files which failed to be repaired are counted as processed
+    Progress(u64, u64, u64, u64),
+
+    /// File was downloaded and successfully verified
+    FileRepaired(IntegrityFile),
+
+    /// `(file, error message)`
+    FileFailed(IntegrityFile, String)
+}
+
+/// File which couldn't be repaired
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairFailure {
+    pub file: IntegrityFile,
+    pub error: String
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairSummary {
+    pub repaired: Vec<IntegrityFile>,
+    pub failed: Vec<RepairFailure>
+}
+
+impl RepairSummary {
+    #[inline]
+    /// Check if all the files were repaired
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+enum Message {
+    /// `(downloaded bytes delta)`
+    Downloaded(u64),
+
+    /// `(file, sum of the sent downloaded bytes deltas, result)`
+    Finished(IntegrityFile, u64, Result<(), String>)
+}
+
+/// Repair files of the game folder using multiple threads
+/// 
+/// Every file is downloaded from its `base_url` and verified again after downloading.
+/// Updates are sent from the current thread
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::IntegrityFile;
+/// use anime_game_core::repairer::repair::*;
+/// 
+/// let broken: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let summary = repair_all("/path/to/game", broken, 4, |update| {
+///     if let RepairUpdate::Progress(_, _, curr, total) = update {
+///         println!("Downloaded {curr} of {total} bytes");
+///     }
+/// });
+/// 
+/// for failure in summary.failed {
+///     eprintln!("Failed to repair {:?}: {}", failure.file.path, failure.error);
+/// }
+/// ```
+#[inline]
+pub fn repair_all(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    threads: usize,
+    updater: impl Fn(RepairUpdate)
+) -> RepairSummary {
+    repair_all_from(game_dir, files, vec![Box::new(CdnSource::new())], threads, updater)
+}
+
+/// Repair files of the game folder using multiple threads, taking them from the first source which has their copy
+/// 
+/// Sources are tried in the given order, so `CdnSource` should be the last one.
+/// Every file is verified again after repairing. Updates are sent from the current thread
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::IntegrityFile;
+/// use anime_game_core::repairer::repair::*;
+/// use anime_game_core::repairer::source::*;
+/// 
+/// let broken: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let sources: Vec<Box<dyn RepairSource>> = vec![
+///     Box::new(InstallationSource::new("/path/to/another/game")),
+///     Box::new(CdnSource::new())
+/// ];
+/// 
+/// let summary = repair_all_from("/path/to/game", broken, sources, 4, |_| {});
+/// ```
+#[tracing::instrument(level = "debug", skip(files, sources, updater))]
+pub fn repair_all_from(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    sources: Vec<Box<dyn RepairSource>>,
+    threads: usize,
+    updater: impl Fn(RepairUpdate)
+) -> RepairSummary {
+    let game_dir = game_dir.as_ref().to_path_buf();
+
+    let files = files.into_iter().collect::<Vec<_>>();
+
+    for source in &sources {
+        if let Err(err) = source.prepare(&game_dir, &files) {
+            tracing::warn!("Failed to prepare {}: {err}", source.name());
+        }
+    }
+
+    let files = VecDeque::from(files);
+
+    let total_files = files.len() as u64;
+    let total_bytes = files.iter().map(|file| file.size).sum::<u64>();
+
+    let queue = Arc::new(Mutex::new(files));
+    let sources = Arc::new(sources);
+
+    let (send, recv) = std::sync::mpsc::channel();
+
+    let workers = (0..threads.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let send = send.clone();
+            let game_dir = game_dir.clone();
+            let sources = sources.clone();
+
+            std::thread::spawn(move || {
+                loop {
+                    let Some(file) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+
+                    let progress_send = send.clone();
+                    let prev_downloaded = Cell::new(0);
+
+                    // Bytes reported to the main thread, so it can replace them
+                    // by the file's size when the file is processed
+                    let sent = Arc::new(AtomicU64::new(0));
+                    let progress_sent = sent.clone();
+
+                    let output = game_dir.join(&file.path);
+
+                    let result = fetch_file(&sources, &file, &output, move |curr, _| {
+                        let delta = curr.saturating_sub(prev_downloaded.replace(curr));
+
+                        if delta > 0 {
+                            progress_sent.fetch_add(delta, Ordering::Relaxed);
+
+                            let _ = progress_send.send(Message::Downloaded(delta));
+                        }
+                    });
+
+                    let downloaded = sent.load(Ordering::Relaxed);
+
+                    let result = match result {
+                        Ok(source) if file.verify(&game_dir) => {
+                            tracing::debug!("Repaired {:?} from {source}", file.path);
+
+                            Ok(())
+                        }
+
+                        Ok(source) => Err(format!("File is still broken after repairing from {source}")),
+
+                        Err(err) => Err(err.to_string())
+                    };
+
+                    if send.send(Message::Finished(file, downloaded, result)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    drop(send);
+
+    let mut summary = RepairSummary::default();
+
+    let mut repaired = 0;
+    let mut downloaded = 0;
+
+    (updater)(RepairUpdate::Progress(0, total_files, 0, total_bytes));
+
+    while let Ok(message) = recv.recv() {
+        match message {
+            Message::Downloaded(delta) => {
+                downloaded += delta;
+            }
+
+            Message::Finished(file, file_downloaded, result) => {
+                repaired += 1;
+
+                // Count the file as fully processed even if it wasn't downloaded entirely
+                downloaded = downloaded.saturating_sub(file_downloaded) + file.size;
+
+                match result {
+                    Ok(()) => {
+                        (updater)(RepairUpdate::FileRepaired(file.clone()));
+
+                        summary.repaired.push(file);
+                    }
+
+                    Err(error) => {
+                        tracing::error!("Failed to repair file {:?}: {error}", file.path);
+
+                        (updater)(RepairUpdate::FileFailed(file.clone(), error.clone()));
+
+                        summary.failed.push(RepairFailure {
+                            file,
+                            error
+                        });
+                    }
+                }
+            }
+        }
+
+        (updater)(RepairUpdate::Progress(repaired, total_files, downloaded.min(total_bytes), total_bytes));
+    }
+
+    for worker in workers {
+        if let Err(err) = worker.join() {
+            tracing::error!("Repairing worker panicked: {err:?}");
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use super::super::source::DirectorySource;
+
+    #[test]
+    pub fn test_progress() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-repair-progress");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(path.join("source"))?;
+        std::fs::create_dir_all(path.join("game"))?;
+
+        std::fs::write(path.join("source/hello"), "hello")?;
+
+        // Broken file of the expected size which can't be repaired
+        std::fs::write(path.join("game/broken"), "0123456789")?;
+
+        let files = vec![
+            IntegrityFile {
+                path: "hello".into(),
+                md5: String::from("5d41402abc4b2a76b9719d911017c592"),
+                size: 5,
+                base_url: String::new()
+            },
+            IntegrityFile {
+                path: "broken".into(),
+                md5: String::from("00000000000000000000000000000000"),
+                size: 10,
+                base_url: String::new()
+            },
+            IntegrityFile {
+                path: "missing".into(),
+                md5: String::from("00000000000000000000000000000000"),
+                size: 100,
+                base_url: String::new()
+            }
+        ];
+
+        let progress = RefCell::new(Vec::new());
+
+        let summary = repair_all_from(path.join("game"), files, vec![Box::new(DirectorySource::new(path.join("source")))], 2, |update| {
+            if let RepairUpdate::Progress(repaired, total, curr, total_bytes) = update {
+                progress.borrow_mut().push((repaired, total, curr, total_bytes));
+            }
+        });
+
+        assert_eq!(summary.repaired.len(), 1);
+        assert_eq!(summary.failed.len(), 2);
+
+        let progress = progress.into_inner();
+
+        // Failed files are counted as processed
+        assert_eq!(progress.first(), Some(&(0, 3, 0, 115)));
+        assert_eq!(progress.last(), Some(&(3, 3, 115, 115)));
+
+        assert!(progress.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use super::IntegrityFile;
+use super::skip_rules::SkipRules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    SizeMismatch,
+
+    /// File's hash is different or the file couldn't be read
+    HashMismatch
+}
+
+/// Result of the single file verification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileReport {
+    /// Expected file info
+    pub file: IntegrityFile,
+
+    pub status: FileStatus,
+
+    /// Size of the file on the disk
+    pub actual_size: Option<u64>,
+
+    /// Hash of the file on the disk
+    /// 
+    /// `None` if the file wasn't hashed (e.g. in the fast verification mode)
+    pub actual_md5: Option<String>
+}
+
+impl FileReport {
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.status == FileStatus::Ok
+    }
+}
+
+/// File which is stored in the game folder but not listed in the integrity files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtraneousFile {
+    /// Path relative to the game folder
+    pub path: PathBuf,
+
+    pub size: u64
+}
+
+/// Verification results of the game files, grouped by their status
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::{IntegrityFile, SkipRules};
+/// use anime_game_core::repairer::verify::*;
+/// 
+/// let files: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let mut report = verify_report("/path/to/game", files, VerifyOptions::default(), |_| {});
+/// 
+/// report.find_extraneous("/path/to/game", &SkipRules::from_globs(&["ScreenShot"])).unwrap();
+/// 
+/// println!("{}", serde_json::to_string_pretty(&report).unwrap());
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub ok: Vec<FileReport>,
+    pub missing: Vec<FileReport>,
+    pub size_mismatch: Vec<FileReport>,
+    pub hash_mismatch: Vec<FileReport>,
+    pub extraneous: Vec<ExtraneousFile>
+}
+
+impl VerificationReport {
+    /// Add file verification result to the report
+    pub fn push(&mut self, report: FileReport) {
+        match report.status {
+            FileStatus::Ok => self.ok.push(report),
+            FileStatus::Missing => self.missing.push(report),
+            FileStatus::SizeMismatch => self.size_mismatch.push(report),
+            FileStatus::HashMismatch => self.hash_mismatch.push(report)
+        }
+    }
+
+    /// Add results of another report to this one
+    pub fn merge(&mut self, report: VerificationReport) {
+        self.ok.extend(report.ok);
+        self.missing.extend(report.missing);
+        self.size_mismatch.extend(report.size_mismatch);
+        self.hash_mismatch.extend(report.hash_mismatch);
+        self.extraneous.extend(report.extraneous);
+    }
+
+    /// Sort all the files by their paths
+    pub fn sort(&mut self) {
+        for files in [&mut self.ok, &mut self.missing, &mut self.size_mismatch, &mut self.hash_mismatch] {
+            files.sort_by(|a, b| a.file.path.cmp(&b.file.path));
+        }
+
+        self.extraneous.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// Check if there's no files which should be repaired
+    /// 
+    /// Extraneous files are not counted
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.size_mismatch.is_empty() && self.hash_mismatch.is_empty()
+    }
+
+    /// Get amount of verified files
+    #[inline]
+    pub fn checked(&self) -> usize {
+        self.ok.len() + self.missing.len() + self.size_mismatch.len() + self.hash_mismatch.len()
+    }
+
+    /// Iterate over files which should be repaired
+    pub fn broken(&self) -> impl Iterator<Item = &IntegrityFile> {
+        self.missing.iter()
+            .chain(self.size_mismatch.iter())
+            .chain(self.hash_mismatch.iter())
+            .map(|report| &report.file)
+    }
+
+    /// Get files which should be repaired
+    pub fn into_broken(self) -> Vec<IntegrityFile> {
+        self.missing.into_iter()
+            .chain(self.size_mismatch)
+            .chain(self.hash_mismatch)
+            .map(|report| report.file)
+            .collect()
+    }
+
+    /// Find files of the game folder which are not listed in this report
+    /// 
+    /// Files and folders matching `skip_rules` are ignored
+    /// 
+    /// ⚠️ Be aware that the game can create its own files after downloading, so extraneous files may not be really unused
+    pub fn find_extraneous(&mut self, game_dir: impl AsRef<Path>, skip_rules: &SkipRules) -> anyhow::Result<()> {
+        let game_dir = game_dir.as_ref();
+
+        let used_files = self.ok.iter()
+            .chain(self.missing.iter())
+            .chain(self.size_mismatch.iter())
+            .chain(self.hash_mismatch.iter())
+            .map(|report| report.file.path.clone())
+            .collect::<Vec<_>>();
+
+        for path in super::try_get_unused_files(game_dir, used_files, skip_rules)? {
+            let size = path.metadata()
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+
+            let path = path.strip_prefix(game_dir)
+                .map(Path::to_path_buf)
+                .unwrap_or(path);
+
+            self.extraneous.push(ExtraneousFile {
+                path,
+                size
+            });
+        }
+
+        self.extraneous.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SkipRule {
+    /// Glob pattern relative to the game folder
+    /// 
+    /// - `*` matches any characters except `/`
+    /// - `**` matches any amount of folders
+    /// - `?` matches any single character except `/`
+    /// - `[abc]` and `[!abc]` match any (or none) of the listed characters
+    /// 
+    /// Patterns without `/` match file or folder name at any depth,
+    /// so `ScreenShot` is the same as `**/ScreenShot`
+    Glob(String),
+
+    /// Regular expression matching the whole path relative to the game folder
+    /// 
+    /// Path components are always separated by `/`
+    Regex(String)
+}
+
+impl SkipRule {
+    #[inline]
+    pub fn glob(glob: impl ToString) -> Self {
+        Self::Glob(glob.to_string())
+    }
+
+    #[inline]
+    pub fn regex(regex: impl ToString) -> Self {
+        Self::Regex(regex.to_string())
+    }
+
+    /// Compile the rule to an anchored regular expression
+    pub fn compile(&self) -> Result<Regex, regex::Error> {
+        match self {
+            Self::Glob(glob) => Regex::new(&glob_to_regex(glob)),
+            Self::Regex(regex) => Regex::new(&format!("^(?:{regex})$"))
+        }
+    }
+}
+
+/// Set of rules describing files and folders which shouldn't be listed as unused
+/// 
+/// If some folder is matched - all its content is skipped as well
+/// 
+/// ```
+/// use anime_game_core::repairer::skip_rules::*;
+/// 
+/// let rules = SkipRules::new()
+///     .with_rule(SkipRule::glob("ScreenShot"))
+///     .and_then(|rules| rules.with_rule(SkipRule::regex(r"mods/.*\.dll")))
+///     .unwrap();
+/// 
+/// assert!(rules.is_match("ScreenShot"));
+/// assert!(rules.is_match("mods/extra.dll"));
+/// 
+/// assert!(!rules.is_match("MyScreenShotTool.dll"));
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<SkipRule>", into = "Vec<SkipRule>")]
+pub struct SkipRules {
+    rules: Vec<SkipRule>,
+    compiled: Vec<Regex>
+}
+
+impl SkipRules {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile skip rules
+    pub fn from_rules(rules: impl IntoIterator<Item = SkipRule>) -> Result<Self, regex::Error> {
+        let mut skip_rules = Self::new();
+
+        skip_rules.extend(rules)?;
+
+        Ok(skip_rules)
+    }
+
+    /// Compile glob skip rules
+    /// 
+    /// Panics if any of the rules can't be compiled, so should be used only for hardcoded rules
+    pub fn from_globs(globs: &[&str]) -> Self {
+        Self::from_rules(globs.iter().map(SkipRule::glob))
+            .expect("Failed to compile skip rules")
+    }
+
+    /// Add new rule
+    pub fn push(&mut self, rule: SkipRule) -> Result<(), regex::Error> {
+        self.compiled.push(rule.compile()?);
+        self.rules.push(rule);
+
+        Ok(())
+    }
+
+    /// Add new rules
+    pub fn extend(&mut self, rules: impl IntoIterator<Item = SkipRule>) -> Result<(), regex::Error> {
+        for rule in rules {
+            self.push(rule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add new rule
+    pub fn with_rule(mut self, rule: SkipRule) -> Result<Self, regex::Error> {
+        self.push(rule)?;
+
+        Ok(self)
+    }
+
+    /// Add rules of another set
+    pub fn merge(mut self, rules: &SkipRules) -> Self {
+        self.rules.extend(rules.rules.iter().cloned());
+        self.compiled.extend(rules.compiled.iter().cloned());
+
+        self
+    }
+
+    #[inline]
+    pub fn rules(&self) -> &[SkipRule] {
+        &self.rules
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check if the path relative to the game folder matches any of the rules
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.compiled.iter().any(|rule| rule.is_match(&path))
+    }
+}
+
+impl PartialEq for SkipRules {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.rules == other.rules
+    }
+}
+
+impl Eq for SkipRules {}
+
+impl TryFrom<Vec<SkipRule>> for SkipRules {
+    type Error = regex::Error;
+
+    #[inline]
+    fn try_from(rules: Vec<SkipRule>) -> Result<Self, Self::Error> {
+        Self::from_rules(rules)
+    }
+}
+
+impl From<SkipRules> for Vec<SkipRule> {
+    #[inline]
+    fn from(rules: SkipRules) -> Self {
+        rules.rules
+    }
+}
+
+/// Convert glob pattern to an anchored regular expression
+fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.trim_start_matches('/');
+
+    let mut regex = if glob.contains('/') {
+        String::from("^")
+    } else {
+        String::from("^(?:.*/)?")
+    };
+
+    let mut chars = glob.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                // `**/` matches zero or more folders
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+
+                    regex.push_str("(?:.*/)?");
+                }
+
+                else {
+                    regex.push_str(".*");
+                }
+            }
+
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+
+                for char in chars.by_ref() {
+                    if char == ']' && !class.is_empty() && class != "!" {
+                        closed = true;
+
+                        break;
+                    }
+
+                    class.push(char);
+                }
+
+                if closed {
+                    regex.push('[');
+
+                    let class = match class.strip_prefix('!') {
+                        Some(class) => {
+                            regex.push('^');
+
+                            class
+                        }
+
+                        None => class.as_str()
+                    };
+
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                }
+
+                // Unclosed bracket is matched literally
+                else {
+                    regex.push_str(&regex::escape(&format!("[{class}")));
+                }
+            }
+
+            _ => regex.push_str(&regex::escape(&char.to_string()))
+        }
+    }
+
+    regex.push('$');
+
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_globs() -> Result<(), regex::Error> {
+        let rules = SkipRules::from_rules([
+            SkipRule::glob("ScreenShot"),
+            SkipRule::glob("Game_Data/webCaches"),
+            SkipRule::glob("logs/*.log"),
+            SkipRule::glob("**/cache?/**"),
+            SkipRule::glob("[!a]bc.txt")
+        ])?;
+
+        assert!(rules.is_match("ScreenShot"));
+        assert!(rules.is_match("Game_Data/ScreenShot"));
+        assert!(!rules.is_match("MyScreenShotTool.dll"));
+        assert!(!rules.is_match("ScreenShot.png"));
+
+        assert!(rules.is_match("Game_Data/webCaches"));
+        assert!(!rules.is_match("webCaches"));
+        assert!(!rules.is_match("Other_Data/webCaches"));
+
+        assert!(rules.is_match("logs/output.log"));
+        assert!(!rules.is_match("logs/nested/output.log"));
+
+        assert!(rules.is_match("cache1/file"));
+        assert!(rules.is_match("some/folder/cacheA/file"));
+        assert!(!rules.is_match("cache/file"));
+
+        assert!(rules.is_match("xbc.txt"));
+        assert!(!rules.is_match("abc.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_regexes() -> Result<(), regex::Error> {
+        let rules = SkipRules::new()
+            .with_rule(SkipRule::regex(r"mods/[^/]+\.dll"))?;
+
+        assert!(rules.is_match("mods/extra.dll"));
+        assert!(!rules.is_match("game/mods/extra.dll"));
+        assert!(!rules.is_match("mods/extra.dll.bak"));
+
+        assert!(SkipRules::new().with_rule(SkipRule::regex("(")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_serde() -> Result<(), serde_json::Error> {
+        let rules = SkipRules::from_globs(&["ScreenShot"]);
+
+        let json = serde_json::to_string(&rules)?;
+
+        assert_eq!(json, r#"[{"Glob":"ScreenShot"}]"#);
+        assert_eq!(serde_json::from_str::<SkipRules>(&json)?, rules);
+
+        assert!(serde_json::from_str::<SkipRules>(r#"[{"Regex":"("}]"#).is_err());
+
+        Ok(())
+    }
+}
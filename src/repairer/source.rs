@@ -0,0 +1,886 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Serialize, Deserialize};
+use zip::ZipArchive;
+
+use crate::installer::downloader::Downloader;
+use crate::installer::archives::{Archive, Size};
+use crate::installer::free_space;
+
+use super::IntegrityFile;
+use super::cache::VerificationCache;
+use super::pkg_version::PKG_VERSION_NAME;
+
+/// Boxed progress callback, called with `(written bytes, file size)`
+pub type SourceProgress = Box<dyn Fn(u64, u64) + Send + 'static>;
+
+/// Place the game files can be taken from
+pub trait RepairSource: Send + Sync {
+    /// Name of the source used in logs
+    fn name(&self) -> String;
+
+    /// Write a copy of the file to the `output` path
+    /// 
+    /// Return `Ok(false)` if the source doesn't have a copy of the file matching its md5 hash
+    fn fetch(&self, file: &IntegrityFile, output: &Path, progress: SourceProgress) -> anyhow::Result<bool>;
+
+    /// Called once before the files of the `game_dir` are fetched, so the source
+    /// can prepare all their copies at once. Does nothing by default
+    /// 
+    /// Failed preparation isn't fatal: files are fetched one by one then
+    #[allow(unused_variables)]
+    fn prepare(&self, game_dir: &Path, files: &[IntegrityFile]) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Download files from their `base_url`, or from a mirror with the same layout
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CdnSource {
+    /// Url used instead of the file's `base_url`
+    pub base_url: Option<String>
+}
+
+impl CdnSource {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    /// Download files from the given url instead of their `base_url`
+    pub fn with_base_url(mut self, base_url: impl ToString) -> Self {
+        self.base_url = Some(base_url.to_string());
+
+        self
+    }
+}
+
+impl RepairSource for CdnSource {
+    fn name(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => format!("CDN ({base_url})"),
+            None => String::from("CDN")
+        }
+    }
+
+    fn fetch(&self, file: &IntegrityFile, output: &Path, progress: SourceProgress) -> anyhow::Result<bool> {
+        let base_url = self.base_url.as_ref().unwrap_or(&file.base_url);
+
+        let mut downloader = Downloader::new(format!("{base_url}/{}", file.path.to_string_lossy()))?;
+
+        // Obviously re-download file entirely
+        downloader.continue_downloading = false;
+
+        downloader.download(output, progress)?;
+
+        Ok(true)
+    }
+}
+
+/// Copy files from the local folder with the same layout as the game folder, e.g. from a USB copy of the game
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DirectorySource {
+    pub path: PathBuf,
+
+    /// Hardlink files if they can't be reflinked
+    /// 
+    /// Disabled by default because hardlinked files share their content,
+    /// so changing the repaired file changes the source file as well
+    pub hardlinks: bool
+}
+
+impl DirectorySource {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            hardlinks: false
+        }
+    }
+
+    #[inline]
+    /// Hardlink files if they can't be reflinked
+    pub fn with_hardlinks(mut self, hardlinks: bool) -> Self {
+        self.hardlinks = hardlinks;
+
+        self
+    }
+}
+
+impl RepairSource for DirectorySource {
+    #[inline]
+    fn name(&self) -> String {
+        format!("directory {:?}", self.path)
+    }
+
+    fn fetch(&self, file: &IntegrityFile, output: &Path, progress: SourceProgress) -> anyhow::Result<bool> {
+        let source = self.path.join(&file.path);
+
+        if !file.verify(&self.path) {
+            return Ok(false);
+        }
+
+        let method = copy_file(&source, output, self.hardlinks)?;
+
+        tracing::trace!("Copied {:?} using {method:?}", source);
+
+        (progress)(file.size, file.size);
+
+        Ok(true)
+    }
+}
+
+/// Take files from another installation of the same game version
+/// 
+/// Files verified by the installation's cache are not hashed again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallationSource {
+    pub path: PathBuf,
+    pub cache: Option<VerificationCache>,
+
+    /// Hardlink files if they can't be reflinked
+    /// 
+    /// Disabled by default because hardlinked files share their content,
+    /// so updating one installation would change files of another one
+    pub hardlinks: bool
+}
+
+impl InstallationSource {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: None,
+            hardlinks: false
+        }
+    }
+
+    #[inline]
+    /// Trust files verified by the installation's cache
+    /// 
+    /// Cache must be opened for the installation's folder with the game version being repaired,
+    /// e.g. `VerificationCache::open(&path, version)`
+    pub fn with_cache(mut self, cache: VerificationCache) -> Self {
+        self.cache = Some(cache);
+
+        self
+    }
+
+    #[inline]
+    /// Hardlink files if they can't be reflinked
+    pub fn with_hardlinks(mut self, hardlinks: bool) -> Self {
+        self.hardlinks = hardlinks;
+
+        self
+    }
+}
+
+impl RepairSource for InstallationSource {
+    #[inline]
+    fn name(&self) -> String {
+        format!("installation {:?}", self.path)
+    }
+
+    fn fetch(&self, file: &IntegrityFile, output: &Path, progress: SourceProgress) -> anyhow::Result<bool> {
+        let cached = self.cache.as_ref()
+            .map(|cache| cache.is_verified(&self.path, file))
+            .unwrap_or(false);
+
+        if !cached && !file.verify(&self.path) {
+            return Ok(false);
+        }
+
+        let source = self.path.join(&file.path);
+        let method = copy_file(&source, output, self.hardlinks)?;
+
+        tracing::trace!("Copied {:?} using {method:?}", source);
+
+        (progress)(file.size, file.size);
+
+        Ok(true)
+    }
+}
+
+/// Extract files from the full game archive, e.g. from the one which was already downloaded for installation
+/// 
+/// Only entries matching files' md5 hashes are extracted. Multipart archives are supported
+/// 
+/// Zip archives are opened once and their entries are read by all the repairing threads at once.
+/// Tar archives have no index, so all the requested entries are extracted in a single pass
+/// by `prepare`, next to the files they repair. Their copies are stored with `.archive` suffix
+/// and are not listed as extraneous files if they're left after an interrupted repairing
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::IntegrityFile;
+/// use anime_game_core::repairer::repair::*;
+/// use anime_game_core::repairer::source::*;
+/// 
+/// let broken: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let sources: Vec<Box<dyn RepairSource>> = vec![
+///     Box::new(ArchiveSource::open("/path/to/game.zip.001").unwrap())
+/// ];
+/// 
+/// let summary = repair_all_from("/path/to/game", broken, sources, 4, |_| {});
+/// ```
+#[derive(Debug)]
+pub struct ArchiveSource {
+    path: PathBuf,
+
+    /// Path of the game folder inside the archive
+    prefix: String,
+
+    /// Archive entries with their uncompressed sizes, if known
+    entries: HashMap<String, Option<u64>>,
+
+    /// Zip archive opened once, its clones share the central directory
+    zip: Option<ZipArchive<SharedFile>>,
+
+    /// Tar archive entries read by `prepare`, with paths of their copies
+    /// 
+    /// `None` if the entry's hash doesn't match the file's one
+    extracted: Mutex<HashMap<String, Option<PathBuf>>>
+}
+
+impl ArchiveSource {
+    /// Open the archive and list its entries
+    /// 
+    /// If the game files are stored in a subfolder of the archive -
+    /// it's found by the `pkg_version` file location
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let archive = Archive::open(&path)?;
+
+        let zip = match archive {
+            Archive::Zip(_, _) => Some(ZipArchive::new(SharedFile::open(&path)?)?),
+            _ => None
+        };
+
+        let entries = archive.entries()?
+            .map(|entry| {
+                let entry = entry?;
+
+                let size = match entry.size {
+                    Size::Uncompressed(size) => Some(size),
+                    Size::Both { uncompressed, .. } => Some(uncompressed),
+
+                    // Tar archives store only the entry's size, but it's not worth to rely on it
+                    Size::Compressed(_) => None
+                };
+
+                Ok((entry.name.replace('\\', "/"), size))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        let prefix = entries.keys()
+            .filter_map(|name| name.strip_suffix(PKG_VERSION_NAME))
+            .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+            .min_by_key(|prefix| prefix.len())
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(Self {
+            path,
+            prefix,
+            entries,
+            zip,
+            extracted: Mutex::new(HashMap::new())
+        })
+    }
+
+    #[inline]
+    /// Specify path of the game folder inside the archive
+    pub fn with_prefix(mut self, prefix: impl AsRef<str>) -> Self {
+        let prefix = prefix.as_ref().trim_matches('/');
+
+        self.prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{prefix}/")
+        };
+
+        self
+    }
+
+    #[inline]
+    /// Get path to the archive
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Get name of the archive entry of the file
+    pub fn entry_name(&self, file: &IntegrityFile) -> String {
+        let path = file.path.to_string_lossy();
+
+        format!("{}{}", self.prefix, path.trim_start_matches('/'))
+    }
+
+    /// Check if the archive has an entry which can be a copy of the file
+    fn has_entry(&self, file: &IntegrityFile) -> bool {
+        match self.entries.get(&self.entry_name(file)) {
+            None => false,
+            Some(Some(size)) => *size == file.size,
+            Some(None) => true
+        }
+    }
+}
+
+impl RepairSource for ArchiveSource {
+    #[inline]
+    fn name(&self) -> String {
+        format!("archive {:?}", self.path)
+    }
+
+    fn prepare(&self, game_dir: &Path, files: &[IntegrityFile]) -> anyhow::Result<()> {
+        let archive = Archive::open(&self.path)?;
+
+        // Zip entries are read by their index, and 7z ones can't be read in a single pass
+        if !matches!(archive, Archive::Tar(..) | Archive::TarXz(..) | Archive::TarGz(..) | Archive::TarBz2(..) | Archive::TarZst(..)) {
+            return Ok(());
+        }
+
+        let mut requested = files.iter()
+            .filter(|file| self.has_entry(file))
+            .map(|file| (self.entry_name(file), file))
+            .collect::<HashMap<_, _>>();
+
+        if requested.is_empty() {
+            return Ok(());
+        }
+
+        // Entries are extracted before any source is tried, so they must fit the disk at once
+        let required = requested.values().map(|file| file.size).sum::<u64>();
+
+        if let Some(available) = free_space::unreserved(game_dir) {
+            if available < required {
+                anyhow::bail!("Not enough free space to extract {} entries: required {required} bytes, available {available}", requested.len());
+            }
+        }
+
+        tracing::debug!("Extracting {} entries of {:?}", requested.len(), self.path);
+
+        let mut extracted = self.extracted.lock().unwrap();
+
+        archive.for_each_entry(|entry, reader| {
+            let name = entry.name.replace('\\', "/");
+
+            let Some(file) = requested.remove(&name) else {
+                return Ok(());
+            };
+
+            let output = game_dir.join(&file.path);
+
+            let mut temp_name = output.file_name().unwrap_or_default().to_os_string();
+
+            temp_name.push(".archive");
+
+            let temp = output.with_file_name(temp_name);
+
+            if let Some(parent) = temp.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            if write_verified(reader, &temp, file, |_| {})? {
+                extracted.insert(name, Some(temp));
+            } else {
+                tracing::debug!("Archive entry {name} has different hash, expected {}", file.md5);
+
+                extracted.insert(name, None);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn fetch(&self, file: &IntegrityFile, output: &Path, progress: SourceProgress) -> anyhow::Result<bool> {
+        if !self.has_entry(file) {
+            return Ok(false);
+        }
+
+        let name = self.entry_name(file);
+
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Entry was already read by `prepare`
+        let extracted = self.extracted.lock().unwrap().remove(&name);
+
+        match extracted {
+            Some(Some(extracted)) => {
+                if std::fs::rename(&extracted, output).is_err() {
+                    std::fs::copy(&extracted, output)?;
+                    std::fs::remove_file(&extracted)?;
+                }
+
+                (progress)(file.size, file.size);
+
+                return Ok(true);
+            }
+
+            Some(None) => return Ok(false),
+            None => ()
+        }
+
+        let mut temp_name = output.file_name().unwrap_or_default().to_os_string();
+
+        temp_name.push(".repair");
+
+        let temp = output.with_file_name(temp_name);
+
+        let is_valid = match &self.zip {
+            Some(zip) => {
+                // Clone has its own reading position, so threads don't block each other
+                let mut zip = zip.clone();
+
+                let mut entry = zip.by_name(&name)?;
+
+                write_verified(&mut entry, &temp, file, |hashed| (progress)(hashed, file.size))?
+            }
+
+            None => {
+                let mut entry = Archive::open(&self.path)?.read_entry(&name)?;
+
+                write_verified(&mut entry, &temp, file, |hashed| (progress)(hashed, file.size))?
+            }
+        };
+
+        if !is_valid {
+            tracing::debug!("Archive entry {name} has different hash, expected {}", file.md5);
+
+            return Ok(false);
+        }
+
+        std::fs::rename(temp, output)?;
+
+        Ok(true)
+    }
+}
+
+impl Drop for ArchiveSource {
+    fn drop(&mut self) {
+        // Remove entries extracted for the files repaired by other sources
+        for path in self.extracted.get_mut().unwrap().values().flatten() {
+            #[allow(unused_must_use)] {
+                std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Write the reader's content to the file if it matches the file's hash
+/// 
+/// Return `Ok(false)` and remove the written file if the hash is different
+fn write_verified(reader: &mut dyn Read, path: &Path, file: &IntegrityFile, progress: impl FnMut(u64)) -> std::io::Result<bool> {
+    let mut reader = WritingReader {
+        reader,
+        writer: File::create(path)?
+    };
+
+    let result = super::hash::md5_reader(&mut reader, progress)
+        .and_then(|hash| {
+            reader.writer.flush()?;
+
+            Ok(hash)
+        });
+
+    drop(reader);
+
+    match result {
+        Ok(hash) if hash.eq_ignore_ascii_case(&file.md5) => Ok(true),
+
+        Ok(_) => {
+            std::fs::remove_file(path)?;
+
+            Ok(false)
+        }
+
+        Err(err) => {
+            let _ = std::fs::remove_file(path);
+
+            Err(err)
+        }
+    }
+}
+
+/// Reader which writes all the read data to the writer
+struct WritingReader<R, W> {
+    reader: R,
+    writer: W
+}
+
+impl<R: Read, W: Write> Read for WritingReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        self.writer.write_all(&buf[..read])?;
+
+        Ok(read)
+    }
+}
+
+/// File which can be read by many readers at once, each with its own position
+#[derive(Debug, Clone)]
+struct SharedFile {
+    file: Arc<File>,
+    position: u64
+}
+
+impl SharedFile {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            file: Arc::new(File::open(path)?),
+            position: 0
+        })
+    }
+}
+
+impl Read for SharedFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read_at(buf, self.position)?;
+
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for SharedFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        };
+
+        let Some(position) = position else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek to a negative or overflowing position"));
+        };
+
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CopyMethod {
+    /// Copy-on-write clone of the file, supported by btrfs, xfs and some other filesystems
+    Reflink,
+
+    /// Both paths point to the same file
+    Hardlink,
+
+    /// File's content was copied
+    Copy
+}
+
+/// Copy file trying to reflink it first, and then to copy its content
+/// 
+/// If `hardlink` is true, hardlink is tried before copying the content.
+/// Parent folders of the `to` path are created, existing file is replaced
+pub fn copy_file(from: impl AsRef<Path>, to: impl AsRef<Path>, hardlink: bool) -> std::io::Result<CopyMethod> {
+    let from = from.as_ref();
+    let to = to.as_ref();
+
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_name = to.file_name().unwrap_or_default().to_os_string();
+
+    temp_name.push(".repair");
+
+    let temp = to.with_file_name(temp_name);
+
+    if temp.exists() {
+        std::fs::remove_file(&temp)?;
+    }
+
+    let method = if reflink(from, &temp).is_ok() {
+        CopyMethod::Reflink
+    }
+
+    else if hardlink && std::fs::hard_link(from, &temp).is_ok() {
+        CopyMethod::Hardlink
+    }
+
+    else {
+        std::fs::copy(from, &temp)?;
+
+        CopyMethod::Copy
+    };
+
+    std::fs::rename(temp, to)?;
+
+    Ok(method)
+}
+
+fn reflink(from: &Path, to: &Path) -> std::io::Result<()> {
+    let source = File::open(from)?;
+    let target = File::create(to)?;
+
+    let result = unsafe {
+        libc::ioctl(target.as_raw_fd(), libc::FICLONE as _, source.as_raw_fd())
+    };
+
+    if result != 0 {
+        let err = std::io::Error::last_os_error();
+
+        drop(target);
+
+        let _ = std::fs::remove_file(to);
+
+        return Err(err);
+    }
+
+    // Keep permissions of the original file
+    target.set_permissions(source.metadata()?.permissions())?;
+
+    Ok(())
+}
+
+/// Write the file using the first source which has its copy
+/// 
+/// Return name of the used source
+pub fn fetch_file(sources: &[Box<dyn RepairSource>], file: &IntegrityFile, output: &Path, progress: impl Fn(u64, u64) + Clone + Send + 'static) -> anyhow::Result<String> {
+    let mut errors = Vec::new();
+
+    for source in sources {
+        match source.fetch(file, output, Box::new(progress.clone())) {
+            Ok(true) => return Ok(source.name()),
+            Ok(false) => tracing::trace!("{} doesn't have a copy of {:?}", source.name(), file.path),
+
+            Err(err) => {
+                tracing::warn!("Failed to fetch {:?} from {}: {err}", file.path, source.name());
+
+                errors.push(format!("{}: {err}", source.name()));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        anyhow::bail!("No repair source has a copy of the file");
+    }
+
+    anyhow::bail!("Failed to fetch the file: {}", errors.join("; "));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+
+    fn prepare(name: &str) -> std::io::Result<PathBuf> {
+        let path = std::env::temp_dir().join(format!(".agc-test-source-{name}"));
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(&path)?;
+
+        Ok(path)
+    }
+
+    fn hello() -> IntegrityFile {
+        IntegrityFile {
+            path: PathBuf::from("folder/file"),
+            md5: String::from("5d41402abc4b2a76b9719d911017c592"),
+            size: 5,
+            base_url: String::new()
+        }
+    }
+
+    #[test]
+    pub fn test_copy_file() -> std::io::Result<()> {
+        let path = prepare("copy-file")?;
+
+        std::fs::write(path.join("from"), "hello")?;
+        std::fs::write(path.join("to"), "old content")?;
+
+        let method = copy_file(path.join("from"), path.join("to"), false)?;
+
+        assert_ne!(method, CopyMethod::Hardlink);
+        assert_eq!(std::fs::read_to_string(path.join("to"))?, "hello");
+        assert_ne!(path.join("from").metadata()?.ino(), path.join("to").metadata()?.ino());
+
+        // Copied file doesn't share its content with the original one
+        std::fs::write(path.join("to"), "world")?;
+
+        assert_eq!(std::fs::read_to_string(path.join("from"))?, "hello");
+
+        let method = copy_file(path.join("from"), path.join("sub/linked"), true)?;
+
+        assert_ne!(method, CopyMethod::Copy);
+        assert_eq!(std::fs::read_to_string(path.join("sub/linked"))?, "hello");
+        assert!(!path.join("sub/linked.repair").exists());
+
+        std::fs::remove_dir_all(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_directory_source() -> anyhow::Result<()> {
+        let path = prepare("directory")?;
+
+        std::fs::create_dir_all(path.join("source/folder"))?;
+        std::fs::write(path.join("source/folder/file"), "hello")?;
+
+        let source = DirectorySource::new(path.join("source"));
+
+        assert!(source.fetch(&hello(), &path.join("output"), Box::new(|_, _| {}))?);
+        assert_eq!(std::fs::read_to_string(path.join("output"))?, "hello");
+
+        // Same size but different hash
+        std::fs::write(path.join("source/folder/file"), "world")?;
+
+        assert!(!source.fetch(&hello(), &path.join("mismatch"), Box::new(|_, _| {}))?);
+        assert!(!path.join("mismatch").exists());
+
+        std::fs::remove_dir_all(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_installation_source() -> anyhow::Result<()> {
+        let path = prepare("installation")?;
+
+        std::fs::create_dir_all(path.join("broken/folder"))?;
+        std::fs::create_dir_all(path.join("valid/folder"))?;
+
+        std::fs::write(path.join("broken/folder/file"), "world")?;
+        std::fs::write(path.join("valid/folder/file"), "hello")?;
+
+        let sources: Vec<Box<dyn RepairSource>> = vec![
+            Box::new(InstallationSource::new(path.join("broken"))),
+            Box::new(InstallationSource::new(path.join("valid")))
+        ];
+
+        assert!(!sources[0].fetch(&hello(), &path.join("output"), Box::new(|_, _| {}))?);
+
+        let name = fetch_file(&sources, &hello(), &path.join("output"), |_, _| {})?;
+
+        assert_eq!(name, sources[1].name());
+        assert_eq!(std::fs::read_to_string(path.join("output"))?, "hello");
+        assert_ne!(path.join("valid/folder/file").metadata()?.ino(), path.join("output").metadata()?.ino());
+
+        assert!(fetch_file(&sources[..1], &hello(), &path.join("output"), |_, _| {}).is_err());
+
+        std::fs::remove_dir_all(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_archive_source() -> anyhow::Result<()> {
+        use crate::repairer::repair::repair_all_from;
+
+        let path = prepare("archive")?;
+
+        let files = [
+            ("game/pkg_version", "{}"),
+            ("game/folder/file", "hello"),
+            ("game/other", "other content")
+        ];
+
+        let mut zip = zip::ZipWriter::new(File::create(path.join("archive.zip"))?);
+
+        for (name, content) in files {
+            zip.start_file(name, zip::write::FileOptions::default())?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        zip.finish()?;
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(File::create(path.join("archive.tar.gz"))?, flate2::Compression::default()));
+
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+
+            builder.append_data(&mut header, name, content.as_bytes())?;
+        }
+
+        builder.into_inner()?.finish()?;
+
+        // Entry with the same size but different content, and a file which is not archived
+        let broken = [
+            hello(),
+            IntegrityFile {
+                path: PathBuf::from("other"),
+                md5: String::from("00000000000000000000000000000000"),
+                size: 13,
+                base_url: String::new()
+            },
+            IntegrityFile {
+                path: PathBuf::from("missing"),
+                md5: String::from("00000000000000000000000000000000"),
+                size: 1,
+                base_url: String::new()
+            }
+        ];
+
+        for name in ["archive.zip", "archive.tar.gz"] {
+            let game_dir = path.join(format!("{name}-game"));
+
+            std::fs::create_dir_all(&game_dir)?;
+
+            let source = ArchiveSource::open(path.join(name))?;
+
+            assert_eq!(source.entry_name(&hello()), "game/folder/file");
+
+            let summary = repair_all_from(&game_dir, broken.clone(), vec![Box::new(source)], 2, |_| {});
+
+            assert_eq!(summary.repaired, vec![hello()]);
+            assert_eq!(summary.failed.len(), 2);
+
+            assert_eq!(std::fs::read_to_string(game_dir.join("folder/file"))?, "hello");
+
+            // Temporary copies of the entries are not left
+            assert_eq!(game_dir.join("folder").read_dir()?.count(), 1);
+            assert_eq!(game_dir.read_dir()?.count(), 1);
+        }
+
+        // Tar entries are extracted in a single pass before fetching
+        let source = ArchiveSource::open(path.join("archive.tar.gz"))?;
+
+        source.prepare(&path.join("prepared"), &broken)?;
+
+        assert_eq!(std::fs::read_to_string(path.join("prepared/folder/file.archive"))?, "hello");
+        assert!(!path.join("prepared/other.archive").exists());
+
+        assert!(source.fetch(&hello(), &path.join("prepared/folder/file"), Box::new(|_, _| {}))?);
+        assert!(!source.fetch(&broken[1], &path.join("prepared/other"), Box::new(|_, _| {}))?);
+
+        assert_eq!(std::fs::read_to_string(path.join("prepared/folder/file"))?, "hello");
+        assert!(!path.join("prepared/folder/file.archive").exists());
+
+        // Entries which weren't fetched are removed with the source
+        source.prepare(&path.join("unused"), &broken)?;
+
+        assert!(path.join("unused/folder/file.archive").exists());
+
+        drop(source);
+
+        assert!(!path.join("unused/folder/file.archive").exists());
+
+        std::fs::remove_dir_all(path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,452 @@
+use std::path::{Path, PathBuf};
+use std::collections::{VecDeque, HashMap};
+use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+
+use serde::{Serialize, Deserialize};
+
+use super::IntegrityFile;
+use super::cache::{VerificationCache, CacheEntry};
+use super::report::{VerificationReport, FileReport, FileStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VerifyMode {
+    /// Compare only files' sizes (`IntegrityFile::fast_verify`)
+    Fast,
+
+    /// Compare files' sizes and hashes (`IntegrityFile::verify`)
+    Full
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VerifyOptions {
+    pub mode: VerifyMode,
+
+    /// Amount of worker threads
+    /// 
+    /// Default is the amount of available CPU cores
+    pub threads: usize
+}
+
+impl Default for VerifyOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            mode: VerifyMode::Full,
+            threads: std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(4)
+        }
+    }
+}
+
+impl VerifyOptions {
+    #[inline]
+    /// Specify files verification mode
+    pub fn with_mode(mut self, mode: VerifyMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    #[inline]
+    /// Specify amount of worker threads
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyUpdate {
+    /// `(checked files, total files, checked bytes)`
+    Progress(u64, u64, u64),
+
+    /// `(file, is valid)`
+    FileVerified(IntegrityFile, bool)
+}
+
+/// Verify files of the game folder using multiple threads
+/// 
+/// Updates are sent from the current thread. Return list of broken files
+/// 
+/// ```no_run
+/// use anime_game_core::repairer::IntegrityFile;
+/// use anime_game_core::repairer::verify::*;
+/// 
+/// let files: Vec<IntegrityFile> = Vec::new();
+/// 
+/// let broken = verify_all("/path/to/game", files, VerifyOptions::default(), |update| {
+///     if let VerifyUpdate::Progress(checked, total, _) = update {
+///         println!("Verified {checked} of {total} files");
+///     }
+/// });
+/// ```
+#[inline]
+pub fn verify_all(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    options: VerifyOptions,
+    updater: impl Fn(VerifyUpdate)
+) -> Vec<IntegrityFile> {
+    verify_report(game_dir, files, options, updater).into_broken()
+}
+
+/// Verify files of the game folder using multiple threads
+/// 
+/// Updates are sent from the current thread. Return report with all the verified files
+#[tracing::instrument(level = "debug", skip(files, updater))]
+pub fn verify_report(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    options: VerifyOptions,
+    updater: impl Fn(VerifyUpdate)
+) -> VerificationReport {
+    let game_dir = game_dir.as_ref().to_path_buf();
+
+    let files = files.into_iter().collect::<VecDeque<_>>();
+    let total = files.len() as u64;
+
+    let queue = Arc::new(Mutex::new(files));
+
+    let (send, recv) = std::sync::mpsc::channel();
+
+    let workers = (0..options.threads.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let send = send.clone();
+            let game_dir = game_dir.clone();
+
+            std::thread::spawn(move || {
+                loop {
+                    // Don't hold the lock while the file is being verified
+                    let Some(file) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+
+                    if send.send(file.check(&game_dir, options.mode)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    drop(send);
+
+    let mut report = VerificationReport::default();
+
+    let mut checked = 0;
+    let mut bytes = 0;
+
+    (updater)(VerifyUpdate::Progress(0, total, 0));
+
+    while let Ok(file_report) = recv.recv() {
+        checked += 1;
+        bytes += file_report.file.size;
+
+        (updater)(VerifyUpdate::FileVerified(file_report.file.clone(), file_report.is_ok()));
+        (updater)(VerifyUpdate::Progress(checked, total, bytes));
+
+        report.push(file_report);
+    }
+
+    for worker in workers {
+        if let Err(err) = worker.join() {
+            tracing::error!("Verification worker panicked: {err:?}");
+        }
+    }
+
+    report.sort();
+
+    report
+}
+
+/// Verify files of the game folder, skipping the ones which weren't changed since their last verification
+/// 
+/// Only `VerifyMode::Full` uses the cache. Verified files are added to the cache
+/// and it's saved when the verification is finished. Return list of broken files
+#[inline]
+pub fn verify_all_cached(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    options: VerifyOptions,
+    cache: &mut VerificationCache,
+    updater: impl Fn(VerifyUpdate)
+) -> Vec<IntegrityFile> {
+    verify_report_cached(game_dir, files, options, cache, updater).into_broken()
+}
+
+/// Verify files of the game folder, skipping the ones which weren't changed since their last verification
+/// 
+/// Only `VerifyMode::Full` uses the cache. Verified files are added to the cache
+/// and it's saved when the verification is finished. Return report with all the verified files
+#[tracing::instrument(level = "debug", skip(files, cache, updater))]
+pub fn verify_report_cached(
+    game_dir: impl AsRef<Path> + std::fmt::Debug,
+    files: impl IntoIterator<Item = IntegrityFile>,
+    options: VerifyOptions,
+    cache: &mut VerificationCache,
+    updater: impl Fn(VerifyUpdate)
+) -> VerificationReport {
+    if options.mode == VerifyMode::Fast {
+        return verify_report(game_dir, files, options, updater);
+    }
+
+    let game_dir = game_dir.as_ref();
+
+    let (cached, unchecked): (Vec<_>, Vec<_>) = files.into_iter()
+        .partition(|file| cache.is_verified(game_dir, file));
+
+    tracing::debug!("Skipping {} cached files", cached.len());
+
+    let total = (cached.len() + unchecked.len()) as u64;
+
+    let cached_files = cached.len() as u64;
+    let cached_bytes = cached.iter().map(|file| file.size).sum::<u64>();
+
+    let mut cached_report = VerificationReport::default();
+
+    for file in cached {
+        (updater)(VerifyUpdate::FileVerified(file.clone(), true));
+
+        cached_report.push(FileReport {
+            actual_size: Some(file.size),
+            actual_md5: Some(file.md5.to_ascii_lowercase()),
+            status: FileStatus::Ok,
+            file
+        });
+    }
+
+    // Metadata must be captured before hashing, otherwise changes
+    // made during the verification would be trusted by the cache
+    let entries = unchecked.iter()
+        .filter_map(|file| {
+            CacheEntry::new(game_dir.join(&file.path), &file.md5)
+                .map(|entry| (file.path.clone(), entry))
+        })
+        .collect::<HashMap<PathBuf, CacheEntry>>();
+
+    let entries = RefCell::new(entries);
+
+    let cache = RefCell::new(cache);
+
+    let mut report = verify_report(game_dir, unchecked, options, |update| {
+        match update {
+            VerifyUpdate::Progress(checked, _, bytes) => {
+                (updater)(VerifyUpdate::Progress(cached_files + checked, total, cached_bytes + bytes));
+            }
+
+            VerifyUpdate::FileVerified(file, valid) => {
+                let entry = entries.borrow_mut().remove(&file.path);
+
+                match entry {
+                    Some(entry) if valid => cache.borrow_mut().insert(&file, entry),
+
+                    _ => {
+                        cache.borrow_mut().remove(&file.path);
+                    }
+                }
+
+                (updater)(VerifyUpdate::FileVerified(file, valid));
+            }
+        }
+    });
+
+    if let Err(err) = cache.borrow().save() {
+        tracing::warn!("Failed to save verification cache: {err}");
+    }
+
+    report.merge(cached_report);
+    report.sort();
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::SkipRules;
+    use super::super::report::ExtraneousFile;
+    use super::super::hash::md5_reader;
+
+    fn integrity_file(path: &str, content: &str) -> std::io::Result<IntegrityFile> {
+        Ok(IntegrityFile {
+            path: PathBuf::from(path),
+            md5: md5_reader(content.as_bytes(), |_| {})?,
+            size: content.len() as u64,
+            base_url: String::new()
+        })
+    }
+
+    #[test]
+    pub fn test_verify_report() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-verify-report");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(path.join("data"))?;
+        std::fs::create_dir_all(path.join("ScreenShot"))?;
+
+        std::fs::write(path.join("data/ok"), "ok")?;
+        std::fs::write(path.join("data/size"), "wrong size")?;
+        std::fs::write(path.join("data/hash"), "HASH")?;
+        std::fs::write(path.join("data/extra"), "extra")?;
+        std::fs::write(path.join("ScreenShot/image.png"), "image")?;
+
+        // Leftovers of the interrupted repairing
+        std::fs::write(path.join("data/hash.archive"), "hash")?;
+        std::fs::write(path.join("data/size.repair"), "size")?;
+
+        let files = vec![
+            integrity_file("data/ok", "ok")?,
+            integrity_file("data/missing", "missing")?,
+            integrity_file("data/size", "size")?,
+            integrity_file("data/hash", "hash")?
+        ];
+
+        let paths = |reports: &[FileReport]| reports.iter()
+            .map(|report| report.file.path.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        let options = VerifyOptions::default().with_threads(2);
+
+        let mut report = verify_report(&path, files.clone(), options, |_| {});
+
+        assert_eq!(paths(&report.ok), ["data/ok"]);
+        assert_eq!(paths(&report.missing), ["data/missing"]);
+        assert_eq!(paths(&report.size_mismatch), ["data/size"]);
+        assert_eq!(paths(&report.hash_mismatch), ["data/hash"]);
+
+        assert_eq!(report.size_mismatch[0].actual_size, Some(10));
+        assert_eq!(report.missing[0].actual_size, None);
+
+        assert!(!report.is_ok());
+        assert_eq!(report.checked(), 4);
+
+        // Fast mode doesn't compare hashes
+        let fast = verify_report(&path, files.clone(), options.with_mode(VerifyMode::Fast), |_| {});
+
+        assert_eq!(paths(&fast.ok), ["data/hash", "data/ok"]);
+        assert!(fast.hash_mismatch.is_empty());
+
+        report.find_extraneous(&path, &SkipRules::from_globs(&["ScreenShot"]))?;
+
+        assert_eq!(report.extraneous, vec![ExtraneousFile {
+            path: PathBuf::from("data/extra"),
+            size: 5
+        }]);
+
+        // Reports of separately verified files are merged into the same buckets
+        let mut merged = verify_report(&path, files[..2].to_vec(), options, |_| {});
+
+        merged.merge(verify_report(&path, files[2..].to_vec(), options, |_| {}));
+        merged.find_extraneous(&path, &SkipRules::from_globs(&["ScreenShot"]))?;
+        merged.sort();
+
+        assert_eq!(merged, report);
+
+        let mut broken = report.into_broken();
+
+        broken.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(broken, vec![files[3].clone(), files[1].clone(), files[2].clone()]);
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_verify_all() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(".agc-test-verify-all");
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        std::fs::create_dir_all(&path)?;
+
+        let files = (0..8)
+            .map(|i| integrity_file(&format!("file-{i}"), &"content".repeat(i + 1)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        for file in &files {
+            std::fs::write(path.join(&file.path), "content".repeat(file.size as usize / 7))?;
+        }
+
+        // Same size, different content
+        std::fs::write(path.join("file-3"), "CONTENT".repeat(4))?;
+
+        let total_bytes = files.iter().map(|file| file.size).sum::<u64>();
+
+        let updates = Mutex::new(Vec::new());
+
+        let options = VerifyOptions::default().with_threads(4);
+
+        // Fast mode only compares sizes
+        let broken = verify_all(&path, files.clone(), options.with_mode(VerifyMode::Fast), |update| {
+            updates.lock().unwrap().push(update);
+        });
+
+        assert!(broken.is_empty());
+
+        let progress = |updates: &[VerifyUpdate]| updates.iter()
+            .filter_map(|update| match update {
+                VerifyUpdate::Progress(checked, total, bytes) => Some((*checked, *total, *bytes)),
+                VerifyUpdate::FileVerified(_, _) => None
+            })
+            .collect::<Vec<_>>();
+
+        let fast_progress = progress(&updates.lock().unwrap());
+
+        assert_eq!(fast_progress.first(), Some(&(0, 8, 0)));
+        assert_eq!(fast_progress.last(), Some(&(8, 8, total_bytes)));
+
+        // Full mode catches the corrupted file
+        updates.lock().unwrap().clear();
+
+        let broken = verify_all(&path, files.clone(), options, |update| {
+            updates.lock().unwrap().push(update);
+        });
+
+        assert_eq!(broken, vec![files[3].clone()]);
+
+        let full_progress = progress(&updates.lock().unwrap());
+
+        assert_eq!(full_progress.len(), 9);
+        assert_eq!(full_progress.last(), Some(&(8, 8, total_bytes)));
+
+        // Cached verification remembers only valid files
+        let mut cache = VerificationCache::open(&path, crate::version::Version::new(1, 0, 0));
+
+        updates.lock().unwrap().clear();
+
+        let broken = verify_all_cached(&path, files.clone(), options, &mut cache, |update| {
+            updates.lock().unwrap().push(update);
+        });
+
+        assert_eq!(broken, vec![files[3].clone()]);
+        assert_eq!(cache.len(), 7);
+        assert_eq!(progress(&updates.lock().unwrap()).last(), Some(&(8, 8, total_bytes)));
+
+        assert!(!cache.is_verified(&path, &files[3]));
+
+        // Cached files are still reported in the progress
+        updates.lock().unwrap().clear();
+
+        let broken = verify_all_cached(&path, files.clone(), options, &mut cache, |update| {
+            updates.lock().unwrap().push(update);
+        });
+
+        assert_eq!(broken, vec![files[3].clone()]);
+        assert_eq!(progress(&updates.lock().unwrap()).last(), Some(&(8, 8, total_bytes)));
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
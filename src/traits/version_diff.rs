@@ -2,6 +2,12 @@ use std::path::Path;
 
 use crate::version::Version;
 
+#[cfg(feature = "install")]
+use crate::installer::hooks::InstallHooks;
+
+#[cfg(feature = "install")]
+use crate::installer::event_log::{EventLog, EventKind, LoggedEvent};
+
 pub trait VersionDiffExt {
     /// Type that will be used as downloading / unpacking / installation error
     type Error;
@@ -94,6 +100,48 @@ pub trait VersionDiffExt {
     }
 
     #[cfg(feature = "install")]
+    /// Try to install the difference into the path returned by `Self::installation_path` method,
+    /// running given hooks around the installation
+    /// 
+    /// This method can fail if installation path is not provided
+    fn install_with_hooks(&self, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        let path = self.installation_path()
+            .expect("Difference installation path is not provided");
+
+        self.install_to_with_hooks(path, hooks, updater)
+    }
+
+    #[cfg(feature = "install")]
+    #[inline]
     /// Try to install the difference by given location
-    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error>;
+    fn install_to(&self, path: impl AsRef<Path>, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error> {
+        self.install_to_with_hooks(path, &(), updater)
+    }
+
+    #[cfg(feature = "install")]
+    /// Try to install the difference by given location, running given hooks around the installation
+    /// and writing all the updates to the event log
+    /// 
+    /// Returned error is logged as well
+    fn install_to_with_log(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, event_log: &EventLog, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error>
+    where
+        Self::Update: LoggedEvent + 'static,
+        Self::Error: std::fmt::Display
+    {
+        let result = self.install_to_with_hooks(path, hooks, event_log.updater(updater));
+
+        if let Err(err) = &result {
+            if let Err(log_err) = event_log.write(EventKind::Error, err.to_string()) {
+                tracing::warn!("Failed to write event log: {log_err}");
+            }
+        }
+
+        result
+    }
+
+    #[cfg(feature = "install")]
+    /// Try to install the difference by given location, running given hooks around the installation
+    /// 
+    /// Installation is cancelled if any hook returns an error
+    fn install_to_with_hooks(&self, path: impl AsRef<Path>, hooks: &impl InstallHooks, updater: impl Fn(Self::Update) + Clone + Send + 'static) -> Result<(), Self::Error>;
 }
@@ -1,91 +1,340 @@
 use serde::{Serialize, Deserialize};
 
 use std::fmt::{Debug, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VersionParseError {
+    #[error("Incorrect version format: {0:?}")]
+    IncorrectFormat(String),
+
+    #[error("Incorrect version component {component:?} in {version:?}")]
+    IncorrectComponent {
+        version: String,
+        component: String
+    }
+}
+
+/// Game version in `major.minor.patch[.revision][+build]` format
+/// 
+/// Most of the games use only three components, but some of them
+/// (e.g. PGR and other Kuro titles) have longer versions
+/// 
+/// Build number is ignored when versions are compared, like in semver
+/// 
+/// ```
+/// use anime_game_core::prelude::Version;
+/// 
+/// let version: Version = "2.10.300.1+4567".parse().unwrap();
+/// 
+/// assert_eq!(version.major, 2);
+/// assert_eq!(version.patch, 300);
+/// assert_eq!(version.revision, Some(1));
+/// assert_eq!(version.build, Some(4567));
+/// 
+/// assert_eq!(version, "2.10.300.1+4567");
+/// assert_eq!(version, "2.10.300.1");
+/// ```
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "VersionRepr", into = "String")]
 pub struct Version {
-    pub version: [u8; 3]
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+
+    /// Optional fourth component
+    pub revision: Option<u32>,
+
+    /// Optional build number, written after the `+` sign
+    pub build: Option<u32>
 }
 
 impl Version {
     #[inline]
-    pub fn new(a: u8, b: u8, c: u8) -> Self {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
         Self {
-            version: [a, b, c]
+            major,
+            minor,
+            patch,
+            revision: None,
+            build: None
         }
     }
 
-    #[allow(clippy::should_implement_trait)]
-    /// Get `Version` from the string
+    #[inline]
+    /// Set fourth version component
+    pub fn with_revision(mut self, revision: u32) -> Self {
+        self.revision = Some(revision);
+
+        self
+    }
+
+    #[inline]
+    /// Set build number
+    pub fn with_build(mut self, build: u32) -> Self {
+        self.build = Some(build);
+
+        self
+    }
+
+    /// Get `Version` from the `.version` file content
+    /// 
+    /// Three bytes files store `major`, `minor` and `patch` components as raw bytes.
+    /// Longer files store version in the string form
     /// 
     /// ```
     /// use anime_game_core::prelude::Version;
     /// 
-    /// let version = Version::from_str("1.10.2").expect("Failed to parse version string");
+    /// assert_eq!(Version::from_bytes(&[1, 2, 3]).unwrap(), "1.2.3");
+    /// assert_eq!(Version::from_bytes(b"1.2.300").unwrap(), "1.2.300");
     /// ```
-    pub fn from_str<T: AsRef<str>>(str: T) -> Option<Self> {
-        let parts = str.as_ref().split('.').collect::<Vec<&str>>();
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VersionParseError> {
+        if let [major, minor, patch] = bytes {
+            return Ok(Self::new(*major as u32, *minor as u32, *patch as u32));
+        }
 
-        if parts.len() != 3 {
-            return None;
+        match std::str::from_utf8(bytes) {
+            Ok(version) => version.trim().parse(),
+            Err(_) => Err(VersionParseError::IncorrectFormat(String::from_utf8_lossy(bytes).to_string()))
         }
+    }
 
-        if let (Ok(a), Ok(b), Ok(c)) = (parts[0].parse(), parts[1].parse(), parts[2].parse()) {
-            return Some(Version::new(a, b, c));
+    /// Convert `Version` to the `.version` file content
+    /// 
+    /// Versions which fit the old three bytes format are stored in it,
+    /// so they still can be read by older versions of the library
+    /// 
+    /// ```
+    /// use anime_game_core::prelude::Version;
+    /// 
+    /// assert_eq!(Version::new(1, 2, 3).to_bytes(), vec![1, 2, 3]);
+    /// assert_eq!(Version::new(1, 2, 300).to_bytes(), b"1.2.300".to_vec());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let is_short = self.revision.is_none() && self.build.is_none();
+
+        match (u8::try_from(self.major), u8::try_from(self.minor), u8::try_from(self.patch)) {
+            (Ok(major), Ok(minor), Ok(patch)) if is_short => vec![major, minor, patch],
+
+            _ => self.to_string().into_bytes()
         }
+    }
 
-        None
+    /// Read `Version` from the `.version` file
+    pub fn read_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self::from_bytes(&std::fs::read(path)?)?)
+    }
+
+    #[inline]
+    /// Write `Version` to the `.version` file
+    pub fn write_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
     }
 
     /// Converts `Version` struct to plain format (e.g. "123")
     /// 
+    /// Build number is not included
+    /// 
     /// ```
     /// use anime_game_core::prelude::Version;
     /// 
     /// assert_eq!(Version::new(1, 2, 3).to_plain_string(), "123");
+    /// assert_eq!(Version::new(1, 2, 3).with_revision(4).to_plain_string(), "1234");
     /// ```
     pub fn to_plain_string(&self) -> String {
-        format!("{}{}{}", self.version[0], self.version[1], self.version[2])
+        match self.revision {
+            Some(revision) => format!("{}{}{}{revision}", self.major, self.minor, self.patch),
+            None => format!("{}{}{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = VersionParseError;
+
+    /// Get `Version` from the string
+    /// 
+    /// ```
+    /// use anime_game_core::prelude::Version;
+    /// 
+    /// let version: Version = "1.10.2".parse().expect("Failed to parse version string");
+    /// ```
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let (version, build) = match str.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (str, None)
+        };
+
+        let parse = |component: &str| {
+            // Unlike `u32::from_str` don't allow `+` sign
+            if component.is_empty() || !component.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(VersionParseError::IncorrectComponent {
+                    version: str.to_string(),
+                    component: component.to_string()
+                });
+            }
+
+            component.parse::<u32>().map_err(|_| VersionParseError::IncorrectComponent {
+                version: str.to_string(),
+                component: component.to_string()
+            })
+        };
+
+        let parts = version.split('.').collect::<Vec<&str>>();
+
+        let (major, minor, patch, revision) = match parts.as_slice() {
+            [major, minor, patch] => (parse(major)?, parse(minor)?, parse(patch)?, None),
+            [major, minor, patch, revision] => (parse(major)?, parse(minor)?, parse(patch)?, Some(parse(revision)?)),
+
+            _ => return Err(VersionParseError::IncorrectFormat(str.to_string()))
+        };
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            revision,
+            build: build.map(parse).transpose()?
+        })
+    }
+}
+
+impl TryFrom<&str> for Version {
+    type Error = VersionParseError;
+
+    #[inline]
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = VersionParseError;
+
+    #[inline]
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Version> for String {
+    #[inline]
+    fn from(version: Version) -> Self {
+        version.to_string()
+    }
+}
+
+/// Serialized `Version` forms
+/// 
+/// Older versions of the library serialized `Version` as `{ "version": [1, 2, 3] }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VersionRepr {
+    String(String),
+
+    Legacy {
+        version: [u8; 3]
+    }
+}
+
+impl TryFrom<VersionRepr> for Version {
+    type Error = VersionParseError;
+
+    fn try_from(value: VersionRepr) -> Result<Self, Self::Error> {
+        match value {
+            VersionRepr::String(version) => version.parse(),
+            VersionRepr::Legacy { version: [major, minor, patch] } => Ok(Self::new(major as u32, minor as u32, patch as u32))
+        }
     }
 }
 
 impl Debug for Version {
+    #[inline]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}.{}", self.version[0], self.version[1], self.version[2])
+        Display::fmt(self, f)
     }
 }
 
 impl Display for Version {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}.{}", self.version[0], self.version[1], self.version[2])
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(revision) = self.revision {
+            write!(f, ".{revision}")?;
+        }
+
+        if let Some(build) = self.build {
+            write!(f, "+{build}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Version {
+    #[inline]
+    /// Components compared by `PartialEq`, `Ord` and `Hash`
+    fn precedence(&self) -> (u32, u32, u32, Option<u32>) {
+        (self.major, self.minor, self.patch, self.revision)
+    }
+}
+
+impl PartialEq for Version {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.precedence() == other.precedence()
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    #[inline]
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.precedence().cmp(&other.precedence())
+    }
+}
+
+impl std::hash::Hash for Version {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.precedence().hash(state);
     }
 }
 
 impl PartialEq<String> for Version {
     #[inline]
     fn eq(&self, other: &String) -> bool {
-        &self.to_string() == other
+        self == &other.as_str()
     }
 }
 
 impl PartialEq<Version> for String {
     #[inline]
     fn eq(&self, other: &Version) -> bool {
-        self == &other.to_string()
+        other == self
     }
 }
 
 impl PartialEq<&str> for Version {
     #[inline]
     fn eq(&self, other: &&str) -> bool {
-        &self.to_string() == other
+        matches!(Version::from_str(other), Ok(other) if *self == other)
     }
 }
 
 impl PartialEq<Version> for &str {
     #[inline]
     fn eq(&self, other: &Version) -> bool {
-        self == &other.to_string()
+        other == self
     }
 }
 
@@ -99,7 +348,7 @@ mod tests {
 
         assert_eq!(version, "0.0.0");
         assert_eq!(version, "0.0.0".to_string());
-        assert_eq!(Some(version), Version::from_str("0.0.0"));
+        assert_eq!(Ok(version), "0.0.0".parse());
         assert_eq!(version.to_plain_string(), "000".to_string());
     }
 
@@ -107,7 +356,7 @@ mod tests {
     pub fn test_version_from_str() {
         let version = Version::from_str("0.0.0");
 
-        assert!(version.is_some());
+        assert!(version.is_ok());
 
         let version = version.unwrap();
 
@@ -119,9 +368,9 @@ mod tests {
 
     #[test]
     pub fn test_version_long() {
-        let version = Version::from_str("100.0.255");
+        let version = Version::try_from("100.0.255");
 
-        assert!(version.is_some());
+        assert!(version.is_ok());
 
         let version = version.unwrap();
 
@@ -131,10 +380,77 @@ mod tests {
         assert_eq!(version.to_plain_string(), "1000255".to_string());
     }
 
+    #[test]
+    pub fn test_version_wide() {
+        let version = Version::from_str("2.1000.70000.5+123456").unwrap();
+
+        assert_eq!(version, Version::new(2, 1000, 70000).with_revision(5).with_build(123456));
+        assert_eq!(version, "2.1000.70000.5+123456");
+
+        assert!(Version::new(1, 2, 3).with_revision(0) > Version::new(1, 2, 3));
+        assert!(Version::new(1, 2, 300) > Version::new(1, 2, 255));
+    }
+
+    #[test]
+    pub fn test_version_build() {
+        let version = Version::from_str("1.2.3+456").unwrap();
+
+        assert_eq!(version.build, Some(456));
+        assert_eq!(version, Version::new(1, 2, 3));
+        assert_eq!(version, "1.2.3");
+        assert_eq!(version, Version::new(1, 2, 3).with_build(789));
+
+        assert_eq!(version.cmp(&Version::new(1, 2, 3)), std::cmp::Ordering::Equal);
+        assert!(version < Version::new(1, 2, 4));
+        assert!(version < Version::new(1, 2, 3).with_revision(0));
+
+        assert_ne!(version, Version::new(1, 2, 3).with_revision(456));
+        assert_ne!(version, "1.2.3.456");
+
+        let versions = std::collections::HashSet::from([version, Version::new(1, 2, 3)]);
+
+        assert_eq!(versions.len(), 1);
+    }
+
+    #[test]
+    pub fn test_version_bytes() {
+        let versions = [
+            Version::new(4, 0, 0),
+            Version::new(255, 255, 255),
+            Version::new(1, 2, 256),
+            Version::new(1, 2, 3).with_revision(4),
+            Version::new(1, 2, 3).with_build(5)
+        ];
+
+        for version in versions {
+            assert_eq!(Version::from_bytes(&version.to_bytes()), Ok(version));
+        }
+
+        assert_eq!(Version::new(4, 0, 0).to_bytes(), vec![4, 0, 0]);
+        assert!(Version::from_bytes(&[1, 2]).is_err());
+    }
+
+    #[test]
+    pub fn test_version_serde() -> Result<(), serde_json::Error> {
+        let version = Version::new(1, 2, 3).with_revision(4);
+
+        assert_eq!(serde_json::to_string(&version)?, r#""1.2.3.4""#);
+        assert_eq!(serde_json::from_str::<Version>(r#""1.2.3.4""#)?, version);
+        assert_eq!(serde_json::from_str::<Version>(r#"{"version":[1,2,3]}"#)?, Version::new(1, 2, 3));
+
+        assert!(serde_json::from_str::<Version>(r#""1.2""#).is_err());
+
+        Ok(())
+    }
+
     #[test]
     pub fn test_incorrect_versions() {
-        assert_eq!(Version::from_str(""), None);
-        assert_eq!(Version::from_str("..0"), None);
-        assert_eq!(Version::from_str("0.0."), None);
+        assert!(Version::from_str("").is_err());
+        assert!(Version::from_str("..0").is_err());
+        assert!(Version::from_str("0.0.").is_err());
+        assert!(Version::from_str("0.0.0.0.0").is_err());
+        assert!(Version::from_str("0.+1.0").is_err());
+        assert!(Version::from_str("0.0.0+").is_err());
+        assert!(Version::from_str("0.0.99999999999").is_err());
     }
 }